@@ -0,0 +1,415 @@
+//! ## Checking a whole module in one go.
+//!
+//! This is the single entry point for front-ends (like a CLI or language
+//! server) that want every problem with a module: it loads the module and
+//! everything it imports, runs the structural checks, and lowers each
+//! definition (against those of the imported modules), collecting the errors
+//! from every phase.
+
+use crate::errors::{Code, LabeledError, SimpleError};
+use crate::loader::{module_name, LoadedModule, Loader, ModuleGraph, STD_FILEPATH};
+use crate::lower::{define_qualified, Defs, Modules};
+use crate::nbe;
+use crate::source::{Source, Span};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// A result, along with the errors (and warnings) encountered while producing
+/// it.
+#[derive(Debug)]
+pub struct WithErrors<T, E = SimpleError> {
+    pub result: T,
+    pub errors: Vec<E>,
+}
+
+impl<T: Default, E> Default for WithErrors<T, E> {
+    fn default() -> Self {
+        WithErrors {
+            result: T::default(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// The definitions of a module that were successfully lowered, named by
+/// alias. Each is evaluated (on demand) against the definitions in scope
+/// where it's made, including those it imports.
+#[derive(Debug, Default)]
+pub struct CheckedModule {
+    pub defs: Defs,
+}
+
+/// Parses and checks the module in `src`, lowering each of its definitions.
+/// Its imports are read relative to `src`'s filename, and checked too (though
+/// only their errors that concern `src`, like an unreadable import, are
+/// produced). Errors from every phase are produced together, ordered by
+/// where they start.
+pub fn check_module(src: &Source) -> WithErrors<CheckedModule, LabeledError> {
+    let loader = Loader::new(|path: &Path| fs::read_to_string(path));
+    let graph = loader.load_source(Path::new(&src.filename), src.text.clone());
+    let entry = graph.entry.clone();
+    check_graph(graph)
+        .remove(&entry)
+        .expect("the entry module is always loaded")
+}
+
+/// The standard library's modules, for lowering terms that refer to them
+/// qualified (e.g. `Std.K`) without importing them.
+pub fn std_modules() -> Modules {
+    let read = |_: &Path| Err(io::Error::from(io::ErrorKind::NotFound));
+    let graph = Loader::new(read)
+        .load(Path::new(STD_FILEPATH))
+        .expect("the standard library is embedded");
+
+    check_graph(graph)
+        .into_iter()
+        .map(|(path, checked)| (Rc::new(module_name(&path)), checked.result.defs))
+        .collect()
+}
+
+/// Checks every module in `graph`, each against the definitions of the
+/// modules it imports. Each module's definitions and errors (ordered by where
+/// they start) are keyed by its path.
+pub fn check_graph(
+    graph: ModuleGraph,
+) -> HashMap<PathBuf, WithErrors<CheckedModule, LabeledError>> {
+    let mut remaining = graph.modules;
+    let mut checked = HashMap::new();
+    let paths: Vec<PathBuf> = remaining.keys().cloned().collect();
+    for path in paths {
+        check_in_order(&path, &mut remaining, &mut checked);
+    }
+
+    checked
+        .into_iter()
+        .map(|(path, checked)| (path, checked.result))
+        .collect()
+}
+
+/// A checked module, along with the aliases it defines (whether or not their
+/// definitions could be lowered).
+struct Checked {
+    result: WithErrors<CheckedModule, LabeledError>,
+    defined: HashSet<Rc<String>>,
+}
+
+impl Checked {
+    /// The definitions that importers of this module may refer to. Aliases
+    /// whose definitions failed to lower (which has already been reported)
+    /// become globals, so that their importers aren't swamped with errors.
+    fn exports(&self) -> Defs {
+        let mut exports = self.result.result.defs.clone();
+        for alias in &self.defined {
+            let name = nbe::Name::new(alias.as_str());
+            if !exports.contains(&name) {
+                let global = nbe::Value::stuck(nbe::Stuck::global(name.clone()));
+                exports.insert(name, global);
+            }
+        }
+        exports
+    }
+}
+
+/// Checks the module at `path` (if it hasn't been already), after checking
+/// the modules it imports. Import cycles have already been broken by the
+/// loader, so this terminates.
+fn check_in_order(
+    path: &Path,
+    remaining: &mut HashMap<PathBuf, LoadedModule>,
+    checked: &mut HashMap<PathBuf, Checked>,
+) {
+    let loaded = match remaining.remove(path) {
+        Some(loaded) => loaded,
+        None => return,
+    };
+    for imported in loaded.imports.iter().flatten() {
+        check_in_order(imported, remaining, checked);
+    }
+
+    let result = check_loaded(loaded, checked);
+    checked.insert(path.to_path_buf(), result);
+}
+
+fn check_loaded(loaded: LoadedModule, checked: &HashMap<PathBuf, Checked>) -> Checked {
+    let LoadedModule {
+        source,
+        module,
+        imports,
+        errors,
+    } = loaded;
+    let mut errors: Vec<LabeledError> = errors.into_iter().map(LabeledError::from).collect();
+
+    if module.is_empty() {
+        let span = Span::new(0, source.text.len());
+        let hint = SimpleError::hint("module contains no definitions", span);
+        errors.push(hint.with_code(Code::EmptyModule).into());
+    }
+    // Vars among the imported names have already been reported by the parser
+    errors.extend(module.check_duplicate_defs());
+
+    let mut in_scope = Defs::new();
+    let mut modules = Modules::new();
+    for (import, path) in module.imports.iter().zip(&imports) {
+        // Imports that weren't loaded have already been reported
+        let (filepath, path, imported) = match (&import.filepath, path) {
+            (Some(filepath), Some(path)) => (filepath, path, &checked[path]),
+            _ => continue,
+        };
+        let exports = imported.exports();
+
+        for alias in import.aliases.iter().filter(|alias| !alias.is_bad()) {
+            let name = nbe::Name::new(alias.text.as_str());
+            match exports.get(&name) {
+                Some(def) => in_scope.insert(name, def),
+                None => {
+                    let message = format!("\"{}\" has no alias `{}`", filepath.text, alias.text);
+                    let error = SimpleError::new(message, alias.span.clone());
+                    errors.push(error.with_code(Code::UnknownAlias).into());
+                }
+            }
+        }
+        modules.insert(Rc::new(module_name(path)), exports);
+    }
+    define_qualified(&mut in_scope, &modules);
+
+    let mut defined = HashSet::new();
+    let mut checked = CheckedModule::default();
+    for def in &module.defs {
+        let body = match &def.body {
+            Some(body) => body,
+            None => continue,
+        };
+        errors.extend(body.warn_shadowed());
+
+        let alias = match &def.alias {
+            Some(alias) if !alias.is_bad() => alias,
+            _ => continue,
+        };
+        defined.insert(Rc::clone(&alias.text));
+        match body.lower_with_modules(&in_scope, &modules) {
+            Ok(term) => {
+                let name = nbe::Name::new(alias.text.as_str());
+                in_scope.define(name.clone(), term);
+                let value = in_scope.get(&name).expect("`name` was just defined");
+                checked.defs.insert(name, value);
+            }
+            Err(lower_errors) => errors.extend(lower_errors.into_iter().map(LabeledError::from)),
+        }
+    }
+
+    errors.sort_by_key(|error| error.span().start);
+    Checked {
+        result: WithErrors {
+            result: checked,
+            errors,
+        },
+        defined,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Severity;
+
+    fn check(text: &str) -> WithErrors<CheckedModule, LabeledError> {
+        check_module(&Source::anonymous(text))
+    }
+
+    #[test]
+    fn lowers_every_def_of_a_valid_module() {
+        let WithErrors { result, errors } = check("True = (t, f) => t; Id = x => x; T = Id True;");
+
+        assert!(errors.is_empty());
+        assert_eq!(norm_of(&result, "T"), "(t, f) => t");
+    }
+
+    fn check_all(
+        files: &[(&str, &str)],
+        entry: &str,
+    ) -> HashMap<PathBuf, WithErrors<CheckedModule, LabeledError>> {
+        let files: HashMap<PathBuf, String> = files
+            .iter()
+            .map(|(path, text)| (PathBuf::from(path), text.to_string()))
+            .collect();
+        let read = |path: &Path| {
+            files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        };
+        check_graph(Loader::new(read).load(Path::new(entry)).unwrap())
+    }
+
+    fn check_files(files: &[(&str, &str)], entry: &str) -> WithErrors<CheckedModule, LabeledError> {
+        check_all(files, entry).remove(Path::new(entry)).unwrap()
+    }
+
+    fn norm_of(checked: &CheckedModule, alias: &str) -> String {
+        checked
+            .defs
+            .get(&nbe::Name::new(alias))
+            .unwrap()
+            .quote()
+            .to_string()
+    }
+
+    #[test]
+    fn imported_aliases_lower_to_their_definitions() {
+        let WithErrors { result, errors } = check_files(
+            &[
+                ("main.lmy", r#"import { Zero } from "./nats"; Z = Zero;"#),
+                ("nats.lmy", "Zero = (s, z) => z;"),
+            ],
+            "main.lmy",
+        );
+
+        assert!(errors.is_empty());
+        assert_eq!(norm_of(&result, "Z"), "(s, z) => z");
+    }
+
+    #[test]
+    fn qualified_names_refer_to_imported_modules() {
+        let WithErrors { result, errors } = check_files(
+            &[
+                (
+                    "main.lmy",
+                    r#"import { K } from "./general-purpose"; import { K } from "./other";
+                       A = GeneralPurpose.K GeneralPurpose.I; B = Other.K;"#,
+                ),
+                ("general-purpose.lmy", "I = x => x; K = (x, y) => x;"),
+                ("other.lmy", "K = (x, y) => y;"),
+            ],
+            "main.lmy",
+        );
+
+        assert!(errors.is_empty());
+        assert_eq!(norm_of(&result, "A"), "(y, x) => x");
+        assert_eq!(norm_of(&result, "B"), "(x, y) => y");
+    }
+
+    #[test]
+    fn reports_aliases_missing_from_imported_modules() {
+        let WithErrors { errors, .. } = check_files(
+            &[
+                (
+                    "main.lmy",
+                    r#"import { Two } from "./nats"; T = Nats.Three;"#,
+                ),
+                ("nats.lmy", "Zero = (s, z) => z;"),
+            ],
+            "main.lmy",
+        );
+
+        let messages: Vec<&str> = errors.iter().map(LabeledError::message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                r#""./nats" has no alias `Two`"#,
+                "module `Nats` has no alias `Three`"
+            ]
+        );
+    }
+
+    #[test]
+    fn standard_library_aliases_normalize() {
+        let WithErrors { result, errors } =
+            check(r#"import { S, K } from "std"; I = S K K; J = Std.I;"#);
+
+        assert!(errors.is_empty());
+        assert_eq!(norm_of(&result, "I"), "z => z");
+        assert_eq!(norm_of(&result, "J"), "x => x");
+    }
+
+    #[test]
+    fn assigns_every_error_a_code() {
+        let main = r#"
+            import { Two } from "./cycle";
+            import { X } from "./missing\q";
+            imprt { Y } from "std";
+            A = x => x => y;
+            A = B _;
+            C = (x => x;
+            D = Nope.K Cycle.Three (in => in) %;
+            E = (x, y) => x
+            F = _x;
+        "#;
+        let checked = check_all(
+            &[
+                ("main.lmy", main),
+                ("cycle.lmy", r#"import { A } from "./main"; Two = A;"#),
+            ],
+            "main.lmy",
+        );
+
+        let errors: Vec<&LabeledError> = checked
+            .values()
+            .flat_map(|checked| &checked.errors)
+            .collect();
+        assert!(errors.len() > 10);
+        for error in errors {
+            assert_ne!(error.code(), Code::Unspecified, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn merges_errors_from_every_phase_in_order() {
+        let WithErrors { result, errors } =
+            check(r#"import { bad } from "std"; A = x => x => y; A = B; C = (x => x;"#);
+
+        let found: Vec<(&str, Span, Severity)> = errors
+            .iter()
+            .map(|error| (error.message(), error.span().clone(), error.severity()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "imported names must be capitalized aliases, found var `bad`",
+                    Span::new(9, 12),
+                    Severity::Error
+                ),
+                (
+                    "`x` shadows an enclosing variable",
+                    Span::new(36, 37),
+                    Severity::Warning
+                ),
+                ("unbound variable `y`", Span::new(41, 42), Severity::Error),
+                (
+                    "alias `A` already defined",
+                    Span::new(44, 45),
+                    Severity::Error
+                ),
+                ("unknown alias `B`", Span::new(48, 49), Severity::Error),
+                ("unmatched '('", Span::new(55, 56), Severity::Error),
+                (
+                    "expected a ')' before this",
+                    Span::new(62, 63),
+                    Severity::Hint
+                ),
+            ]
+        );
+        // Only `C` survives, since its unmatched '(' doesn't hide its body
+        let defined: Vec<&str> = result
+            .defs
+            .iter()
+            .map(|(alias, _)| alias.as_str())
+            .collect();
+        assert_eq!(defined, vec!["C"]);
+    }
+
+    #[test]
+    fn hints_that_an_empty_module_has_no_definitions() {
+        let WithErrors { errors, .. } = check("# Nothing to see here\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "module contains no definitions");
+        assert_eq!(errors[0].severity(), Severity::Hint);
+        assert_eq!(*errors[0].span(), Span::new(0, 22));
+
+        let WithErrors { errors, .. } = check(r#"import { I } from "std";"#);
+        assert!(errors.is_empty());
+    }
+}
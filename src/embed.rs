@@ -0,0 +1,95 @@
+//! ## A single-call entry point for embedders.
+//!
+//! Front-ends that can't (or would rather not) drive each phase themselves,
+//! like a browser demo compiled to WebAssembly, can evaluate a term with one
+//! call and get plain strings back. Nothing here touches the filesystem.
+
+use crate::check::WithErrors;
+use crate::environment::Environment;
+use crate::errors::Report;
+use crate::nbe;
+use crate::source::Source;
+
+/// The outcome of `eval_source`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalOutput {
+    /// The normal form of the input term in surface syntax, or `None` if any
+    /// phase failed.
+    pub normal_form: Option<String>,
+    /// Every error (and warning) encountered, each fully rendered against the
+    /// input.
+    pub errors: Vec<String>,
+}
+
+/// Parses, lowers, and normalizes the term in `input` (with only the standard
+/// library in scope, qualified as `Std`), performing at most
+/// `nbe::DEFAULT_FUEL` beta reductions.
+pub fn eval_source(input: &str) -> EvalOutput {
+    let WithErrors {
+        result: normal,
+        errors,
+    } = Environment::default().eval_term(input, nbe::DEFAULT_FUEL);
+
+    let src = Source::anonymous(input);
+    EvalOutput {
+        normal_form: normal.map(|normal| normal.to_string()),
+        errors: errors
+            .iter()
+            .map(|error| Report(error, &src).to_string())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn produces_the_normal_form_in_surface_syntax() {
+        let output = eval_source("(f => x => f (f x)) (y => y)");
+
+        assert_eq!(
+            output,
+            EvalOutput {
+                normal_form: Some(String::from("x => x")),
+                errors: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn refers_to_the_standard_library() {
+        let output = eval_source("Std.K Std.I");
+
+        assert_eq!(output.normal_form, Some(String::from("(y, x) => x")));
+        assert!(output.errors.is_empty());
+    }
+
+    #[test]
+    fn renders_errors_against_the_input() {
+        let output = eval_source("x => Id x");
+
+        assert_eq!(output.normal_form, None);
+        assert_eq!(output.errors.len(), 1);
+        assert!(output.errors[0].starts_with("error[E018]: unknown alias `Id`\n"));
+        assert!(output.errors[0].contains("--> <anonymous>:1:6"));
+    }
+
+    #[test]
+    fn reports_divergent_input_instead_of_overflowing() {
+        let output = eval_source("(x => x x) (x => x x)");
+
+        assert_eq!(output.normal_form, None);
+        assert_eq!(output.errors.len(), 1);
+        assert!(output.errors[0].contains("evaluation nested more than"));
+    }
+
+    #[test]
+    fn produces_no_normal_form_after_syntax_errors() {
+        let output = eval_source("(x => x) (y => y");
+
+        assert_eq!(output.normal_form, None);
+        assert!(output.errors[0].starts_with("error[E"));
+        assert!(output.errors[0].contains("unmatched '('"));
+    }
+}
@@ -0,0 +1,470 @@
+//! ## The definitions accumulated over an REPL session, and the session itself.
+//!
+//! Each input is run through the usual phases (parsing, lowering, and
+//! normalization) against the definitions made so far.
+
+use crate::check::{std_modules, WithErrors};
+use crate::errors::{Code, SimpleError};
+use crate::lower::{define_qualified, Defs, Modules};
+use crate::nbe;
+use crate::source::Span;
+use crate::syntax::{
+    parse_repl_input, parse_repl_inputs, Def, ParseResult, ReplCommand, ReplInput, Term,
+};
+
+pub struct Environment {
+    defs: Defs,
+    /// The modules whose definitions may be referred to qualified, which are
+    /// those of the standard library.
+    modules: Modules,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        let modules = std_modules();
+        let mut defs = Defs::new();
+        define_qualified(&mut defs, &modules);
+        Environment { defs, modules }
+    }
+}
+
+impl Environment {
+    /// Records `def` for use by later inputs, producing the errors that
+    /// prevented its body from being lowered (if any). Incomplete definitions
+    /// are ignored, since the parser has already reported their problems.
+    pub fn define(&mut self, def: &Def) -> Vec<SimpleError> {
+        let (alias, body) = match (&def.alias, &def.body) {
+            (Some(alias), Some(body)) if !alias.is_bad() => (alias, body),
+            _ => return Vec::new(),
+        };
+
+        match body.lower_with_modules(&self.defs, &self.modules) {
+            Ok(term) => {
+                self.defs.define(nbe::Name::new(alias.text.as_str()), term);
+                Vec::new()
+            }
+            Err(errors) => errors,
+        }
+    }
+
+    /// Parses, lowers, and normalizes the term in `source` against this
+    /// environment's definitions, performing at most `fuel` beta reductions.
+    /// The normal form is produced along with every error encountered along
+    /// the way; it's missing if any phase failed.
+    pub fn eval_term(&self, source: &str, fuel: usize) -> WithErrors<Option<nbe::Term>> {
+        let ParseResult { result, mut errors } = parse_repl_input(source);
+        if !errors.is_empty() {
+            return WithErrors {
+                result: None,
+                errors,
+            };
+        }
+
+        let term = match result {
+            ReplInput::Term(term) => term,
+            ReplInput::Def(def) => {
+                let error = SimpleError::new("expected a term, found a definition", def.span);
+                errors.push(error.with_code(Code::UnexpectedInput));
+                return WithErrors {
+                    result: None,
+                    errors,
+                };
+            }
+            ReplInput::Command(ReplCommand::Norm(Some(term))) => term,
+            ReplInput::Command(ReplCommand::Norm(None)) | ReplInput::Empty | ReplInput::Unknown => {
+                return WithErrors {
+                    result: None,
+                    errors,
+                }
+            }
+            ReplInput::Command(..) => {
+                let span = Span::new(0, source.len());
+                let error = SimpleError::new("expected a term, found a command", span);
+                errors.push(error.with_code(Code::UnexpectedInput));
+                return WithErrors {
+                    result: None,
+                    errors,
+                };
+            }
+        };
+
+        let normal = self.norm(&term, fuel);
+        errors.extend(normal.errors);
+        WithErrors {
+            result: normal.result,
+            errors,
+        }
+    }
+
+    /// Lowers and normalizes `term` against this environment's definitions,
+    /// performing at most `fuel` beta reductions.
+    fn norm(&self, term: &Term, fuel: usize) -> WithErrors<Option<nbe::Term>> {
+        let lowered = match term.lower_with_modules(&self.defs, &self.modules) {
+            Ok(lowered) => lowered,
+            Err(errors) => {
+                return WithErrors {
+                    result: None,
+                    errors,
+                }
+            }
+        };
+
+        match lowered.norm_against(&self.defs, fuel) {
+            Ok(normal) => {
+                let errors = warn_constant_applications(term, &normal);
+                WithErrors {
+                    result: Some(normal),
+                    errors,
+                }
+            }
+            Err(diverged) => {
+                let error = SimpleError::new(diverged.to_string(), term.span().clone())
+                    .with_code(Code::Diverged);
+                WithErrors {
+                    result: None,
+                    errors: vec![error],
+                }
+            }
+        }
+    }
+}
+
+/// Warns about each application in `normal` (the normal form of `term`) of a
+/// global that isn't a function. Globals with definitions are unfolded during
+/// normalization, so any left at the head of an application have none. The
+/// warning points at the global's first use in `term`, if it appears there.
+fn warn_constant_applications(term: &Term, normal: &nbe::Term) -> Vec<SimpleError> {
+    normal
+        .constant_applications(&|_| true)
+        .into_iter()
+        .map(|global| {
+            let span = term.global_span(&global).unwrap_or_else(|| term.span());
+            let message = format!("`{}` is applied, but isn't a function", global.as_str());
+            SimpleError::warning(message, span.clone()).with_code(Code::AppliedConstant)
+        })
+        .collect()
+}
+
+/// An REPL session: the definitions accepted so far, against which each new
+/// input is evaluated.
+pub struct ReplSession {
+    environment: Environment,
+    /// The most beta reductions performed when normalizing a single input.
+    fuel: usize,
+}
+
+impl ReplSession {
+    pub fn new() -> Self {
+        ReplSession {
+            environment: Environment::default(),
+            fuel: nbe::DEFAULT_FUEL,
+        }
+    }
+
+    /// Parses and runs one line of input, which may consist of several
+    /// `;`-separated inputs. Definitions are recorded (replacing, with a
+    /// warning, any earlier definition of the same alias), terms (and `:norm`
+    /// commands) are normalized, and `:reset` forgets every definition. The
+    /// normal form of the last input, if it produced one, is accompanied by
+    /// every error encountered along the way. Inputs that failed to parse
+    /// aren't run at all.
+    ///
+    /// `:quit` and `:load` concern the front-end rather than the session, so
+    /// they're accepted here but do nothing.
+    pub fn feed(&mut self, line: &str) -> WithErrors<Option<nbe::Term>> {
+        let mut normal = None;
+        let mut errors = Vec::new();
+        for (
+            _,
+            ParseResult {
+                result,
+                errors: parse_errors,
+            },
+        ) in parse_repl_inputs(line)
+        {
+            if !parse_errors.is_empty() {
+                errors.extend(parse_errors);
+                normal = None;
+                continue;
+            }
+
+            let run = self.run(result);
+            normal = run.result;
+            errors.extend(run.errors);
+        }
+        WithErrors {
+            result: normal,
+            errors,
+        }
+    }
+
+    fn run(&mut self, input: ReplInput) -> WithErrors<Option<nbe::Term>> {
+        let term = match input {
+            ReplInput::Def(def) => {
+                let alias = match (&def.alias, &def.body) {
+                    (Some(alias), Some(_)) if !alias.is_bad() => alias,
+                    // The parser has already reported what's missing
+                    _ => return WithErrors::default(),
+                };
+                let redefining = self
+                    .environment
+                    .defs
+                    .contains(&nbe::Name::new(alias.text.as_str()));
+
+                let mut errors = self.environment.define(&def);
+                // A definition whose body can't be lowered replaces nothing
+                if redefining && errors.is_empty() {
+                    let message = format!("redefining alias `{}`", alias.text);
+                    let warning = SimpleError::warning(message, alias.span.clone());
+                    errors.push(warning.with_code(Code::RedefinedAlias));
+                }
+                return WithErrors {
+                    result: None,
+                    errors,
+                };
+            }
+            ReplInput::Term(term) | ReplInput::Command(ReplCommand::Norm(Some(term))) => term,
+            ReplInput::Command(ReplCommand::Reset) => {
+                self.environment = Environment::default();
+                return WithErrors::default();
+            }
+            ReplInput::Command(..) | ReplInput::Empty | ReplInput::Unknown => {
+                return WithErrors::default()
+            }
+        };
+
+        self.environment.norm(&term, self.fuel)
+    }
+}
+
+impl Default for ReplSession {
+    fn default() -> Self {
+        ReplSession::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Severity;
+    use crate::nbe::DEFAULT_FUEL;
+
+    fn define(env: &mut Environment, source: &str) {
+        let ParseResult { result, errors } = parse_repl_input(source);
+        assert!(errors.is_empty());
+        match result {
+            ReplInput::Def(def) => assert!(env.define(&def).is_empty()),
+            other => panic!("expected a definition, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn evaluates_terms_against_earlier_definitions() {
+        let mut env = Environment::default();
+        define(&mut env, "Id = x => x");
+
+        let WithErrors {
+            result: normal,
+            errors,
+        } = env.eval_term("Id (y => y)", DEFAULT_FUEL);
+
+        assert!(errors.is_empty());
+        let expected = nbe::Term::abs(nbe::Name::new("y"), nbe::Term::index(0));
+        assert!(normal.unwrap().alpha_eq(&expected));
+    }
+
+    #[test]
+    fn evaluates_terms_against_the_standard_library() {
+        let env = Environment::default();
+
+        let WithErrors {
+            result: normal,
+            errors,
+        } = env.eval_term("Std.S Std.K Std.K", DEFAULT_FUEL);
+
+        assert!(errors.is_empty());
+        let expected = nbe::Term::abs(nbe::Name::new("z"), nbe::Term::index(0));
+        assert!(normal.unwrap().alpha_eq(&expected));
+    }
+
+    #[test]
+    fn reports_errors_from_every_phase() {
+        let env = Environment::default();
+
+        let WithErrors {
+            result: normal,
+            errors,
+        } = env.eval_term("Id", DEFAULT_FUEL);
+        assert!(normal.is_none());
+        assert_eq!(errors[0].message(), "unknown alias `Id`");
+
+        let WithErrors {
+            result: normal,
+            errors,
+        } = env.eval_term("(x => x x) (x => x x)", 10);
+        assert!(normal.is_none());
+        assert_eq!(errors[0].message(), "evaluation exceeded 10 steps");
+    }
+
+    #[test]
+    fn terms_with_syntax_errors_are_not_normalized() {
+        let mut env = Environment::default();
+        define(&mut env, "K = (x, y) => x");
+
+        let WithErrors {
+            result: normal,
+            errors,
+        } = env.eval_term("K (z => z) (w => w", DEFAULT_FUEL);
+        assert!(normal.is_none());
+        assert_eq!(errors[0].message(), "unmatched '('");
+
+        let mut session = ReplSession::new();
+        let WithErrors {
+            result: normal,
+            errors,
+        } = session.feed("K = (x, y) => x; K (z => z) (w => w");
+        assert!(normal.is_none());
+        assert_eq!(errors[0].message(), "unmatched '('");
+    }
+
+    #[test]
+    fn assigns_every_error_a_code() {
+        let mut session = ReplSession::new();
+        let mut errors = Vec::new();
+        for line in [
+            "A = x => x",
+            "A = x => y",
+            ":frobnicate",
+            ":norm",
+            "Id = x => x; Id (y => y",
+        ] {
+            errors.extend(session.feed(line).errors);
+        }
+        let env = Environment::default();
+        for source in ["A = x => x", ":reset", "x => y; z"] {
+            errors.extend(env.eval_term(source, DEFAULT_FUEL).errors);
+        }
+        errors.extend(env.eval_term("(x => x x) (x => x x)", 10).errors);
+
+        assert!(errors.len() > 5);
+        for error in &errors {
+            assert_ne!(error.code(), Code::Unspecified, "{:?}", error);
+        }
+    }
+
+    fn feed_ok(session: &mut ReplSession, input: &str) -> Option<nbe::Term> {
+        let WithErrors {
+            result: normal,
+            errors,
+        } = session.feed(input);
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        normal
+    }
+
+    #[test]
+    fn sessions_remember_definitions() {
+        let mut session = ReplSession::new();
+
+        assert!(feed_ok(&mut session, "K = x => y => x").is_none());
+        assert!(feed_ok(&mut session, "Id = K (y => y)").is_none());
+        let normal = feed_ok(&mut session, ":norm Id K").unwrap();
+
+        assert!(normal.alpha_eq(&nbe::Term::abs(nbe::Name::new("y"), nbe::Term::index(0))));
+    }
+
+    #[test]
+    fn sessions_warn_about_redefinitions() {
+        let mut session = ReplSession::new();
+        feed_ok(&mut session, "A = x => x");
+
+        let WithErrors { errors, .. } = session.feed("A = (x, y) => y");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].severity(), Severity::Warning);
+        assert_eq!(errors[0].message(), "redefining alias `A`");
+
+        let normal = feed_ok(&mut session, "A").unwrap();
+        assert!(matches!(normal.classify(), Some(nbe::KnownForm::False)));
+    }
+
+    #[test]
+    fn failed_redefinitions_keep_the_old_definition() {
+        let mut session = ReplSession::new();
+        feed_ok(&mut session, "A = x => x");
+
+        let WithErrors { errors, .. } = session.feed("A = x => y");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "unbound variable `y`");
+
+        let normal = feed_ok(&mut session, "A").unwrap();
+        assert!(normal.alpha_eq(&nbe::Term::abs(nbe::Name::new("x"), nbe::Term::index(0))));
+    }
+
+    #[test]
+    fn reset_forgets_every_definition() {
+        let mut session = ReplSession::new();
+        feed_ok(&mut session, "Id = x => x");
+        feed_ok(&mut session, ":reset");
+
+        let WithErrors {
+            result: normal,
+            errors,
+        } = session.feed("Id");
+        assert!(normal.is_none());
+        assert_eq!(errors[0].message(), "unknown alias `Id`");
+    }
+
+    #[test]
+    fn warns_about_applications_of_constants() {
+        let mut env = Environment::default();
+        // `Zero` is opaque, so it's not a function
+        let zero = nbe::Name::new("Zero");
+        let opaque = nbe::Value::stuck(nbe::Stuck::global(zero.clone()));
+        env.defs.insert(zero, opaque);
+
+        let WithErrors {
+            result: normal,
+            errors,
+        } = env.eval_term("(x => Zero x) (y => y)", DEFAULT_FUEL);
+
+        assert_eq!(normal.unwrap().to_string(), "Zero (y => y)");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message(),
+            "`Zero` is applied, but isn't a function"
+        );
+        assert_eq!(errors[0].severity(), Severity::Warning);
+        assert_eq!(errors[0].code(), Code::AppliedConstant);
+        assert_eq!(*errors[0].span(), Span::new(6, 10));
+
+        let WithErrors { errors, .. } = env.eval_term("Std.K Std.I", DEFAULT_FUEL);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn sessions_survive_divergent_input() {
+        let mut session = ReplSession::new();
+
+        let WithErrors {
+            result: normal,
+            errors,
+        } = session.feed("(x => x x) (x => x x)");
+        assert!(normal.is_none());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code(), Code::Diverged);
+
+        assert!(feed_ok(&mut session, "x => x").is_some());
+    }
+
+    #[test]
+    fn sessions_run_every_input_on_a_line() {
+        let mut session = ReplSession::new();
+
+        let normal = feed_ok(
+            &mut session,
+            "Zero = s => z => z; Suc = n => s => z => s (n s z); Suc Zero",
+        )
+        .unwrap();
+
+        assert_eq!(normal.as_church_numeral(), Some(1));
+    }
+}
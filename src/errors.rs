@@ -3,12 +3,169 @@ use std::fmt;
 
 pub trait Error: fmt::Debug {
     fn report(&self, src: &Source, f: &mut fmt::Formatter) -> fmt::Result;
+
+    /// Produces a terse, single-line `file:line:col: severity: message`
+    /// rendering of this error, suitable for editor gutters and `grep`.
+    fn report_compact(&self, src: &Source) -> String;
+}
+
+/// How serious a diagnostic is. Only `Error`s indicate that the input is
+/// invalid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Hint,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Hint => write!(f, "hint"),
+        }
+    }
+}
+
+/// A stable identifier for each sort of diagnostic, so that they may be
+/// documented, filtered, or suppressed independently of their (possibly
+/// changing) messages. Codes are rendered like `E007`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Code {
+    /// No code has been assigned.
+    #[default]
+    Unspecified,
+    /// A token the lexer doesn't recognize (or one that's out of place).
+    UnknownToken,
+    /// A string (e.g. a filepath) missing its closing '"'.
+    UnterminatedString,
+    /// A block comment missing its closing '}#'.
+    UnterminatedComment,
+    /// An unknown escape sequence in a string.
+    UnknownEscape,
+    /// Something other than a definition or import at the top level of a
+    /// module (or other than a definition or term in the REPL).
+    ExpectedDef,
+    /// A definition or import that isn't followed by a ';'.
+    MissingSemicolon,
+    /// Input where none belongs, e.g. a second ',' or a trailing ';'.
+    ExtraneousInput,
+    /// A particular token (e.g. '=' or ')') that's missing.
+    ExpectedToken,
+    /// A var or alias that's missing.
+    ExpectedName,
+    /// A var where an alias is expected, or vice versa.
+    MisusedName,
+    /// A term (e.g. the body of a definition) that's missing.
+    MissingTerm,
+    /// An import whose filepath is missing.
+    MissingFilepath,
+    /// A '(' without a matching ')'.
+    UnmatchedParen,
+    /// A slight misspelling of `import`.
+    ImportTypo,
+    /// An REPL command that doesn't exist.
+    UnknownCommand,
+    /// A REPL input of the wrong sort (e.g. a definition where a term is
+    /// expected).
+    UnexpectedInput,
+    /// A var that isn't bound by any enclosing abstraction.
+    UnboundVar,
+    /// An alias that isn't defined (or imported).
+    UnknownAlias,
+    /// A qualified name whose module isn't imported.
+    UnknownModule,
+    /// A reference to the wildcard `_`.
+    WildcardReference,
+    /// An alias defined more than once in a module.
+    DuplicateDef,
+    /// An alias redefined in an REPL session.
+    RedefinedAlias,
+    /// A module that (directly or indirectly) imports itself.
+    ImportCycle,
+    /// An imported module that can't be read.
+    UnreadableModule,
+    /// A term whose normalization exceeded its budget.
+    Diverged,
+    /// A bound var that's never referred to.
+    UnusedVar,
+    /// A bound var with the same name as an enclosing one.
+    ShadowedVar,
+    /// An imported alias that's never referred to.
+    UnusedImport,
+    /// A module without any imports or definitions.
+    EmptyModule,
+    /// Indentation that mixes tabs and spaces.
+    MixedIndentation,
+    /// A term nested too deeply to parse.
+    NestedTooDeeply,
+    /// A reserved word (`let` or `in`) used as a var.
+    ReservedWord,
+    /// An application (in a normal form) of something that isn't a function,
+    /// like an alias without a definition.
+    AppliedConstant,
+}
+
+impl Code {
+    /// Produces this code's number, or `None` if it's `Unspecified`. Numbers
+    /// are never reused, so new codes must be added at the end.
+    pub fn number(&self) -> Option<u16> {
+        let number = match self {
+            Code::Unspecified => return None,
+            Code::UnknownToken => 1,
+            Code::UnterminatedString => 2,
+            Code::UnterminatedComment => 3,
+            Code::UnknownEscape => 4,
+            Code::ExpectedDef => 5,
+            Code::MissingSemicolon => 6,
+            Code::ExtraneousInput => 7,
+            Code::ExpectedToken => 8,
+            Code::ExpectedName => 9,
+            Code::MisusedName => 10,
+            Code::MissingTerm => 11,
+            Code::MissingFilepath => 12,
+            Code::UnmatchedParen => 13,
+            Code::ImportTypo => 14,
+            Code::UnknownCommand => 15,
+            Code::UnexpectedInput => 16,
+            Code::UnboundVar => 17,
+            Code::UnknownAlias => 18,
+            Code::UnknownModule => 19,
+            Code::WildcardReference => 20,
+            Code::DuplicateDef => 21,
+            Code::RedefinedAlias => 22,
+            Code::ImportCycle => 23,
+            Code::UnreadableModule => 24,
+            Code::Diverged => 25,
+            Code::UnusedVar => 26,
+            Code::ShadowedVar => 27,
+            Code::UnusedImport => 28,
+            Code::EmptyModule => 29,
+            Code::MixedIndentation => 30,
+            Code::NestedTooDeeply => 31,
+            Code::ReservedWord => 32,
+            Code::AppliedConstant => 33,
+        };
+        Some(number)
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.number() {
+            Some(number) => write!(f, "E{:03}", number),
+            None => write!(f, "E???"),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct SimpleError {
     message: String,
     span: Span,
+    severity: Severity,
+    code: Code,
 }
 
 impl SimpleError {
@@ -16,12 +173,451 @@ impl SimpleError {
         SimpleError {
             message: message.into(),
             span,
+            severity: Severity::Error,
+            code: Code::Unspecified,
+        }
+    }
+
+    /// Assigns `code` to this error.
+    pub fn with_code(mut self, code: Code) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        SimpleError {
+            severity: Severity::Warning,
+            ..SimpleError::new(message, span)
+        }
+    }
+
+    pub fn hint(message: impl Into<String>, span: Span) -> Self {
+        SimpleError {
+            severity: Severity::Hint,
+            ..SimpleError::new(message, span)
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn code(&self) -> Code {
+        self.code
+    }
+
+    /// Renders this error's severity, followed by its code (if it has one),
+    /// e.g. `error[E017]`.
+    fn heading(&self) -> String {
+        match self.code {
+            Code::Unspecified => self.severity.to_string(),
+            code => format!("{}[{}]", self.severity, code),
         }
     }
 }
 
+impl SimpleError {
+    /// Renders this error as a JSON object of the form
+    /// `{"severity": .., "code": .., "message": .., "file": .., "start": {"line": .., "col": ..}, "end": ..}`,
+    /// with 1-based lines and columns (as computed by `Source::line_col`).
+    /// The `code` is only present if one has been assigned.
+    pub fn to_json(&self, src: &Source) -> String {
+        let (start_line, start_col) = src.line_col(self.span.start);
+        let (end_line, end_col) = src.line_col(self.span.end);
+        let code = match self.code {
+            Code::Unspecified => String::new(),
+            code => format!(r#""code":"{}","#, code),
+        };
+        format!(
+            r#"{{"severity":"{}",{}"message":{},"file":{},"start":{{"line":{},"col":{}}},"end":{{"line":{},"col":{}}}}}"#,
+            self.severity,
+            code,
+            json_string(&self.message),
+            json_string(&src.filename),
+            start_line,
+            start_col,
+            end_line,
+            end_col
+        )
+    }
+}
+
+/// An error that points at several places: a primary span (where the
+/// problem is), and any number of secondary spans, each labeled with a note
+/// explaining its relevance (e.g. "first defined here"). Its severity and
+/// code are those of its primary `SimpleError`.
+#[derive(Debug)]
+pub struct LabeledError {
+    error: SimpleError,
+    labels: Vec<(Span, String)>,
+}
+
+impl From<SimpleError> for LabeledError {
+    fn from(error: SimpleError) -> Self {
+        LabeledError {
+            error,
+            labels: Vec::new(),
+        }
+    }
+}
+
+impl LabeledError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        SimpleError::new(message, span).into()
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        SimpleError::warning(message, span).into()
+    }
+
+    /// Assigns `code` to this error.
+    pub fn with_code(mut self, code: Code) -> Self {
+        self.error.code = code;
+        self
+    }
+
+    /// Adds a secondary span, along with a note about it.
+    pub fn with_label(mut self, span: Span, note: impl Into<String>) -> Self {
+        self.labels.push((span, note.into()));
+        self
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.error.severity
+    }
+
+    pub fn message(&self) -> &str {
+        &self.error.message
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.error.span
+    }
+
+    pub fn code(&self) -> Code {
+        self.error.code
+    }
+
+    pub fn labels(&self) -> &[(Span, String)] {
+        &self.labels
+    }
+}
+
+impl Error for LabeledError {
+    /// Reports the primary span just like a `SimpleError`, followed by each
+    /// labeled span (underlined with `---`) in turn.
+    fn report(&self, src: &Source, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error.report(src, f)?;
+        for (span, note) in &self.labels {
+            write_labeled_snippet(src, span, '-', Some(note), f)?;
+        }
+        Ok(())
+    }
+
+    fn report_compact(&self, src: &Source) -> String {
+        self.error.report_compact(src)
+    }
+}
+
+/// Displays an error's full report (see `Error::report`) against the source
+/// it occurs in.
+pub struct Report<'a>(pub &'a dyn Error, pub &'a Source);
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.report(self.1, f)
+    }
+}
+
+/// Renders `errors` (all of which occur in `src`) as a JSON array of the
+/// objects produced by `SimpleError::to_json`.
+pub fn diagnostics_json(errors: &[SimpleError], src: &Source) -> String {
+    let objects: Vec<String> = errors.iter().map(|error| error.to_json(src)).collect();
+    format!("[{}]", objects.join(","))
+}
+
+/// Quotes and escapes `text` as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
 impl Error for SimpleError {
     fn report(&self, src: &Source, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "error: {}", self.message)
+        writeln!(f, "{}: {}", self.heading(), self.message)?;
+        write_snippet(src, &self.span, f)
+    }
+
+    fn report_compact(&self, src: &Source) -> String {
+        let (line, col) = src.line_col(self.span.start);
+        format!(
+            "{}:{}:{}: {}: {}",
+            src.filename,
+            line,
+            col,
+            self.heading(),
+            self.message
+        )
+    }
+}
+
+/// Writes the line of `src` containing the start of `span`, along with a
+/// `^^^` underline beneath the spanned text:
+///
+/// ```text
+///   --> nats.lmy:3:8
+///    |
+///  3 | Loop = (x => x x) x => x x;
+///    |        ^^^^^^^^^^
+/// ```
+///
+/// Spans that cross a line break are only underlined up to the end of their
+/// first line, and empty spans are drawn as a single caret.
+fn write_snippet(src: &Source, span: &Span, f: &mut fmt::Formatter) -> fmt::Result {
+    write_labeled_snippet(src, span, '^', None, f)
+}
+
+/// Like `write_snippet`, but underlines with `underline` and follows the
+/// underline with `label` (if there is one).
+fn write_labeled_snippet(
+    src: &Source,
+    span: &Span,
+    underline: char,
+    label: Option<&str>,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    let (line, col) = src.line_col(span.start);
+    let start = usize::min(span.start, src.text.len());
+    let line_text = src.line_of(start);
+    let line_start = src.text[..start].rfind('\n').map_or(0, |i| i + 1);
+    // A span that starts at the '\n' of a "\r\n" starts past the end of the
+    // line's text
+    let end = usize::max(usize::min(span.end, line_start + line_text.len()), start);
+
+    let gutter = " ".repeat(line.to_string().len());
+    let indent = " ".repeat(col - 1);
+    let width = usize::max(src.snippet(&Span::new(start, end)).chars().count(), 1);
+    let underline = underline.to_string().repeat(width);
+
+    writeln!(f, "{}--> {}:{}:{}", gutter, src.filename, line, col)?;
+    writeln!(f, "{} |", gutter)?;
+    writeln!(f, "{} | {}", line, line_text)?;
+    match label {
+        Some(label) => writeln!(f, "{} | {}{} {}", gutter, indent, underline, label),
+        None => writeln!(f, "{} | {}{}", gutter, indent, underline),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(text: &str) -> Source {
+        Source::new(String::from("test.lmy"), String::from(text))
+    }
+
+    #[test]
+    fn reports_spans_that_start_within_a_crlf() {
+        let src = source("A = x => x;\r\nB = y;");
+        let error = SimpleError::new("oops", Span::new(12, 14));
+
+        let expected = "error: oops
+ --> test.lmy:1:13
+  |
+1 | A = x => x;
+  |             ^
+";
+        assert_eq!(Report(&error, &src).to_string(), expected);
+    }
+
+    #[test]
+    fn reports_the_spanned_line_with_carets() {
+        let src = source("Id = x => x;\nLoop = (x => x x) x => x x;\n");
+        let error = SimpleError::new("oops", Span::new(20, 30));
+
+        let expected = r#"error: oops
+ --> test.lmy:2:8
+  |
+2 | Loop = (x => x x) x => x x;
+  |        ^^^^^^^^^^
+"#;
+        assert_eq!(Report(&error, &src).to_string(), expected);
+    }
+
+    #[test]
+    fn underlines_multiline_spans_to_the_end_of_the_first_line() {
+        let src = source("Id = x =>\r\n  x;");
+        let error = SimpleError::new("oops", Span::new(5, 14));
+
+        let expected = r#"error: oops
+ --> test.lmy:1:6
+  |
+1 | Id = x =>
+  |      ^^^^
+"#;
+        assert_eq!(Report(&error, &src).to_string(), expected);
+    }
+
+    #[test]
+    fn draws_a_single_caret_for_empty_spans() {
+        let src = source("Id = x => x");
+        let error = SimpleError::new("missing a ';'", Span::point(11));
+
+        let expected = r#"error: missing a ';'
+ --> test.lmy:1:12
+  |
+1 | Id = x => x
+  |            ^
+"#;
+        assert_eq!(Report(&error, &src).to_string(), expected);
+    }
+
+    #[test]
+    fn reports_compactly_on_one_line() {
+        let src = source("K = x;");
+        let error = SimpleError::new("unbound variable", Span::new(4, 5));
+
+        assert_eq!(
+            error.report_compact(&src),
+            "test.lmy:1:5: error: unbound variable"
+        );
+    }
+
+    #[test]
+    fn renders_diagnostics_as_json() {
+        let src = source("Id = x => x;\nK = \"y\";");
+        let errors = vec![
+            SimpleError::new("unbound variable `y`", Span::new(17, 20)),
+            SimpleError::new("missing a ';'", Span::point(12)),
+        ];
+
+        let json: serde_json::Value =
+            serde_json::from_str(&diagnostics_json(&errors, &src)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {
+                    "severity": "error",
+                    "message": "unbound variable `y`",
+                    "file": "test.lmy",
+                    "start": { "line": 2, "col": 5 },
+                    "end": { "line": 2, "col": 8 },
+                },
+                {
+                    "severity": "error",
+                    "message": "missing a ';'",
+                    "file": "test.lmy",
+                    "start": { "line": 1, "col": 13 },
+                    "end": { "line": 1, "col": 13 },
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        let src = source("x");
+        let error = SimpleError::new("a \"quoted\"\tname\\", Span::new(0, 1));
+
+        let json: serde_json::Value = serde_json::from_str(&error.to_json(&src)).unwrap();
+        assert_eq!(json["message"], "a \"quoted\"\tname\\");
+    }
+
+    #[test]
+    fn reports_are_prefixed_with_their_severity() {
+        let src = source("x => y");
+        let warning = SimpleError::warning("unused variable `x`", Span::new(0, 1));
+
+        assert!(Report(&warning, &src)
+            .to_string()
+            .starts_with("warning: unused variable `x`\n"));
+        assert_eq!(
+            SimpleError::hint("just so you know", Span::new(5, 6)).report_compact(&src),
+            "test.lmy:1:6: hint: just so you know"
+        );
+    }
+
+    #[test]
+    fn reports_each_labeled_span_with_its_note() {
+        let src = source("Id = x => x;\nId = y => y;\n");
+        let error = LabeledError::new("alias `Id` already defined", Span::new(13, 15))
+            .with_code(Code::DuplicateDef)
+            .with_label(Span::new(0, 2), "first defined here");
+
+        let expected = r#"error[E021]: alias `Id` already defined
+ --> test.lmy:2:1
+  |
+2 | Id = y => y;
+  | ^^
+ --> test.lmy:1:1
+  |
+1 | Id = x => x;
+  | -- first defined here
+"#;
+        assert_eq!(Report(&error, &src).to_string(), expected);
+        assert_eq!(
+            error.report_compact(&src),
+            "test.lmy:2:1: error[E021]: alias `Id` already defined"
+        );
+
+        let warning = LabeledError::warning("`x` shadows an enclosing variable", Span::new(5, 6));
+        assert!(Report(&warning, &src)
+            .to_string()
+            .starts_with("warning: `x` shadows an enclosing variable\n"));
+    }
+
+    #[test]
+    fn reports_include_the_code_if_there_is_one() {
+        let src = source("x => y");
+        let error =
+            SimpleError::new("unbound variable `y`", Span::new(5, 6)).with_code(Code::UnboundVar);
+
+        assert_eq!(error.code(), Code::UnboundVar);
+        assert!(Report(&error, &src)
+            .to_string()
+            .starts_with("error[E017]: unbound variable `y`\n"));
+        assert_eq!(
+            error.report_compact(&src),
+            "test.lmy:1:6: error[E017]: unbound variable `y`"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&error.to_json(&src)).unwrap();
+        assert_eq!(json["code"], "E017");
+    }
+
+    #[test]
+    fn parse_errors_are_assigned_codes() {
+        let errors = crate::syntax::parse_module("Id = (x => x; K x = y;").errors;
+        let codes: Vec<Code> = errors.iter().map(SimpleError::code).collect();
+
+        assert_eq!(
+            codes,
+            vec![
+                Code::UnmatchedParen,
+                Code::UnmatchedParen,
+                Code::ExpectedDef,
+            ]
+        );
     }
 }
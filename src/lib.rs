@@ -0,0 +1,9 @@
+pub mod check;
+pub mod embed;
+pub mod environment;
+pub mod errors;
+pub mod loader;
+pub mod lower;
+pub mod nbe;
+pub mod source;
+pub mod syntax;
@@ -0,0 +1,324 @@
+//! ## Loading a module along with everything it (transitively) imports.
+//!
+//! Import filepaths are resolved relative to the importing file, with the
+//! `.lmy` extension added if they don't have one. Resolved paths are
+//! normalized lexically (`./a` and `a` are the same module), which also keeps
+//! import cycles from evading detection. The filepath `"std"` is special: it
+//! refers to the standard library, which is embedded rather than read.
+//!
+//! Each imported module's aliases may also be referred to by qualified name,
+//! using a name derived from its filepath (see `module_name`): `General.K`
+//! refers to the `K` defined in `./general`.
+
+use crate::errors::{Code, SimpleError};
+use crate::source::Source;
+use crate::syntax::{parse_module, Module, ParseResult};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// The extension assumed by import filepaths that don't include one.
+const EXTENSION: &str = "lmy";
+
+/// The import filepath (and module path) of the standard library.
+pub const STD_FILEPATH: &str = "std";
+
+/// The source of the standard library.
+pub const STD: &str = include_str!("std.lmy");
+
+/// Every module reachable from an entry module, keyed by normalized path.
+#[derive(Debug, Default)]
+pub struct ModuleGraph {
+    /// The (normalized) path of the entry module.
+    pub entry: PathBuf,
+    pub modules: HashMap<PathBuf, LoadedModule>,
+}
+
+/// A parsed module, along with every error encountered while loading it
+/// (including errors in its imports, which are reported at the import).
+#[derive(Debug)]
+pub struct LoadedModule {
+    pub source: Source,
+    pub module: Module,
+    /// The path of the module that each of `module`'s imports refers to, or
+    /// `None` if it wasn't loaded (e.g. because it's unreadable, or would
+    /// complete an import cycle).
+    pub imports: Vec<Option<PathBuf>>,
+    pub errors: Vec<SimpleError>,
+}
+
+/// Loads the module at `entry` from the filesystem, along with everything it
+/// imports. Only a failure to read the entry module itself is fatal.
+pub fn load(entry: impl AsRef<Path>) -> io::Result<ModuleGraph> {
+    Loader::new(|path: &Path| fs::read_to_string(path)).load(entry.as_ref())
+}
+
+/// A module loader that reads files via `read`, so that it may be run against
+/// something other than the filesystem.
+pub struct Loader<R> {
+    read: R,
+    /// The modules currently being loaded, from the entry module inwards.
+    resolving: Vec<PathBuf>,
+    graph: ModuleGraph,
+}
+
+impl<R: FnMut(&Path) -> io::Result<String>> Loader<R> {
+    pub fn new(read: R) -> Self {
+        Loader {
+            read,
+            resolving: Vec::new(),
+            graph: ModuleGraph::default(),
+        }
+    }
+
+    pub fn load(mut self, entry: &Path) -> io::Result<ModuleGraph> {
+        let text = self.read(&normalize(entry))?;
+        Ok(self.load_source(entry, text))
+    }
+
+    /// Like `load`, but takes the entry module's source as given rather than
+    /// reading it (e.g. since it's being edited).
+    pub fn load_source(mut self, entry: &Path, text: String) -> ModuleGraph {
+        let entry = normalize(entry);
+        self.graph.entry = entry.clone();
+        self.load_module(entry, text);
+        self.graph
+    }
+
+    fn read(&mut self, path: &Path) -> io::Result<String> {
+        if path == Path::new(STD_FILEPATH) {
+            Ok(String::from(STD))
+        } else {
+            (self.read)(path)
+        }
+    }
+
+    fn load_module(&mut self, path: PathBuf, text: String) {
+        let source = Source::new(path.display().to_string(), text);
+        let ParseResult { result, mut errors } = parse_module(&source.text);
+
+        self.resolving.push(path.clone());
+        let mut imports = Vec::new();
+        for import in &result.imports {
+            imports.push(None);
+            let filepath = match &import.filepath {
+                Some(filepath) => filepath,
+                None => continue,
+            };
+
+            let imported = if *filepath.text == STD_FILEPATH {
+                PathBuf::from(STD_FILEPATH)
+            } else {
+                resolve(&path, &filepath.text)
+            };
+            if let Some(start) = self.resolving.iter().position(|p| *p == imported) {
+                let chain: Vec<String> = self.resolving[start..]
+                    .iter()
+                    .chain(Some(&imported))
+                    .map(|p| p.display().to_string())
+                    .collect();
+                let message = format!("import cycle: {}", chain.join(" -> "));
+                let error = SimpleError::new(message, import.span.clone());
+                errors.push(error.with_code(Code::ImportCycle));
+                continue;
+            }
+            if self.graph.modules.contains_key(&imported) {
+                *imports.last_mut().unwrap() = Some(imported);
+                continue;
+            }
+
+            match self.read(&imported) {
+                Ok(text) => {
+                    self.load_module(imported.clone(), text);
+                    *imports.last_mut().unwrap() = Some(imported);
+                }
+                Err(err) => {
+                    let message = format!("can't read `{}`: {}", imported.display(), err);
+                    let error = SimpleError::new(message, filepath.span.clone());
+                    errors.push(error.with_code(Code::UnreadableModule));
+                }
+            }
+        }
+        self.resolving.pop();
+
+        let loaded = LoadedModule {
+            source,
+            module: result,
+            imports,
+            errors,
+        };
+        self.graph.modules.insert(path, loaded);
+    }
+}
+
+/// Produces the name that qualifies references to the aliases of the module
+/// at `path`: its file stem, with each word (separated by '-' or '_')
+/// capitalized. For example, `General.K` refers to `./general`, and
+/// `Std.Y` to `"std"`.
+pub fn module_name(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    stem.split(['-', '_'])
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().map(|c| c.to_ascii_uppercase());
+            first.into_iter().chain(chars)
+        })
+        .collect()
+}
+
+/// Resolves the import `filepath` appearing in the module at `importer`.
+fn resolve(importer: &Path, filepath: &str) -> PathBuf {
+    let dir = importer.parent().unwrap_or_else(|| Path::new(""));
+    let mut path = dir.join(filepath);
+    if path.extension().is_none() {
+        path.set_extension(EXTENSION);
+    }
+    normalize(&path)
+}
+
+/// Removes `.` components, and `..` components along with the component they
+/// cancel out (where there is one).
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(..)) => {
+                    normalized.pop();
+                }
+                _ => normalized.push(component),
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check::{check_module, WithErrors};
+    use crate::nbe;
+    use crate::syntax::{parse_repl_input, ReplInput};
+
+    fn load_from(files: &[(&str, &str)], entry: &str) -> ModuleGraph {
+        let files: HashMap<PathBuf, String> = files
+            .iter()
+            .map(|(path, text)| (PathBuf::from(path), text.to_string()))
+            .collect();
+        let read = |path: &Path| {
+            files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        };
+        Loader::new(read).load(Path::new(entry)).unwrap()
+    }
+
+    fn messages<'g>(graph: &'g ModuleGraph, path: &str) -> Vec<&'g str> {
+        graph.modules[Path::new(path)]
+            .errors
+            .iter()
+            .map(SimpleError::message)
+            .collect()
+    }
+
+    #[test]
+    fn loads_imported_modules() {
+        let graph = load_from(
+            &[
+                ("lib/main.lmy", r#"import { Id } from "./util"; Main = Id;"#),
+                ("lib/util.lmy", "Id = x => x;"),
+            ],
+            "lib/main.lmy",
+        );
+
+        assert_eq!(graph.modules.len(), 2);
+        assert!(messages(&graph, "lib/main.lmy").is_empty());
+        assert_eq!(
+            graph.modules[Path::new("lib/util.lmy")].module.defs.len(),
+            1
+        );
+    }
+
+    #[test]
+    fn reports_direct_import_cycles() {
+        let graph = load_from(
+            &[(
+                "nats.lmy",
+                r#"import { Zero } from "./nats"; Zero = (s, z) => z;"#,
+            )],
+            "./nats.lmy",
+        );
+
+        assert_eq!(
+            messages(&graph, "nats.lmy"),
+            vec!["import cycle: nats.lmy -> nats.lmy"]
+        );
+        let error = &graph.modules[Path::new("nats.lmy")].errors[0];
+        assert_eq!(error.span().start, 0);
+    }
+
+    #[test]
+    fn reports_transitive_import_cycles() {
+        let graph = load_from(
+            &[
+                ("a.lmy", r#"import { B } from "b"; A = B;"#),
+                ("b.lmy", r#"import { A } from "./sub/../a.lmy"; B = A;"#),
+            ],
+            "a.lmy",
+        );
+
+        assert!(messages(&graph, "a.lmy").is_empty());
+        assert_eq!(
+            messages(&graph, "b.lmy"),
+            vec!["import cycle: a.lmy -> b.lmy -> a.lmy"]
+        );
+    }
+
+    #[test]
+    fn loads_the_embedded_standard_library() {
+        let graph = load_from(
+            &[("main.lmy", r#"import { S, K } from "std"; I = S K K;"#)],
+            "main.lmy",
+        );
+
+        assert!(messages(&graph, "main.lmy").is_empty());
+        assert!(messages(&graph, "std").is_empty());
+        let aliases: Vec<&str> = graph.modules[Path::new("std")]
+            .module
+            .defs
+            .iter()
+            .filter_map(|def| def.alias.as_ref())
+            .map(|alias| alias.text.as_str())
+            .collect();
+        assert_eq!(aliases, vec!["I", "K", "S", "B", "C", "Y"]);
+    }
+
+    #[test]
+    fn standard_combinators_behave() {
+        let WithErrors { result, errors } = check_module(&Source::anonymous(STD));
+        assert!(errors.is_empty());
+
+        let (term, _) = parse_repl_input("S K K").into_parts();
+        let skk = match term {
+            ReplInput::Term(term) => term.lower(&result.defs).unwrap(),
+            other => panic!("expected a term, found {:?}", other),
+        };
+        let id = nbe::Term::abs(nbe::Name::new("x"), nbe::Term::index(0));
+        let normal = skk.norm_against(&result.defs, nbe::DEFAULT_FUEL).unwrap();
+        assert!(normal.alpha_eq(&id));
+    }
+
+    #[test]
+    fn reports_unreadable_imports() {
+        let graph = load_from(&[("a.lmy", r#"import { B } from "./b";"#)], "a.lmy");
+
+        assert_eq!(
+            messages(&graph, "a.lmy"),
+            vec!["can't read `b.lmy`: entity not found"]
+        );
+    }
+}
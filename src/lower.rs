@@ -0,0 +1,437 @@
+//! ## Lowering of surface terms to the de Bruijn terms evaluated by `nbe`.
+//!
+//! This is the bridge between parsed programs and the evaluator: bound vars
+//! are replaced by the index of their binder, and aliases (qualified or not)
+//! by globals, which the evaluator unfolds into their definitions on demand.
+
+use crate::errors::{Code, SimpleError};
+use crate::nbe::{self, Usage};
+use crate::source::Span;
+use crate::syntax::Term;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The definitions of the aliases in scope, each named by its alias. A term
+/// lowered against them must be evaluated against them too (see
+/// `nbe::Term::norm_against`), after `define_qualified`.
+pub type Defs = nbe::Defs;
+
+/// The definitions exported by each imported module, keyed by the name that
+/// qualifies them (e.g. `General` in `General.K`).
+pub type Modules = HashMap<Rc<String>, Defs>;
+
+/// The name of the global that `module.alias` lowers to. Aliases can't
+/// contain a `.`, so this never names an unqualified alias.
+fn qualified_name(module: &str, alias: &str) -> nbe::Name {
+    nbe::Name::new(format!("{}.{}", module, alias))
+}
+
+/// Adds the definitions of `modules` to `defs`, named as qualified names
+/// referring to them are lowered.
+pub fn define_qualified(defs: &mut Defs, modules: &Modules) {
+    for (module, exports) in modules {
+        for (alias, value) in exports.iter() {
+            defs.insert(qualified_name(module, alias.as_str()), value.clone());
+        }
+    }
+}
+
+impl Term {
+    /// Lowers this term to an `nbe::Term`, recording an error for each
+    /// unbound var or unknown alias it contains. Incomplete terms (whose
+    /// problems have already been reported by the parser) fail to lower
+    /// without producing any additional errors.
+    pub fn lower(&self, defs: &Defs) -> Result<nbe::Term, Vec<SimpleError>> {
+        self.lower_with_modules(defs, &Modules::new())
+    }
+
+    /// Like `lower`, but also resolves qualified names against `modules`.
+    pub fn lower_with_modules(
+        &self,
+        defs: &Defs,
+        modules: &Modules,
+    ) -> Result<nbe::Term, Vec<SimpleError>> {
+        let mut errors = Vec::new();
+        match self.lower_in(&mut Vec::new(), defs, modules, &mut errors) {
+            Some(term) if errors.is_empty() => Ok(term),
+            _ => Err(errors),
+        }
+    }
+
+    fn lower_in(
+        &self,
+        bound_vars: &mut Vec<Rc<String>>,
+        defs: &Defs,
+        modules: &Modules,
+        errors: &mut Vec<SimpleError>,
+    ) -> Option<nbe::Term> {
+        match self {
+            // A wildcard binder still occupies an index, but can't be
+            // referred to
+            Term::Var { text, span } if text.as_str() == "_" => {
+                let message = "`_` can't be referred to, since it binds nothing";
+                errors.push(
+                    SimpleError::new(message, span.clone()).with_code(Code::WildcardReference),
+                );
+                None
+            }
+            Term::Var { text, span } => match bound_vars.iter().rev().position(|var| var == text) {
+                Some(index) => Some(nbe::Term::index(index)),
+                None => {
+                    let message = format!("unbound variable `{}`", text);
+                    errors
+                        .push(SimpleError::new(message, span.clone()).with_code(Code::UnboundVar));
+                    None
+                }
+            },
+            Term::Alias { text, span } => {
+                let name = nbe::Name::new(text.as_str());
+                if defs.contains(&name) {
+                    Some(nbe::Term::global(name))
+                } else {
+                    let message = format!("unknown alias `{}`", text);
+                    errors.push(
+                        SimpleError::new(message, span.clone()).with_code(Code::UnknownAlias),
+                    );
+                    None
+                }
+            }
+            Term::Qualified { module, name, .. } => {
+                let defs = match modules.get(&module.text) {
+                    Some(defs) => defs,
+                    None => {
+                        let message = format!("unknown module `{}`", module.text);
+                        let error = SimpleError::new(message, module.span.clone());
+                        errors.push(error.with_code(Code::UnknownModule));
+                        return None;
+                    }
+                };
+                let name = name.as_ref()?;
+                if defs.contains(&nbe::Name::new(name.text.as_str())) {
+                    Some(nbe::Term::global(qualified_name(&module.text, &name.text)))
+                } else {
+                    let message = format!("module `{}` has no alias `{}`", module.text, name.text);
+                    let error = SimpleError::new(message, name.span.clone());
+                    errors.push(error.with_code(Code::UnknownAlias));
+                    None
+                }
+            }
+            Term::Abs { vars, body, .. } => {
+                bound_vars.extend(vars.iter().map(|var| Rc::clone(&var.text)));
+                let body = body
+                    .as_ref()
+                    .and_then(|body| body.lower_in(bound_vars, defs, modules, errors));
+                bound_vars.truncate(bound_vars.len() - vars.len());
+
+                if vars.is_empty() {
+                    return None;
+                }
+                let body = body?;
+                Some(vars.iter().rev().fold(body, |body, var| {
+                    nbe::Term::abs(nbe::Name::new(var.text.as_str()), body)
+                }))
+            }
+            // `let x = e in body` is sugar for `(x => body) e`
+            Term::Let {
+                var, value, body, ..
+            } => {
+                let value = value
+                    .as_ref()
+                    .and_then(|value| value.lower_in(bound_vars, defs, modules, errors));
+
+                let var = var.as_ref()?;
+                bound_vars.push(Rc::clone(&var.text));
+                let body = body
+                    .as_ref()
+                    .and_then(|body| body.lower_in(bound_vars, defs, modules, errors));
+                bound_vars.pop();
+
+                let abs = nbe::Term::abs(nbe::Name::new(var.text.as_str()), body?);
+                Some(nbe::Term::app(abs, value?))
+            }
+            Term::App { rator, rands, .. } => {
+                let rator = rator.lower_in(bound_vars, defs, modules, errors);
+                // Lower every operand (rather than stopping at the first
+                // failure) so that all of their errors are recorded
+                let rands: Vec<Option<nbe::Term>> = rands
+                    .iter()
+                    .map(|rand| rand.lower_in(bound_vars, defs, modules, errors))
+                    .collect();
+
+                let rands = rands.into_iter().collect::<Option<Vec<nbe::Term>>>()?;
+                Some(rands.into_iter().fold(rator?, nbe::Term::app))
+            }
+        }
+    }
+
+    /// The span of the first alias (qualified or not) in this term that lowers
+    /// to the global `global`.
+    pub fn global_span(&self, global: &nbe::Name) -> Option<&Span> {
+        match self {
+            Term::Var { .. } | Term::Qualified { name: None, .. } => None,
+            Term::Alias { text, span } => Some(span).filter(|_| text.as_str() == global.as_str()),
+            Term::Qualified {
+                module,
+                name: Some(name),
+                span,
+            } => Some(span).filter(|_| qualified_name(&module.text, &name.text) == *global),
+            Term::Abs { body, .. } => body.as_ref()?.global_span(global),
+            Term::Let { value, body, .. } => value
+                .iter()
+                .chain(body)
+                .find_map(|term| term.global_span(global)),
+            Term::App { rator, rands, .. } => rator
+                .global_span(global)
+                .or_else(|| rands.iter().find_map(|rand| rand.global_span(global))),
+        }
+    }
+
+    /// Classifies how each var bound in this term (by an abstraction or a
+    /// `let`) is used, in the order in which the vars appear. Vars are
+    /// resolved just as they are when lowering, so each `(a, b) => ..` binds
+    /// `b` in an abstraction nested within `a`'s, and a `let` binds its var in
+    /// an abstraction around its body.
+    pub fn usages(&self) -> Vec<(Span, Usage)> {
+        let mut tallies = Vec::new();
+        self.tally_usages(&mut Vec::new(), 0, &mut tallies);
+        tallies
+            .into_iter()
+            .map(|tally| (tally.span, Usage::from_count(tally.count, tally.nested)))
+            .collect()
+    }
+
+    /// Tallies the uses of the vars in `bound_vars`, each of which is paired
+    /// with its index in `tallies`. `depth` counts the enclosing abstractions.
+    fn tally_usages(
+        &self,
+        bound_vars: &mut Vec<(Rc<String>, usize)>,
+        depth: usize,
+        tallies: &mut Vec<Tally>,
+    ) {
+        match self {
+            Term::Var { text, .. } => {
+                if let Some((_, i)) = bound_vars.iter().rev().find(|(var, _)| var == text) {
+                    let tally = &mut tallies[*i];
+                    tally.count += 1;
+                    tally.nested |= depth > tally.depth;
+                }
+            }
+            Term::Alias { .. } | Term::Qualified { .. } => {}
+            Term::Abs { vars, body, .. } => {
+                for (offset, var) in vars.iter().enumerate() {
+                    bound_vars.push((Rc::clone(&var.text), tallies.len()));
+                    tallies.push(Tally::new(var.span.clone(), depth + offset + 1));
+                }
+                if let Some(body) = body {
+                    body.tally_usages(bound_vars, depth + vars.len(), tallies);
+                }
+                bound_vars.truncate(bound_vars.len() - vars.len());
+            }
+            Term::Let {
+                var, value, body, ..
+            } => {
+                if let Some(value) = value {
+                    value.tally_usages(bound_vars, depth, tallies);
+                }
+                if let Some(var) = var {
+                    bound_vars.push((Rc::clone(&var.text), tallies.len()));
+                    tallies.push(Tally::new(var.span.clone(), depth + 1));
+                }
+                if let Some(body) = body {
+                    body.tally_usages(bound_vars, depth + 1, tallies);
+                }
+                if var.is_some() {
+                    bound_vars.pop();
+                }
+            }
+            Term::App { rator, rands, .. } => {
+                rator.tally_usages(bound_vars, depth, tallies);
+                for rand in rands {
+                    rand.tally_usages(bound_vars, depth, tallies);
+                }
+            }
+        }
+    }
+}
+
+/// The uses found so far of the var bound at `span`, whose scope begins at
+/// the given abstraction `depth`.
+struct Tally {
+    span: Span,
+    depth: usize,
+    count: usize,
+    nested: bool,
+}
+
+impl Tally {
+    fn new(span: Span, depth: usize) -> Self {
+        Tally {
+            span,
+            depth,
+            count: 0,
+            nested: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::{parse_repl_input, ReplInput};
+
+    fn term(source: &str) -> Term {
+        let (input, _) = parse_repl_input(source).into_parts();
+        match input {
+            ReplInput::Term(term) => term,
+            other => panic!("expected a term, found {:?}", other),
+        }
+    }
+
+    fn tru() -> nbe::Term {
+        term("(t, f) => t").lower(&Defs::new()).unwrap()
+    }
+
+    #[test]
+    fn lowers_and_normalizes_closed_terms() {
+        let lowered = term("(x => y => x) ((t, f) => t) (z => z)")
+            .lower(&Defs::new())
+            .unwrap();
+
+        assert_eq!(lowered.eval(&nbe::Env::new()).as_bool(), Some(true));
+    }
+
+    #[test]
+    fn replaces_aliases_with_globals() {
+        let mut defs = Defs::new();
+        defs.define(nbe::Name::new("True"), tru());
+
+        let lowered = term("(x => x) True").lower(&defs).unwrap();
+
+        assert_eq!(lowered.to_string(), "(x => x) True");
+        assert_eq!(
+            lowered.eval(&nbe::Env::with_defs(defs)).as_bool(),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn reports_unbound_vars_and_unknown_aliases() {
+        let errors = term("(x => y) False z").lower(&Defs::new()).unwrap_err();
+
+        let messages: Vec<&str> = errors.iter().map(SimpleError::message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "unbound variable `y`",
+                "unknown alias `False`",
+                "unbound variable `z`"
+            ]
+        );
+    }
+
+    #[test]
+    fn lowers_let_to_an_applied_abstraction() {
+        let mut defs = Defs::new();
+        defs.define(nbe::Name::new("True"), tru());
+
+        let lowered = term("let t = True in (x => x) t").lower(&defs).unwrap();
+
+        assert_eq!(
+            lowered.eval(&nbe::Env::with_defs(defs)).as_bool(),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn resolves_qualified_names_in_their_module() {
+        let mut general = Defs::new();
+        general.define(nbe::Name::new("True"), tru());
+        let mut modules = Modules::new();
+        modules.insert(Rc::new(String::from("General")), general);
+
+        let lowered = term("(x => x) General.True")
+            .lower_with_modules(&Defs::new(), &modules)
+            .unwrap();
+        let mut defs = Defs::new();
+        define_qualified(&mut defs, &modules);
+        assert_eq!(
+            lowered.eval(&nbe::Env::with_defs(defs)).as_bool(),
+            Some(true)
+        );
+
+        let errors = term("Other.True General.False")
+            .lower_with_modules(&Defs::new(), &modules)
+            .unwrap_err();
+        let found: Vec<(&str, &Span)> = errors
+            .iter()
+            .map(|error| (error.message(), error.span()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("unknown module `Other`", &Span::new(0, 5)),
+                ("module `General` has no alias `False`", &Span::new(19, 24)),
+            ]
+        );
+    }
+
+    #[test]
+    fn classifies_the_usage_of_each_bound_var() {
+        let usages = term("(f, x) => f (f x) (y => y)").usages();
+
+        assert_eq!(
+            usages,
+            vec![
+                (Span::new(1, 2), Usage::Many),
+                (Span::new(4, 5), Usage::Once),
+                (Span::new(19, 20), Usage::Once),
+            ]
+        );
+    }
+
+    #[test]
+    fn usages_respect_shadowing_and_let() {
+        let usages: Vec<Usage> = term("x => y => let z = y in (x => z) x")
+            .usages()
+            .into_iter()
+            .map(|(_, usage)| usage)
+            .collect();
+
+        assert_eq!(
+            usages,
+            vec![
+                Usage::OnceUnderBinder,
+                Usage::Once,
+                Usage::OnceUnderBinder,
+                Usage::Unused
+            ]
+        );
+    }
+
+    #[test]
+    fn let_vars_are_only_bound_in_the_body() {
+        let errors = term("let x = x in x").lower(&Defs::new()).unwrap_err();
+
+        let messages: Vec<&str> = errors.iter().map(SimpleError::message).collect();
+        assert_eq!(messages, vec!["unbound variable `x`"]);
+    }
+
+    #[test]
+    fn wildcards_bind_a_slot_that_cant_be_referred_to() {
+        let wildcard = term("(x, _) => x").lower(&Defs::new()).unwrap();
+        let named = term("(x, y) => x").lower(&Defs::new()).unwrap();
+        assert!(wildcard.norm().alpha_eq(&named.norm()));
+
+        // The slot still counts, so `x` keeps its index
+        let lowered = term("x => _ => x").lower(&Defs::new()).unwrap();
+        assert!(lowered.alpha_eq(&named));
+
+        let errors = term("_ => _").lower(&Defs::new()).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message(),
+            "`_` can't be referred to, since it binds nothing"
+        );
+        assert_eq!(*errors[0].span(), Span::new(5, 6));
+    }
+}
@@ -1,6 +1 @@
-mod errors;
-mod nbe;
-mod source;
-mod syntax;
-
 fn main() {}
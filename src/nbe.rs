@@ -1,14 +1,31 @@
+mod debug;
+mod path;
+mod sexp;
+mod ski;
+mod usage;
+mod whnf;
+
+pub use self::path::{Path, Step};
+pub use self::sexp::ParseSexpError;
+pub use self::ski::SkiTerm;
+pub use self::usage::Usage;
+
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt;
 use std::rc::Rc;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Name(Rc<String>);
 
 impl Name {
     pub fn new(name: impl Into<String>) -> Self {
         Name(Rc::new(name.into()))
     }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 }
 
 impl Name {
@@ -28,6 +45,34 @@ impl Name {
             }
         }
     }
+
+    /// Like `freshen_in`, but (on a collision) appends a numeric suffix
+    /// instead of ticks: `x` becomes `x1`, `x2`, and so on. A trailing number
+    /// already on the name is replaced, so `x1` also freshens to `x2`.
+    ///
+    /// `next_suffixes` records, per base name, the suffix to try first. It
+    /// should be shared between calls that freshen against the same names, so
+    /// that repeatedly freshening one base doesn't re-try every suffix.
+    pub fn freshen_numeric_in(
+        &self,
+        used: &List<Name>,
+        next_suffixes: &mut HashMap<String, usize>,
+    ) -> Name {
+        if !used.includes(self) {
+            return self.clone();
+        }
+
+        let base = self.0.trim_end_matches(|c: char| c.is_ascii_digit());
+        let next_suffix = next_suffixes.entry(base.to_string()).or_insert(1);
+        loop {
+            let candidate = format!("{}{}", base, next_suffix);
+            *next_suffix += 1;
+
+            if !used.includes(&candidate) {
+                return Name(Rc::new(candidate));
+            }
+        }
+    }
 }
 
 impl AsRef<Name> for Name {
@@ -46,9 +91,21 @@ impl AsRef<String> for Name {
 pub struct Term(Rc<_Term>);
 
 pub enum _Term {
-    Index { index: usize },
-    Abs { name: Name, body: Term },
-    App { rator: Term, rand: Term },
+    Index {
+        index: usize,
+    },
+    /// A reference to a top-level definition (see `Defs`).
+    Global {
+        name: Name,
+    },
+    Abs {
+        name: Name,
+        body: Term,
+    },
+    App {
+        rator: Term,
+        rand: Term,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -64,8 +121,17 @@ enum _Value {
 pub struct Stuck(Rc<_Stuck>);
 
 enum _Stuck {
-    Index { binder_count: usize },
-    App { op: Stuck, arg: Value },
+    Index {
+        binder_count: usize,
+    },
+    /// A global without a definition, which is kept as-is.
+    Global {
+        name: Name,
+    },
+    App {
+        op: Stuck,
+        arg: Value,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -79,14 +145,22 @@ enum ThunkContent {
 
 impl Thunk {
     pub fn thaw(&self) -> Value {
+        Meter::unlimited().run(|meter| self.thaw_in(meter))
+    }
+
+    fn thaw_in(&self, meter: &mut Meter) -> Result<Value, Halt> {
         let mut content = self.0.borrow_mut();
         match &*content {
             ThunkContent::Frozen { term, env } => {
-                let value = term.eval(env);
+                meter.stats.thunks_forced += 1;
+                let value = term.eval_in(env, meter)?;
                 *content = ThunkContent::Thawed(value.clone());
-                value
+                Ok(value)
+            }
+            ThunkContent::Thawed(value) => {
+                meter.stats.thunk_hits += 1;
+                Ok(value.clone())
             }
-            ThunkContent::Thawed(value) => value.clone(),
         }
     }
 
@@ -95,30 +169,345 @@ impl Thunk {
     }
 }
 
-pub type Env = List<Value>;
+/// A step budget for `norm_with_fuel` that's generous enough for any
+/// reasonable term.
+pub const DEFAULT_FUEL: usize = 1_000_000;
+
+/// The most evaluations (and quotations) that a budgeted normalization (e.g.
+/// `norm_with_fuel`) nests within one another. Each beta reduction in a chain
+/// like that of `(x => x x) (x => x x)` nests another, so without this limit a
+/// step budget like `DEFAULT_FUEL` would overflow the stack long before it ran
+/// out.
+pub const MAX_EVAL_DEPTH: usize = 1000;
+
+/// The error produced when normalization exceeds its budget of steps, or
+/// nests evaluations too deeply to continue.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Diverged {
+    OutOfSteps { max_steps: usize },
+    TooDeep { max_depth: usize },
+}
+
+impl fmt::Display for Diverged {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diverged::OutOfSteps { max_steps } => {
+                write!(f, "evaluation exceeded {} steps", max_steps)
+            }
+            Diverged::TooDeep { max_depth } => {
+                write!(f, "evaluation nested more than {} levels deep", max_depth)
+            }
+        }
+    }
+}
+
+/// The error produced when a quoted term would exceed its budget of nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TooLarge {
+    pub max_nodes: usize,
+}
+
+impl fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "result too large to display (> {} nodes)",
+            self.max_nodes
+        )
+    }
+}
+
+/// The order in which arguments are evaluated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Arguments are frozen into thunks, which are only evaluated (once) if
+    /// they're needed.
+    CallByName,
+    /// Arguments are evaluated before they're passed, even if they're never
+    /// needed.
+    CallByValue,
+}
+
+/// The ways in which a `Meter` can cut evaluation short.
+#[derive(Debug)]
+enum Halt {
+    Diverged(Diverged),
+    TooLarge(TooLarge),
+}
+
+impl From<Diverged> for Halt {
+    fn from(diverged: Diverged) -> Self {
+        Halt::Diverged(diverged)
+    }
+}
+
+impl From<TooLarge> for Halt {
+    fn from(too_large: TooLarge) -> Self {
+        Halt::TooLarge(too_large)
+    }
+}
+
+/// Counts of the work done while normalizing a term (see
+/// `Term::norm_instrumented`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NormStats {
+    /// Closures that were applied to an argument.
+    pub beta_reductions: usize,
+    /// Thunks that were evaluated for the first time.
+    pub thunks_forced: usize,
+    /// Thunks whose value was reused, since they'd already been evaluated.
+    pub thunk_hits: usize,
+    /// Closures built by evaluating an abstraction.
+    pub closures_allocated: usize,
+}
+
+/// The state threaded through evaluation: budgets of beta reductions and
+/// quoted nodes and, optionally, a trace of the bindings introduced along the
+/// way.
+struct Meter {
+    max_steps: Option<usize>,
+    steps: usize,
+    max_nodes: Option<usize>,
+    nodes: usize,
+    /// The number of evaluations currently nested within one another.
+    depth: usize,
+    /// Whether quoting should also eta-reduce.
+    eta: bool,
+    strategy: Strategy,
+    /// The bindings introduced so far (if they're being traced), each along
+    /// with the binder count at which it was introduced.
+    trace: Option<Vec<(Name, Value, usize)>>,
+    /// The number of binders that quoting has descended under.
+    binder_count: usize,
+    stats: NormStats,
+    /// Memoized quotations of values (if any are being reused).
+    cache: Option<QuoteCache>,
+}
+
+impl Meter {
+    fn unlimited() -> Self {
+        Meter {
+            max_steps: None,
+            steps: 0,
+            max_nodes: None,
+            nodes: 0,
+            depth: 0,
+            eta: false,
+            strategy: Strategy::CallByName,
+            trace: None,
+            binder_count: 0,
+            stats: NormStats::default(),
+            cache: None,
+        }
+    }
+
+    fn limited(max_steps: usize) -> Self {
+        Meter {
+            max_steps: Some(max_steps),
+            ..Meter::unlimited()
+        }
+    }
+
+    fn sized(max_nodes: usize) -> Self {
+        Meter {
+            max_nodes: Some(max_nodes),
+            ..Meter::unlimited()
+        }
+    }
+
+    fn traced() -> Self {
+        Meter {
+            trace: Some(Vec::new()),
+            ..Meter::unlimited()
+        }
+    }
+
+    /// Records that `name` has been bound to `value` (if tracing).
+    fn bind(&mut self, name: &Name, value: &Value) {
+        if let Some(trace) = &mut self.trace {
+            trace.push((name.clone(), value.clone(), self.binder_count));
+        }
+    }
+
+    /// Records a single beta reduction, failing if the budget is exhausted.
+    fn burn(&mut self) -> Result<(), Diverged> {
+        match self.max_steps {
+            Some(max_steps) if self.steps >= max_steps => Err(Diverged::OutOfSteps { max_steps }),
+            _ => {
+                self.steps += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Runs `f` as an evaluation nested within the current one, failing
+    /// (when budgeted) if that nests evaluations more than `MAX_EVAL_DEPTH`
+    /// deep.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Meter) -> Result<T, Halt>) -> Result<T, Halt> {
+        if self.max_steps.is_some() && self.depth >= MAX_EVAL_DEPTH {
+            let max_depth = MAX_EVAL_DEPTH;
+            return Err(Diverged::TooDeep { max_depth }.into());
+        }
+
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Records a single quoted node, failing if the budget is exhausted.
+    fn grow(&mut self) -> Result<(), TooLarge> {
+        match self.max_nodes {
+            Some(max_nodes) if self.nodes >= max_nodes => Err(TooLarge { max_nodes }),
+            _ => {
+                self.nodes += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Runs `f`, which can't diverge (in the sense of exceeding its budget)
+    /// since this meter's budget is unlimited.
+    fn run<T>(mut self, f: impl FnOnce(&mut Meter) -> Result<T, Halt>) -> T {
+        self.run_mut(f)
+    }
+
+    /// Like `run`, but doesn't consume this meter, so that its trace may be
+    /// inspected afterwards.
+    fn run_mut<T>(&mut self, f: impl FnOnce(&mut Meter) -> Result<T, Halt>) -> T {
+        debug_assert!(self.max_steps.is_none() && self.max_nodes.is_none());
+        match f(self) {
+            Ok(result) => result,
+            Err(_) => unreachable!("an unlimited budget was exhausted"),
+        }
+    }
+}
 
 impl Term {
     pub fn norm(&self) -> Term {
-        let val = self.eval(&Env::new());
-        val.quote()
+        self.norm_with(Strategy::CallByName)
+    }
+
+    /// Like `norm`, but produces a beta-eta normal form: any abstraction of
+    /// the form `x => f x` (where `x` doesn't occur in `f`) becomes `f`.
+    pub fn norm_eta(&self) -> Term {
+        let meter = Meter {
+            eta: true,
+            ..Meter::unlimited()
+        };
+        meter.run(|meter| self.norm_in(&Env::new(), meter))
+    }
+
+    /// Like `norm`, but passes arguments according to `strategy`, including
+    /// while normalizing under binders. Unlike `eval_with` followed by
+    /// `quote`, this normalizes entirely with the one strategy.
+    pub fn norm_with(&self, strategy: Strategy) -> Term {
+        let meter = Meter {
+            strategy,
+            ..Meter::unlimited()
+        };
+        meter.run(|meter| self.norm_in(&Env::new(), meter))
+    }
+
+    /// Like `norm`, but also produces every binding introduced during
+    /// normalization (both by applying abstractions and by quoting under
+    /// them), in the order they occurred.
+    ///
+    /// Note that quoting the bound values forces them, so unlike `norm`, this
+    /// won't terminate on terms that discard divergent arguments.
+    pub fn norm_traced(&self) -> (Term, Vec<(Name, Term)>) {
+        let mut meter = Meter::traced();
+        let term = meter.run_mut(|meter| self.norm_in(&Env::new(), meter));
+
+        let trace = meter
+            .trace
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value, binder_count)| {
+                let term = value.quote_from(binder_count, &List::new());
+                (name, term)
+            })
+            .collect();
+
+        (term, trace)
+    }
+
+    /// Like `norm`, but also counts the work done along the way, which shows
+    /// how much a term benefits from sharing thunks.
+    pub fn norm_instrumented(&self) -> (Term, NormStats) {
+        let mut meter = Meter::unlimited();
+        let term = meter.run_mut(|meter| self.norm_in(&Env::new(), meter));
+        (term, meter.stats)
+    }
+
+    /// Like `norm`, but gives up once `max_steps` beta reductions have been
+    /// performed.
+    pub fn norm_with_fuel(&self, max_steps: usize) -> Result<Term, Diverged> {
+        self.norm_against(&Defs::new(), max_steps)
     }
 
+    /// Like `norm_with_fuel`, but resolves globals against `defs`. Each
+    /// definition is only unfolded once the evaluation reaches a use of it.
+    pub fn norm_against(&self, defs: &Defs, max_steps: usize) -> Result<Term, Diverged> {
+        let mut meter = Meter::limited(max_steps);
+        match self.norm_in(&Env::with_defs(defs.clone()), &mut meter) {
+            Ok(term) => Ok(term),
+            Err(Halt::Diverged(diverged)) => Err(diverged),
+            Err(Halt::TooLarge(_)) => unreachable!("no node budget was set"),
+        }
+    }
+
+    /// Evaluates this term call-by-name (see `eval_with`).
     pub fn eval(&self, env: &Env) -> Value {
+        self.eval_with(env, Strategy::CallByName)
+    }
+
+    /// Evaluates this term, passing arguments according to `strategy`. Note
+    /// that under `CallByValue`, evaluation doesn't terminate if any argument
+    /// diverges, even one that's discarded.
+    ///
+    /// Only evaluation itself honors `strategy`: quoting the result evaluates
+    /// under binders call-by-name (see `norm_with`).
+    pub fn eval_with(&self, env: &Env, strategy: Strategy) -> Value {
+        let meter = Meter {
+            strategy,
+            ..Meter::unlimited()
+        };
+        meter.run(|meter| self.eval_in(env, meter))
+    }
+
+    /// Evaluates (in `env`) and quotes this term, both against `meter`.
+    fn norm_in(&self, env: &Env, meter: &mut Meter) -> Result<Term, Halt> {
+        let val = self.eval_in(env, meter)?;
+        val.quote_in(0, &List::new(), meter)
+    }
+
+    fn eval_in(&self, env: &Env, meter: &mut Meter) -> Result<Value, Halt> {
         match &*self.0 {
-            _Term::Index { index } => env.get(*index).map(Clone::clone).unwrap(),
-            _Term::Abs { name, body } => Value::closure(name.clone(), body.clone(), env.clone()),
-            _Term::App { rator, rand } => {
-                let op = rator.eval(env);
-                let rand = rand.eval_or_freeze(env);
-                op.apply(rand)
+            _Term::Index { index } => Ok(env.get(*index).unwrap()),
+            _Term::Global { name } => match env.defs.get(name) {
+                Some(value) => Ok(value),
+                None => Ok(Value::stuck(Stuck::global(name.clone()))),
+            },
+            _Term::Abs { name, body } => {
+                meter.stats.closures_allocated += 1;
+                Ok(Value::closure(name.clone(), body.clone(), env.clone()))
             }
+            _Term::App { rator, rand } => meter.nested(|meter| {
+                let op = rator.eval_in(env, meter)?;
+                let rand = rand.eval_or_freeze(env, meter)?;
+                op.apply_in(rand, meter)
+            }),
         }
     }
 
-    fn eval_or_freeze(&self, env: &Env) -> Value {
-        match &*self.0 {
-            _Term::App { .. } => Value::thunk(self.clone(), env.clone()),
-            _ => self.eval(env),
+    /// Evaluates this argument term according to the meter's strategy.
+    fn eval_or_freeze(&self, env: &Env, meter: &mut Meter) -> Result<Value, Halt> {
+        match (&*self.0, meter.strategy) {
+            (_Term::App { .. }, Strategy::CallByName) => {
+                Ok(Value::thunk(self.clone(), env.clone()))
+            }
+            _ => self.eval_in(env, meter),
         }
     }
 
@@ -126,6 +515,10 @@ impl Term {
         Term(Rc::new(_Term::Index { index }))
     }
 
+    pub fn global(name: Name) -> Self {
+        Term(Rc::new(_Term::Global { name }))
+    }
+
     pub fn abs(name: Name, body: Term) -> Self {
         Term(Rc::new(_Term::Abs { name, body }))
     }
@@ -135,17 +528,156 @@ impl Term {
     }
 }
 
+impl Term {
+    /// Normalizes this term one leftmost-outermost reduction at a time,
+    /// invoking `cb` with the intermediate term after each reduction. At most
+    /// `fuel` reductions are performed, and the last term reached is returned.
+    pub fn norm_with_callback(&self, fuel: usize, cb: &mut dyn FnMut(&Term)) -> Term {
+        let mut term = self.clone();
+        for _ in 0..fuel {
+            match term.step() {
+                Some(next) => {
+                    cb(&next);
+                    term = next;
+                }
+                None => break,
+            }
+        }
+        term
+    }
+
+    /// Performs exactly one leftmost-outermost beta reduction, producing
+    /// `None` if this term is already in normal form. Substitution shifts
+    /// indices as needed, so no variables are captured. Globals are never
+    /// unfolded.
+    pub fn step(&self) -> Option<Term> {
+        match &*self.0 {
+            _Term::Index { .. } | _Term::Global { .. } => None,
+            _Term::Abs { name, body } => body.step().map(|body| Term::abs(name.clone(), body)),
+            _Term::App { rator, rand } => match &*rator.0 {
+                _Term::Abs { body, .. } => Some(body.subst_top(rand)),
+                _ => rator
+                    .step()
+                    .map(|rator| Term::app(rator, rand.clone()))
+                    .or_else(|| rand.step().map(|rand| Term::app(rator.clone(), rand))),
+            },
+        }
+    }
+
+    /// Substitutes `arg` for the innermost binder of this (abstraction body)
+    /// term, removing that binder.
+    fn subst_top(&self, arg: &Term) -> Term {
+        self.subst(0, &arg.shift(1, 0)).shift(-1, 0)
+    }
+
+    /// Substitutes `replacement` for the free variable with index `target`.
+    /// `replacement` is shifted as it passes under each binder, so that its
+    /// own free variables are never captured. Unlike `subst_top`, no binder
+    /// is removed, so indices above `target` are left as they are.
+    pub fn subst(&self, target: usize, replacement: &Term) -> Term {
+        match &*self.0 {
+            _Term::Index { index } if *index == target => replacement.clone(),
+            _Term::Index { .. } | _Term::Global { .. } => self.clone(),
+            _Term::Abs { name, body } => {
+                let body = body.subst(target + 1, &replacement.shift(1, 0));
+                Term::abs(name.clone(), body)
+            }
+            _Term::App { rator, rand } => Term::app(
+                rator.subst(target, replacement),
+                rand.subst(target, replacement),
+            ),
+        }
+    }
+
+    /// Tests if the variable with index `target` occurs in this term.
+    fn mentions(&self, target: usize) -> bool {
+        match &*self.0 {
+            _Term::Index { index } => *index == target,
+            _Term::Global { .. } => false,
+            _Term::Abs { body, .. } => body.mentions(target + 1),
+            _Term::App { rator, rand } => rator.mentions(target) || rand.mentions(target),
+        }
+    }
+
+    /// Shifts every index at or above `cutoff` by `by`. A `cutoff` of 0
+    /// shifts exactly the free variables of this term, as when it's moved
+    /// under (or out from under) `by` binders.
+    pub fn shift(&self, by: isize, cutoff: usize) -> Term {
+        match &*self.0 {
+            _Term::Index { index } if *index >= cutoff => {
+                Term::index((*index as isize + by) as usize)
+            }
+            _Term::Index { .. } | _Term::Global { .. } => self.clone(),
+            _Term::Abs { name, body } => Term::abs(name.clone(), body.shift(by, cutoff + 1)),
+            _Term::App { rator, rand } => {
+                Term::app(rator.shift(by, cutoff), rand.shift(by, cutoff))
+            }
+        }
+    }
+}
+
+impl Term {
+    /// Finds the stuck applications in this (normalized) term whose head is a
+    /// global that `is_constant` reports as not being a function (e.g. one
+    /// without a definition). Each offending application is reported once.
+    pub fn constant_applications(&self, is_constant: &dyn Fn(&Name) -> bool) -> Vec<Name> {
+        let mut found = Vec::new();
+        self.collect_constant_applications(false, is_constant, &mut found);
+        found
+    }
+
+    fn collect_constant_applications(
+        &self,
+        in_rator: bool,
+        is_constant: &dyn Fn(&Name) -> bool,
+        found: &mut Vec<Name>,
+    ) {
+        match &*self.0 {
+            _Term::Index { .. } | _Term::Global { .. } => {}
+            _Term::Abs { body, .. } => {
+                body.collect_constant_applications(false, is_constant, found)
+            }
+            _Term::App { rator, rand } => {
+                // Only inspect the head once per application spine
+                if !in_rator {
+                    if let _Term::Global { name } = &*self.head().0 {
+                        if is_constant(name) {
+                            found.push(name.clone());
+                        }
+                    }
+                }
+                rator.collect_constant_applications(true, is_constant, found);
+                rand.collect_constant_applications(false, is_constant, found);
+            }
+        }
+    }
+
+    fn head(&self) -> &Term {
+        match &*self.0 {
+            _Term::App { rator, .. } => rator.head(),
+            _ => self,
+        }
+    }
+}
+
 impl Value {
     pub fn apply(&self, arg: Value) -> Value {
+        Meter::unlimited().run(|meter| self.apply_in(arg, meter))
+    }
+
+    fn apply_in(&self, arg: Value, meter: &mut Meter) -> Result<Value, Halt> {
         match &*self.0 {
-            _Value::Closure { body, env, .. } => {
+            _Value::Closure { name, body, env } => {
+                meter.burn()?;
+                meter.stats.beta_reductions += 1;
+                meter.bind(name, &arg);
                 let env = env.push(arg);
-                body.eval(&env)
+                body.eval_in(&env, meter)
             }
-            _Value::Stuck(op) => Value::stuck(Stuck::app(op.clone(), arg)),
+            _Value::Stuck(op) => Ok(Value::stuck(Stuck::app(op.clone(), arg))),
             _Value::Thunk(thunk) => {
-                let op = thunk.thaw();
-                op.apply(arg)
+                let op = thunk.thaw_in(meter)?;
+                op.apply_in(arg, meter)
             }
         }
     }
@@ -154,24 +686,102 @@ impl Value {
         self.quote_from(0, &List::new())
     }
 
-    fn quote_from(&self, binder_count: usize, used_names: &List<Name>) -> Term {
-        match &*self.0 {
+    /// Like `quote`, but gives up once the quoted term would have more than
+    /// `max_nodes` nodes (counting every index, abstraction, and application).
+    pub fn quote_with_max_nodes(&self, max_nodes: usize) -> Result<Term, TooLarge> {
+        match self.quote_in(0, &List::new(), &mut Meter::sized(max_nodes)) {
+            Ok(term) => Ok(term),
+            Err(Halt::TooLarge(too_large)) => Err(too_large),
+            Err(Halt::Diverged(_)) => unreachable!("no step budget was set"),
+        }
+    }
+
+    /// Like `quote`, but reuses the result of any previous quote of this very
+    /// value (by identity) recorded in `cache`, including those of values
+    /// shared within it (e.g. an argument that's referred to twice).
+    pub fn quote_cached(&self, cache: &mut QuoteCache) -> Term {
+        let mut meter = Meter {
+            cache: Some(std::mem::take(cache)),
+            ..Meter::unlimited()
+        };
+        let term = meter.run_mut(|meter| self.quote_in(0, &List::new(), meter));
+        *cache = meter.cache.take().expect("the cache is kept by the meter");
+        term
+    }
+
+    /// Quotes this value beneath binders named `used_names` (innermost
+    /// first). Each binder keeps its original name unless one of those binders
+    /// already has it; binders that are merely siblings don't collide.
+    fn quote_in(
+        &self,
+        binder_count: usize,
+        used_names: &List<Name>,
+        meter: &mut Meter,
+    ) -> Result<Term, Halt> {
+        let cache = match &mut meter.cache {
+            Some(cache) => cache,
+            None => return self.quote_uncached(binder_count, used_names, meter),
+        };
+        // A value's quotation depends on the binders it's quoted beneath
+        let key = (Rc::as_ptr(&self.0), binder_count);
+        if let Some((_, names, term)) = cache.quoted.get(&key) {
+            if names.ptr_eq(used_names) || names.iter().eq(used_names.iter()) {
+                cache.hits += 1;
+                return Ok(term.clone());
+            }
+        }
+        cache.misses += 1;
+
+        let term = self.quote_uncached(binder_count, used_names, meter)?;
+        if let Some(cache) = &mut meter.cache {
+            let entry = (self.clone(), used_names.clone(), term.clone());
+            cache.quoted.insert(key, entry);
+        }
+        Ok(term)
+    }
+
+    fn quote_uncached(
+        &self,
+        binder_count: usize,
+        used_names: &List<Name>,
+        meter: &mut Meter,
+    ) -> Result<Term, Halt> {
+        meter.nested(|meter| match &*self.0 {
             _Value::Closure { name, body, env } => {
                 // Update binder count to account for new binder
                 let new_binder_count = binder_count + 1;
                 let proxy_arg = Value::stuck(Stuck::index(new_binder_count));
-                let body_val = body.eval(&env.push(proxy_arg));
+                meter.binder_count = new_binder_count;
+                meter.bind(name, &proxy_arg);
+                let body_val = body.eval_in(&env.push(proxy_arg), meter)?;
                 let name = name.freshen_in(used_names);
                 let used_names = used_names.push(name.clone());
 
-                Term::abs(name, body_val.quote_from(new_binder_count, &used_names))
+                meter.grow()?;
+                let body = body_val.quote_in(new_binder_count, &used_names, meter)?;
+                meter.binder_count = binder_count;
+
+                if meter.eta {
+                    if let _Term::App { rator, rand } = &*body.0 {
+                        if let _Term::Index { index: 0 } = &*rand.0 {
+                            if !rator.mentions(0) {
+                                return Ok(rator.shift(-1, 0));
+                            }
+                        }
+                    }
+                }
+                Ok(Term::abs(name, body))
             }
-            _Value::Stuck(stuck) => stuck.quote_from(binder_count, used_names),
+            _Value::Stuck(stuck) => stuck.quote_in(binder_count, used_names, meter),
             _Value::Thunk(thunk) => {
-                let val = thunk.thaw();
-                val.quote_from(binder_count, used_names)
+                let val = thunk.thaw_in(meter)?;
+                val.quote_in(binder_count, used_names, meter)
             }
-        }
+        })
+    }
+
+    fn quote_from(&self, binder_count: usize, used_names: &List<Name>) -> Term {
+        Meter::unlimited().run(|meter| self.quote_in(binder_count, used_names, meter))
     }
 
     pub fn closure(name: Name, body: Term, env: Env) -> Self {
@@ -187,105 +797,577 @@ impl Value {
     }
 }
 
-impl Stuck {
-    pub fn quote_from(&self, binder_count: usize, used_names: &List<Name>) -> Term {
-        match &*self.0 {
-            _Stuck::Index {
-                binder_count: creation_binder_count,
-            } => {
-                let index = binder_count - creation_binder_count;
-                Term::index(index)
-            }
-            _Stuck::App { op, arg } => {
-                let rator = op.quote_from(binder_count, used_names);
-                let rand = arg.quote_from(binder_count, used_names);
-                Term::app(rator, rand)
-            }
+impl Value {
+    /// Recognizes the Church booleans `(t, f) => t` (`true`) and
+    /// `(t, f) => f` (`false`) in this value's normal form.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.quote().classify() {
+            Some(KnownForm::True) => Some(true),
+            Some(KnownForm::False) => Some(false),
+            _ => None,
         }
     }
 
-    pub fn index(binder_count: usize) -> Self {
-        Stuck(Rc::new(_Stuck::Index { binder_count }))
+    /// Recognizes a Church pair `f => f a b` in this value's normal form,
+    /// producing its components `a` and `b`.
+    pub fn as_pair(&self) -> Option<(Term, Term)> {
+        match self.quote().classify() {
+            Some(KnownForm::Pair(first, second)) => Some((first, second)),
+            _ => None,
+        }
     }
+}
 
-    pub fn app(op: Stuck, arg: Value) -> Self {
-        Stuck(Rc::new(_Stuck::App { op, arg }))
-    }
+/// The standard Church encodings that `Term::classify` recognizes.
+#[derive(Debug, Clone)]
+pub enum KnownForm {
+    /// `(t, f) => t`
+    True,
+    /// `(t, f) => f`
+    False,
+    /// `f => f a b`, where neither `a` nor `b` refers to `f`
+    Pair(Term, Term),
+    /// `p => p ((a, b) => a)`
+    Fst,
+    /// `p => p ((a, b) => b)`
+    Snd,
 }
 
-impl fmt::Debug for _Term {
+impl fmt::Display for KnownForm {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            _Term::Index { index } => write!(f, "{}", index),
-            _Term::Abs { name, body } => write!(f, "{:?} => {:?}", name, body),
-            _Term::App { rator, rand } => write!(f, "({:?} {:?})", rator, rand),
+            KnownForm::True => write!(f, "true"),
+            KnownForm::False => write!(f, "false"),
+            KnownForm::Pair(first, second) => {
+                write!(f, "⟨{}, {}⟩", first.to_source(), second.to_source())
+            }
+            KnownForm::Fst => write!(f, "fst"),
+            KnownForm::Snd => write!(f, "snd"),
         }
     }
 }
 
-impl fmt::Debug for _Value {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            _Value::Closure { name, body, env } => {
-                write!(f, "<{:?} : {:?} in {:?}>", name, body, env)
-            }
-            _Value::Stuck(stuck) => write!(f, "{:?}", stuck),
-            _Value::Thunk(thunk) => write!(f, "{:?}", thunk),
+impl Term {
+    /// Recognizes this (normalized) term as one of the `KnownForm`s, purely
+    /// by its structure.
+    pub fn classify(&self) -> Option<KnownForm> {
+        let body = match &*self.0 {
+            _Term::Abs { body, .. } => body,
+            _ => return None,
+        };
+
+        match &*body.0 {
+            _Term::Abs { body, .. } => match &*body.0 {
+                _Term::Index { index: 1 } => Some(KnownForm::True),
+                _Term::Index { index: 0 } => Some(KnownForm::False),
+                _ => None,
+            },
+            _Term::App { rator, rand } => match (&*rator.0, rand.classify()) {
+                (_Term::Index { index: 0 }, Some(KnownForm::True)) => Some(KnownForm::Fst),
+                (_Term::Index { index: 0 }, Some(KnownForm::False)) => Some(KnownForm::Snd),
+                (_Term::App { rator, rand: first }, _) => match &*rator.0 {
+                    // The components mustn't refer to the pair's binder
+                    _Term::Index { index: 0 } if !first.mentions(0) && !rand.mentions(0) => {
+                        Some(KnownForm::Pair(first.shift(-1, 0), rand.shift(-1, 0)))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
         }
     }
 }
 
-impl fmt::Debug for _Stuck {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            _Stuck::Index { binder_count } => {
-                write!(f, "{{{}}}", binder_count)
+impl Term {
+    /// Recognizes the Church numeral `s => z => s (s (... z))` in this
+    /// (normalized) term, producing the number of applications of `s`.
+    pub fn as_church_numeral(&self) -> Option<u64> {
+        if let _Term::Abs { body, .. } = &*self.0 {
+            if let _Term::Abs { body, .. } = &*body.0 {
+                let mut count = 0;
+                let mut term = body;
+                loop {
+                    match &*term.0 {
+                        _Term::Index { index: 0 } => return Some(count),
+                        _Term::App { rator, rand } => match &*rator.0 {
+                            _Term::Index { index: 1 } => {
+                                count += 1;
+                                term = rand;
+                            }
+                            _ => return None,
+                        },
+                        _ => return None,
+                    }
+                }
             }
-            _Stuck::App { op, arg } => write!(f, "{{{:?} @ {:?}}}", op, arg),
         }
+        None
     }
 }
 
-impl fmt::Debug for ThunkContent {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ThunkContent::Frozen { term, env } => write!(f, "<<{:?} in {:?}>>", term, env),
-            ThunkContent::Thawed(value) => write!(f, "<<{:?}>>", value),
+impl Term {
+    /// Tests if this term and `other` are equal up to the names of their
+    /// binders.
+    pub fn alpha_eq(&self, other: &Term) -> bool {
+        match (&*self.0, &*other.0) {
+            (_Term::Index { index }, _Term::Index { index: other }) => index == other,
+            (_Term::Global { name }, _Term::Global { name: other }) => name == other,
+            (_Term::Abs { body, .. }, _Term::Abs { body: other, .. }) => body.alpha_eq(other),
+            (
+                _Term::App { rator, rand },
+                _Term::App {
+                    rator: other_rator,
+                    rand: other_rand,
+                },
+            ) => rator.alpha_eq(other_rator) && rand.alpha_eq(other_rand),
+            _ => false,
         }
     }
-}
 
-#[derive(Debug)]
-pub struct List<T>(Rc<_List<T>>);
+    /// Like `alpha_eq`, but binders must also have the same names.
+    pub fn structurally_eq(&self, other: &Term) -> bool {
+        match (&*self.0, &*other.0) {
+            (
+                _Term::Abs { name, body },
+                _Term::Abs {
+                    name: other_name,
+                    body: other,
+                },
+            ) => name == other_name && body.structurally_eq(other),
+            (
+                _Term::App { rator, rand },
+                _Term::App {
+                    rator: other_rator,
+                    rand: other_rand,
+                },
+            ) => rator.structurally_eq(other_rator) && rand.structurally_eq(other_rand),
+            _ => self.alpha_eq(other),
+        }
+    }
 
-enum _List<T> {
-    Empty,
-    Cons(T, List<T>),
+    /// Produces a name-canonical, maximally shared version of this term (see
+    /// `Canonicalizer`).
+    pub fn canonicalize(&self) -> Term {
+        Canonicalizer::default().canonicalize(self)
+    }
 }
 
-impl<T> List<T> {
-    pub fn new() -> Self {
-        List(Rc::new(_List::Empty))
-    }
+/// Canonicalizes terms by giving every binder the same name and hash-consing
+/// identical subterms, so that alpha-equivalent (sub)terms canonicalized by
+/// the same `Canonicalizer` are represented by the very same `Rc`.
+#[derive(Default)]
+pub struct Canonicalizer {
+    /// The canonical terms produced so far, keyed by their shape. Children are
+    /// identified by address, which is sound because they're canonical (and
+    /// kept alive by this table).
+    seen: HashMap<Shape, Term>,
+    name: Option<Name>,
+}
 
-    pub fn push(&self, x: T) -> Self {
-        List(Rc::new(_List::Cons(x, self.clone())))
-    }
+#[derive(PartialEq, Eq, Hash)]
+enum Shape {
+    Index(usize),
+    Global(Name),
+    Abs(*const _Term),
+    App(*const _Term, *const _Term),
+}
 
-    pub fn get(&self, i: usize) -> Option<&T> {
-        match &*self.0 {
-            _List::Empty => None,
-            _List::Cons(first, rest) => {
-                if i == 0 {
-                    Some(first)
-                } else {
-                    rest.get(i - 1)
-                }
+impl Canonicalizer {
+    /// The name given to every canonical binder.
+    const BINDER_NAME: &'static str = "x";
+
+    pub fn canonicalize(&mut self, term: &Term) -> Term {
+        match &*term.0 {
+            _Term::Index { index } => self.intern(Shape::Index(*index), || Term::index(*index)),
+            _Term::Global { name } => {
+                self.intern(Shape::Global(name.clone()), || Term::global(name.clone()))
+            }
+            _Term::Abs { body, .. } => {
+                let body = self.canonicalize(body);
+                let name = self
+                    .name
+                    .get_or_insert_with(|| Name::new(Self::BINDER_NAME))
+                    .clone();
+                self.intern(Shape::Abs(Rc::as_ptr(&body.0)), || Term::abs(name, body))
+            }
+            _Term::App { rator, rand } => {
+                let rator = self.canonicalize(rator);
+                let rand = self.canonicalize(rand);
+                let shape = Shape::App(Rc::as_ptr(&rator.0), Rc::as_ptr(&rand.0));
+                self.intern(shape, || Term::app(rator, rand))
             }
         }
     }
 
-    pub fn includes<U>(&self, x: &U) -> bool
+    fn intern(&mut self, shape: Shape, make: impl FnOnce() -> Term) -> Term {
+        self.seen.entry(shape).or_insert_with(make).clone()
+    }
+}
+
+/// Memoized quotations, keyed by the identity of the quoted `Value` and the
+/// number of binders it was quoted beneath.
+#[derive(Default)]
+pub struct QuoteCache {
+    /// Each quoted value is kept alive alongside its quotation, so that its
+    /// address can't be reused by another value. The names of the binders it
+    /// was quoted beneath are kept too, since they may have been freshened
+    /// against.
+    quoted: HashMap<(*const _Value, usize), (Value, List<Name>, Term)>,
+    hits: usize,
+    misses: usize,
+}
+
+impl QuoteCache {
+    /// The number of quotes answered from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// The number of quotes that had to be computed.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+}
+
+impl Stuck {
+    pub fn quote_from(&self, binder_count: usize, used_names: &List<Name>) -> Term {
+        Meter::unlimited().run(|meter| self.quote_in(binder_count, used_names, meter))
+    }
+
+    fn quote_in(
+        &self,
+        binder_count: usize,
+        used_names: &List<Name>,
+        meter: &mut Meter,
+    ) -> Result<Term, Halt> {
+        match &*self.0 {
+            _Stuck::Index {
+                binder_count: creation_binder_count,
+            } => {
+                meter.grow()?;
+                let index = binder_count - creation_binder_count;
+                Ok(Term::index(index))
+            }
+            _Stuck::Global { name } => {
+                meter.grow()?;
+                Ok(Term::global(name.clone()))
+            }
+            _Stuck::App { op, arg } => meter.nested(|meter| {
+                meter.grow()?;
+                let rator = op.quote_in(binder_count, used_names, meter)?;
+                let rand = arg.quote_in(binder_count, used_names, meter)?;
+                Ok(Term::app(rator, rand))
+            }),
+        }
+    }
+
+    pub fn index(binder_count: usize) -> Self {
+        Stuck(Rc::new(_Stuck::Index { binder_count }))
+    }
+
+    pub fn global(name: Name) -> Self {
+        Stuck(Rc::new(_Stuck::Global { name }))
+    }
+
+    pub fn app(op: Stuck, arg: Value) -> Self {
+        Stuck(Rc::new(_Stuck::App { op, arg }))
+    }
+}
+
+impl Term {
+    /// Renders this term in lammy's surface syntax, naming each var after its
+    /// binder. Binders are renamed (by adding ticks) where they'd otherwise
+    /// shadow an enclosing binder. Free vars, which can't occur in the normal
+    /// form of a closed term, are rendered as their raw index.
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        self.write_source(&List::new(), &mut out);
+        out
+    }
+
+    fn write_source(&self, names: &List<Name>, out: &mut String) {
+        match &*self.0 {
+            _Term::Index { index } => match names.get(*index) {
+                Some(name) => out.push_str(&name.0),
+                None => out.push_str(&index.to_string()),
+            },
+            _Term::Global { name } => out.push_str(&name.0),
+            _Term::Abs { .. } => {
+                let mut names = names.clone();
+                let mut binders = Vec::new();
+                let mut body = self;
+                while let _Term::Abs { name, body: inner } = &*body.0 {
+                    let name = name.freshen_in(&names);
+                    names = names.push(name.clone());
+                    binders.push(name);
+                    body = inner;
+                }
+
+                let binders: Vec<&str> = binders.iter().map(|name| name.0.as_str()).collect();
+                match binders.as_slice() {
+                    [binder] => out.push_str(binder),
+                    _ => {
+                        out.push('(');
+                        out.push_str(&binders.join(", "));
+                        out.push(')');
+                    }
+                }
+                out.push_str(" => ");
+                body.write_source_at(Prec::Abs, &names, out);
+            }
+            // Application is left-associative, so the operator needn't be
+            // parenthesized if it's an application itself, but the operand
+            // must be
+            _Term::App { rator, rand } => {
+                rator.write_source_at(Prec::App, names, out);
+                out.push(' ');
+                rand.write_source_at(Prec::Atom, names, out);
+            }
+        }
+    }
+
+    /// Writes this term where a term of at least precedence `min` is
+    /// expected, parenthesizing it if its own precedence is lower.
+    fn write_source_at(&self, min: Prec, names: &List<Name>, out: &mut String) {
+        if self.prec() < min {
+            out.push('(');
+            self.write_source(names, out);
+            out.push(')');
+        } else {
+            self.write_source(names, out);
+        }
+    }
+
+    fn prec(&self) -> Prec {
+        match &*self.0 {
+            _Term::Index { .. } | _Term::Global { .. } => Prec::Atom,
+            _Term::Abs { .. } => Prec::Abs,
+            _Term::App { .. } => Prec::App,
+        }
+    }
+}
+
+/// How tightly each sort of term binds when rendered in surface syntax, from
+/// loosest to tightest. An abstraction's body extends as far as possible, so
+/// it binds the loosest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    Abs,
+    App,
+    Atom,
+}
+
+/// Renders terms in surface syntax, just like `to_source`. (`Debug` shows the
+/// underlying de Bruijn representation instead.)
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_source())
+    }
+}
+
+impl fmt::Debug for _Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            _Term::Index { index } => write!(f, "{}", index),
+            _Term::Global { name } => write!(f, "{}", name.0),
+            _Term::Abs { name, body } => write!(f, "{:?} => {:?}", name, body),
+            _Term::App { rator, rand } => write!(f, "({:?} {:?})", rator, rand),
+        }
+    }
+}
+
+impl fmt::Debug for _Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            _Value::Closure { name, body, env } => {
+                write!(f, "<{:?} : {:?} in {:?}>", name, body, env)
+            }
+            _Value::Stuck(stuck) => write!(f, "{:?}", stuck),
+            _Value::Thunk(thunk) => write!(f, "{:?}", thunk),
+        }
+    }
+}
+
+impl fmt::Debug for _Stuck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            _Stuck::Index { binder_count } => {
+                write!(f, "{{{}}}", binder_count)
+            }
+            _Stuck::Global { name } => write!(f, "{{{}}}", name.0),
+            _Stuck::App { op, arg } => write!(f, "{{{:?} @ {:?}}}", op, arg),
+        }
+    }
+}
+
+impl fmt::Debug for ThunkContent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThunkContent::Frozen { term, env } => write!(f, "<<{:?} in {:?}>>", term, env),
+            ThunkContent::Thawed(value) => write!(f, "<<{:?}>>", value),
+        }
+    }
+}
+
+/// The values bound by the enclosing binders, innermost last, so that a de
+/// Bruijn index is an offset from the end.
+///
+/// Lookups are O(1), and clones only bump a reference count. Pushing copies
+/// the (typically shallow) environment rather than appending to a buffer
+/// shared with other environments: a closure pushed onto the buffer it
+/// closes over would form a reference cycle, and leak.
+///
+/// An environment also carries the definitions that globals refer to.
+#[derive(Clone)]
+pub struct Env {
+    values: Rc<[Value]>,
+    defs: Defs,
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Env::new()
+    }
+}
+
+impl Env {
+    pub fn new() -> Self {
+        Env::with_defs(Defs::new())
+    }
+
+    /// Creates an empty environment in which globals refer to `defs`.
+    pub fn with_defs(defs: Defs) -> Self {
+        Env {
+            values: Rc::new([]),
+            defs,
+        }
+    }
+
+    pub fn push(&self, value: Value) -> Self {
+        let mut values = Vec::with_capacity(self.values.len() + 1);
+        values.extend_from_slice(&self.values);
+        values.push(value);
+        Env {
+            values: values.into(),
+            defs: self.defs.clone(),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<Value> {
+        let position = self.values.len().checked_sub(index + 1)?;
+        Some(self.values[position].clone())
+    }
+}
+
+/// Top-level definitions, which globals are resolved against. Each
+/// definition is evaluated on demand (and at most once), and its value is
+/// shared by every use.
+///
+/// Clones share the same definitions. A definition sees only the definitions
+/// made before it, so the definitions never refer to themselves (which would
+/// form a reference cycle).
+#[derive(Clone, Default)]
+pub struct Defs(Rc<HashMap<Name, Value>>);
+
+impl Defs {
+    pub fn new() -> Self {
+        Defs::default()
+    }
+
+    /// Defines `name` as the (closed) `term`, replacing any earlier
+    /// definition.
+    pub fn define(&mut self, name: Name, term: Term) {
+        let value = Value::thunk(term, Env::with_defs(self.clone()));
+        Rc::make_mut(&mut self.0).insert(name, value);
+    }
+
+    /// Defines `name` as `value`, which may have been defined against other
+    /// definitions (e.g. those of another module), replacing any earlier
+    /// definition.
+    pub fn insert(&mut self, name: Name, value: Value) {
+        Rc::make_mut(&mut self.0).insert(name, value);
+    }
+
+    pub fn get(&self, name: &Name) -> Option<Value> {
+        self.0.get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &Name) -> bool {
+        self.0.contains_key(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Name, &Value)> {
+        self.0.iter()
+    }
+}
+
+impl fmt::Debug for Defs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+impl fmt::Debug for Env {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.values.iter().rev()).finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct List<T>(Rc<_List<T>>);
+
+enum _List<T> {
+    Empty,
+    Cons(T, List<T>),
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        List::new()
+    }
+}
+
+impl<T> List<T> {
+    pub fn new() -> Self {
+        List(Rc::new(_List::Empty))
+    }
+
+    pub fn push(&self, x: T) -> Self {
+        List(Rc::new(_List::Cons(x, self.clone())))
+    }
+
+    /// Tests if `self` and `other` are the very same list (rather than merely
+    /// equal ones).
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let mut rest = self;
+        std::iter::from_fn(move || match &*rest.0 {
+            _List::Empty => None,
+            _List::Cons(first, tail) => {
+                rest = tail;
+                Some(first)
+            }
+        })
+    }
+
+    pub fn get(&self, i: usize) -> Option<&T> {
+        match &*self.0 {
+            _List::Empty => None,
+            _List::Cons(first, rest) => {
+                if i == 0 {
+                    Some(first)
+                } else {
+                    rest.get(i - 1)
+                }
+            }
+        }
+    }
+
+    pub fn includes<U>(&self, x: &U) -> bool
     where
         U: PartialEq,
         T: AsRef<U>,
@@ -347,4 +1429,739 @@ mod tests {
         let name = Name::new("a");
         assert_eq!(name.freshen_in(&used), Name::new("a''"));
     }
+
+    #[test]
+    fn freshen_numeric() {
+        let used = List::new().push(Name::new("x")).push(Name::new("x1"));
+        let mut next_suffixes = HashMap::new();
+
+        let fresh = Name::new("x").freshen_numeric_in(&used, &mut next_suffixes);
+        assert_eq!(fresh, Name::new("x2"));
+
+        let used = used.push(fresh);
+        let fresh = Name::new("x1").freshen_numeric_in(&used, &mut next_suffixes);
+        assert_eq!(fresh, Name::new("x3"));
+
+        let fresh = Name::new("y").freshen_numeric_in(&used, &mut next_suffixes);
+        assert_eq!(fresh, Name::new("y"));
+    }
+
+    #[test]
+    fn env_indices_count_from_the_innermost_binding() {
+        let tru =
+            Term::abs(Name::new("x"), Term::abs(Name::new("y"), Term::index(1))).eval(&Env::new());
+        let fls =
+            Term::abs(Name::new("x"), Term::abs(Name::new("y"), Term::index(0))).eval(&Env::new());
+        let outer = Env::new().push(tru);
+        let inner = outer.push(fls.clone());
+        let sibling = outer.push(fls);
+
+        assert_eq!(inner.get(0).unwrap().as_bool(), Some(false));
+        assert_eq!(inner.get(1).unwrap().as_bool(), Some(true));
+        assert_eq!(sibling.get(1).unwrap().as_bool(), Some(true));
+        assert!(outer.get(1).is_none());
+    }
+
+    #[test]
+    fn quote_keeps_binder_names_that_dont_collide() {
+        // x => (y => y) (z => z)
+        let term = Term::abs(
+            Name::new("x"),
+            Term::app(
+                Term::abs(Name::new("y"), Term::index(0)),
+                Term::abs(Name::new("z"), Term::index(0)),
+            ),
+        );
+
+        assert_eq!(term.norm().to_source(), "(x, z) => z");
+    }
+
+    #[test]
+    fn quote_scopes_names_to_their_binder() {
+        // f => f (x => x) (x => x)
+        let id = Term::abs(Name::new("x"), Term::index(0));
+        let term = Term::abs(
+            Name::new("f"),
+            Term::app(Term::app(Term::index(0), id.clone()), id),
+        );
+
+        assert_eq!(
+            format!("{:?}", term.norm()),
+            format!("{:?}", term),
+            "sibling binders should both keep the name `x`"
+        );
+    }
+
+    #[test]
+    fn call_by_value_forces_discarded_arguments() {
+        // (x => y => y) ((x => x x) (x => x x))
+        let half = Term::abs(Name::new("x"), Term::app(Term::index(0), Term::index(0)));
+        let omega = Term::app(half.clone(), half);
+        let snd = Term::abs(Name::new("x"), Term::abs(Name::new("y"), Term::index(0)));
+        let term = Term::app(snd, omega);
+
+        let by_name = term.eval_with(&Env::new(), Strategy::CallByName);
+        assert!(by_name
+            .quote()
+            .alpha_eq(&Term::abs(Name::new("y"), Term::index(0))));
+
+        let mut meter = Meter {
+            strategy: Strategy::CallByValue,
+            ..Meter::limited(100)
+        };
+        let by_value = term.eval_in(&Env::new(), &mut meter);
+        assert!(matches!(by_value, Err(Halt::Diverged(..))));
+    }
+
+    #[test]
+    fn globals_are_unfolded_on_demand() {
+        // Zero = s => z => z; Suc = n => s => z => s (n s z)
+        let zero = Term::abs(Name::new("s"), Term::abs(Name::new("z"), Term::index(0)));
+        let suc = Term::abs(
+            Name::new("n"),
+            Term::abs(
+                Name::new("s"),
+                Term::abs(
+                    Name::new("z"),
+                    Term::app(
+                        Term::index(1),
+                        Term::app(Term::app(Term::index(2), Term::index(1)), Term::index(0)),
+                    ),
+                ),
+            ),
+        );
+        let mut defs = Defs::new();
+        defs.define(Name::new("Zero"), zero);
+        defs.define(Name::new("Suc"), suc);
+
+        let term = Term::app(
+            Term::global(Name::new("Suc")),
+            Term::global(Name::new("Zero")),
+        );
+        let normal = term.eval(&Env::with_defs(defs)).quote();
+
+        assert_eq!(normal.as_church_numeral(), Some(1));
+    }
+
+    #[test]
+    fn undefined_globals_stay_stuck() {
+        let mut defs = Defs::new();
+        defs.define(Name::new("Id"), Term::abs(Name::new("x"), Term::index(0)));
+
+        // Id (Suc Zero), where only `Id` is defined
+        let suc_zero = Term::app(
+            Term::global(Name::new("Suc")),
+            Term::global(Name::new("Zero")),
+        );
+        let term = Term::app(Term::global(Name::new("Id")), suc_zero.clone());
+        let normal = term.eval(&Env::with_defs(defs)).quote();
+
+        assert!(normal.alpha_eq(&suc_zero));
+        assert_eq!(normal.to_string(), "Suc Zero");
+    }
+
+    #[test]
+    fn definitions_are_evaluated_once_and_shared() {
+        // Big = (x => x) (y => y)
+        let id = |name: &str| Term::abs(Name::new(name), Term::index(0));
+        let mut defs = Defs::new();
+        defs.define(Name::new("Big"), Term::app(id("x"), id("y")));
+        let env = Env::with_defs(defs);
+        let big = Term::global(Name::new("Big"));
+
+        let steps = |term: &Term| {
+            let mut meter = Meter::limited(100);
+            let value = term.eval_in(&env, &mut meter).unwrap();
+            value.quote_in(0, &List::new(), &mut meter).unwrap();
+            meter.steps
+        };
+
+        assert_eq!(steps(&big), 1);
+        assert_eq!(steps(&big), 0);
+        assert_eq!(steps(&Term::app(big.clone(), big)), 1);
+    }
+
+    #[test]
+    fn norm_with_keeps_its_strategy_under_binders() {
+        // z => (x => y => y) ((x => x x) (x => x x))
+        let half = Term::abs(Name::new("x"), Term::app(Term::index(0), Term::index(0)));
+        let omega = Term::app(half.clone(), half);
+        let snd = Term::abs(Name::new("x"), Term::abs(Name::new("y"), Term::index(0)));
+        let term = Term::abs(Name::new("z"), Term::app(snd, omega));
+
+        let expected = Term::abs(Name::new("z"), Term::abs(Name::new("y"), Term::index(0)));
+        assert!(term.norm_with(Strategy::CallByName).alpha_eq(&expected));
+
+        // The abstraction itself is already a value, so the strategy only
+        // comes into play while quoting its body
+        let by_value = term.eval_with(&Env::new(), Strategy::CallByValue);
+        assert!(by_value.quote().alpha_eq(&expected));
+
+        let mut meter = Meter {
+            strategy: Strategy::CallByValue,
+            ..Meter::limited(100)
+        };
+        assert!(matches!(
+            term.norm_in(&Env::new(), &mut meter),
+            Err(Halt::Diverged(..))
+        ));
+    }
+
+    #[test]
+    fn strategies_agree_on_terminating_terms() {
+        // (m, n) => s => m (n s), applied to 2 and 3
+        let numeral = |n: usize| {
+            let body = (0..n).fold(Term::index(0), |acc, _| Term::app(Term::index(1), acc));
+            Term::abs(Name::new("s"), Term::abs(Name::new("z"), body))
+        };
+        let mul = Term::abs(
+            Name::new("m"),
+            Term::abs(
+                Name::new("n"),
+                Term::abs(
+                    Name::new("s"),
+                    Term::app(Term::index(2), Term::app(Term::index(1), Term::index(0))),
+                ),
+            ),
+        );
+        let term = Term::app(Term::app(mul, numeral(2)), numeral(3));
+
+        for strategy in [Strategy::CallByName, Strategy::CallByValue] {
+            assert_eq!(term.norm_with(strategy).as_church_numeral(), Some(6));
+        }
+    }
+
+    #[test]
+    fn norm_with_callback_fires_once_per_reduction() {
+        // (x => x) ((y => y) z), where `z` is free
+        let term = Term::app(
+            Term::abs(Name::new("x"), Term::index(0)),
+            Term::app(Term::abs(Name::new("y"), Term::index(0)), Term::index(0)),
+        );
+
+        let mut count = 0;
+        let result = term.norm_with_callback(10, &mut |_| count += 1);
+
+        assert_eq!(count, 2);
+        assert_eq!(format!("{:?}", result), "Term(0)");
+    }
+
+    #[test]
+    fn norm_with_callback_respects_fuel() {
+        // (x => x x) (x => x x)
+        let omega = Term::abs(Name::new("x"), Term::app(Term::index(0), Term::index(0)));
+        let term = Term::app(omega.clone(), omega);
+
+        let mut count = 0;
+        term.norm_with_callback(5, &mut |_| count += 1);
+
+        assert_eq!(count, 5);
+    }
+
+    #[test]
+    fn finds_applications_of_constants() {
+        // x => Zero (Id x), where `Zero` isn't a function
+        let zero = Name::new("Zero");
+        let id_x = Term::app(Term::global(Name::new("Id")), Term::index(0));
+        let term = Term::abs(Name::new("x"), Term::app(Term::global(zero.clone()), id_x));
+
+        let found = term.constant_applications(&|name| *name == zero);
+        assert_eq!(found, vec![zero]);
+        assert!(term.constant_applications(&|_| false).is_empty());
+    }
+
+    #[test]
+    fn recognizes_church_booleans() {
+        let tru = Term::abs(Name::new("t"), Term::abs(Name::new("f"), Term::index(1)));
+        let fls = Term::abs(Name::new("t"), Term::abs(Name::new("f"), Term::index(0)));
+        let id = Term::abs(Name::new("x"), Term::index(0));
+
+        assert_eq!(tru.eval(&Env::new()).as_bool(), Some(true));
+        assert_eq!(fls.eval(&Env::new()).as_bool(), Some(false));
+        assert_eq!(id.eval(&Env::new()).as_bool(), None);
+    }
+
+    #[test]
+    fn recognizes_church_pairs() {
+        let tru = Term::abs(Name::new("t"), Term::abs(Name::new("f"), Term::index(1)));
+        let fls = Term::abs(Name::new("t"), Term::abs(Name::new("f"), Term::index(0)));
+        // (a, b) => f => f a b
+        let pair = Term::abs(
+            Name::new("a"),
+            Term::abs(
+                Name::new("b"),
+                Term::abs(
+                    Name::new("f"),
+                    Term::app(Term::app(Term::index(0), Term::index(2)), Term::index(1)),
+                ),
+            ),
+        );
+        let term = Term::app(Term::app(pair, tru), fls);
+
+        let (first, second) = term.eval(&Env::new()).as_pair().unwrap();
+        assert_eq!(first.eval(&Env::new()).as_bool(), Some(true));
+        assert_eq!(second.eval(&Env::new()).as_bool(), Some(false));
+    }
+
+    #[test]
+    fn canonicalize_is_idempotent() {
+        // x => y => x (y y)
+        let term = Term::abs(
+            Name::new("x"),
+            Term::abs(
+                Name::new("y"),
+                Term::app(Term::index(1), Term::app(Term::index(0), Term::index(0))),
+            ),
+        );
+
+        let once = term.canonicalize();
+        let twice = once.canonicalize();
+        assert_eq!(format!("{:?}", once), format!("{:?}", twice));
+    }
+
+    #[test]
+    fn canonicalize_shares_alpha_equivalent_terms() {
+        let xx = Term::abs(Name::new("x"), Term::app(Term::index(0), Term::index(0)));
+        let yy = Term::abs(Name::new("y"), Term::app(Term::index(0), Term::index(0)));
+
+        let mut canonicalizer = Canonicalizer::default();
+        let xx = canonicalizer.canonicalize(&xx);
+        let yy = canonicalizer.canonicalize(&yy);
+        assert!(Rc::ptr_eq(&xx.0, &yy.0));
+
+        // Identical subterms within a single term are shared as well
+        let omega = canonicalizer.canonicalize(&Term::app(xx.clone(), yy));
+        match &*omega.0 {
+            _Term::App { rator, rand } => assert!(Rc::ptr_eq(&rator.0, &rand.0)),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn quote_cached_reuses_previous_quotes() {
+        // (x => x x) (y => y), whose evaluation forces a thunk
+        let term = Term::app(
+            Term::abs(Name::new("x"), Term::app(Term::index(0), Term::index(0))),
+            Term::abs(Name::new("y"), Term::index(0)),
+        );
+        let value = term.eval(&Env::new());
+
+        let mut cache = QuoteCache::default();
+        let first = value.quote_cached(&mut cache);
+        let second = value.quote_cached(&mut cache);
+
+        assert_eq!(cache.hits(), 1);
+        assert!(Rc::ptr_eq(&first.0, &second.0));
+    }
+
+    #[test]
+    fn quote_cached_quotes_shared_subvalues_once() {
+        // (x => y => y x x) (z => z), whose body refers to `x` twice
+        let term = Term::app(
+            Term::abs(
+                Name::new("x"),
+                Term::abs(
+                    Name::new("y"),
+                    Term::app(Term::app(Term::index(0), Term::index(1)), Term::index(1)),
+                ),
+            ),
+            Term::abs(Name::new("z"), Term::index(0)),
+        );
+        let value = term.eval(&Env::new());
+
+        let mut cache = QuoteCache::default();
+        let quoted = value.quote_cached(&mut cache);
+
+        assert_eq!(cache.hits(), 1);
+        assert!(quoted.alpha_eq(&term.norm()));
+        let (first, second) = match &*quoted.0 {
+            _Term::Abs { body, .. } => match &*body.0 {
+                _Term::App { rator, rand } => match &*rator.0 {
+                    _Term::App { rand: first, .. } => (first.clone(), rand.clone()),
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        assert!(Rc::ptr_eq(&first.0, &second.0));
+    }
+
+    #[test]
+    fn norm_with_fuel_gives_up_on_divergent_terms() {
+        // (x => x x) (x => x x)
+        let omega = Term::abs(Name::new("x"), Term::app(Term::index(0), Term::index(0)));
+        let term = Term::app(omega.clone(), omega);
+
+        assert_eq!(
+            term.norm_with_fuel(100).unwrap_err(),
+            Diverged::OutOfSteps { max_steps: 100 }
+        );
+    }
+
+    #[test]
+    fn norm_with_fuel_gives_up_on_divergent_terms_before_overflowing() {
+        // (x => x x) (x => x x)
+        let omega = Term::abs(Name::new("x"), Term::app(Term::index(0), Term::index(0)));
+        let term = Term::app(omega.clone(), omega);
+
+        assert_eq!(
+            term.norm_with_fuel(DEFAULT_FUEL).unwrap_err(),
+            Diverged::TooDeep {
+                max_depth: MAX_EVAL_DEPTH
+            }
+        );
+    }
+
+    #[test]
+    fn norm_with_fuel_normalizes_terminating_terms() {
+        // (x => y => x) (z => z)
+        let term = Term::app(
+            Term::abs(Name::new("x"), Term::abs(Name::new("y"), Term::index(1))),
+            Term::abs(Name::new("z"), Term::index(0)),
+        );
+
+        assert!(term.norm_with_fuel(1).is_ok());
+        assert!(term.norm_with_fuel(0).is_err());
+    }
+
+    #[test]
+    fn norm_traced_records_bindings() {
+        // y => (x => x) y
+        let term = Term::abs(
+            Name::new("y"),
+            Term::app(Term::abs(Name::new("x"), Term::index(0)), Term::index(0)),
+        );
+
+        let (_, trace) = term.norm_traced();
+        let trace: Vec<(Name, String)> = trace
+            .into_iter()
+            .map(|(name, term)| (name, format!("{:?}", term)))
+            .collect();
+
+        assert_eq!(
+            trace,
+            vec![
+                (Name::new("y"), String::from("Term(0)")),
+                (Name::new("x"), String::from("Term(0)")),
+            ]
+        );
+    }
+
+    #[test]
+    fn norm_instrumented_counts_shared_thunks() {
+        // (x => x x) ((y => y) (z => z)), where the argument is frozen into a
+        // single thunk that's needed twice
+        let id = |name: &str| Term::abs(Name::new(name), Term::index(0));
+        let term = Term::app(
+            Term::abs(Name::new("x"), Term::app(Term::index(0), Term::index(0))),
+            Term::app(id("y"), id("z")),
+        );
+
+        let (norm, stats) = term.norm_instrumented();
+        assert!(norm.alpha_eq(&id("z")));
+        assert_eq!(
+            stats,
+            NormStats {
+                beta_reductions: 3,
+                thunks_forced: 1,
+                thunk_hits: 1,
+                closures_allocated: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn step_reduces_the_leftmost_outermost_redex() {
+        // y => (x => z => x) y, where the inner `x` mustn't be captured
+        let term = Term::abs(
+            Name::new("y"),
+            Term::app(
+                Term::abs(Name::new("x"), Term::abs(Name::new("z"), Term::index(1))),
+                Term::index(0),
+            ),
+        );
+
+        // y => z => y
+        let stepped = term.step().unwrap();
+        assert_eq!(
+            format!("{:?}", stepped.canonicalize()),
+            format!(
+                "{:?}",
+                Term::abs(Name::new("x"), Term::abs(Name::new("x"), Term::index(1)))
+            )
+        );
+        assert!(stepped.step().is_none());
+    }
+
+    #[test]
+    fn subst_shifts_the_replacement_under_binders() {
+        // x => y => 3 x y 2, where `3` and `2` are free: beneath the two
+        // binders, `3` refers to free variable 1 and `2` to free variable 0
+        let term = Term::abs(
+            Name::new("x"),
+            Term::abs(
+                Name::new("y"),
+                Term::app(
+                    Term::app(Term::app(Term::index(3), Term::index(1)), Term::index(0)),
+                    Term::index(2),
+                ),
+            ),
+        );
+        // z => 1 z, where `1` (beneath `z`) refers to free variable 0
+        let replacement = Term::abs(Name::new("z"), Term::app(Term::index(1), Term::index(0)));
+
+        // Free variable 1 (`3`) is replaced, while free variable 0 (`2`) is
+        // untouched. The replacement's free variable is shifted past `x` and
+        // `y`, so it becomes `3` beneath `z`.
+        assert_eq!(
+            term.subst(1, &replacement).to_sexp(),
+            "(abs x (abs y (app (app (app (abs z (app (idx 3) (idx 0))) (idx 1)) (idx 0)) (idx 2))))"
+        );
+    }
+
+    #[test]
+    fn shift_only_moves_indices_at_or_above_the_cutoff() {
+        // x => x 0, where `0` is free
+        let term = Term::abs(Name::new("x"), Term::app(Term::index(0), Term::index(1)));
+
+        assert_eq!(term.shift(2, 0).to_sexp(), "(abs x (app (idx 0) (idx 3)))");
+        assert_eq!(term.shift(2, 0).shift(-2, 0).to_sexp(), term.to_sexp());
+    }
+
+    #[test]
+    fn step_reduces_the_operator_before_the_operand() {
+        // (f => f) (g => g) ((h => h) (k => k))
+        let id = |name: &str| Term::abs(Name::new(name), Term::index(0));
+        let term = Term::app(Term::app(id("f"), id("g")), Term::app(id("h"), id("k")));
+
+        let mut steps = 0;
+        let mut current = term;
+        while let Some(next) = current.step() {
+            steps += 1;
+            current = next;
+        }
+
+        // The operator's redex, then the outer redex, then the operand's
+        assert_eq!(steps, 3);
+    }
+
+    #[test]
+    fn recognizes_church_numerals() {
+        let numeral = |n: usize| {
+            let body = (0..n).fold(Term::index(0), |acc, _| Term::app(Term::index(1), acc));
+            Term::abs(Name::new("s"), Term::abs(Name::new("z"), body))
+        };
+
+        assert_eq!(numeral(0).as_church_numeral(), Some(0));
+        assert_eq!(numeral(2).as_church_numeral(), Some(2));
+        assert_eq!(numeral(5).as_church_numeral(), Some(5));
+    }
+
+    #[test]
+    fn rejects_non_numerals() {
+        // s => z => s
+        let term = Term::abs(Name::new("s"), Term::abs(Name::new("z"), Term::index(1)));
+        assert_eq!(term.as_church_numeral(), None);
+
+        // s => z => s (z s)
+        let term = Term::abs(
+            Name::new("s"),
+            Term::abs(
+                Name::new("z"),
+                Term::app(Term::index(1), Term::app(Term::index(0), Term::index(1))),
+            ),
+        );
+        assert_eq!(term.as_church_numeral(), None);
+
+        // s => s
+        let term = Term::abs(Name::new("s"), Term::index(0));
+        assert_eq!(term.as_church_numeral(), None);
+    }
+
+    #[test]
+    fn alpha_eq_ignores_binder_names() {
+        // x => x, y => y
+        let x = Term::abs(Name::new("x"), Term::index(0));
+        let y = Term::abs(Name::new("y"), Term::index(0));
+
+        assert!(x.alpha_eq(&y));
+    }
+
+    #[test]
+    fn alpha_eq_compares_structure() {
+        // x => x, x => y => x
+        let id = Term::abs(Name::new("x"), Term::index(0));
+        let k = Term::abs(Name::new("x"), Term::abs(Name::new("y"), Term::index(1)));
+
+        assert!(!id.alpha_eq(&k));
+        assert!(!k.alpha_eq(&id));
+    }
+
+    #[test]
+    fn structurally_eq_compares_binder_names() {
+        // x => x, y => y
+        let x = Term::abs(Name::new("x"), Term::index(0));
+        let y = Term::abs(Name::new("y"), Term::index(0));
+
+        assert!(x.structurally_eq(&x.clone()));
+        assert!(!x.structurally_eq(&y));
+        assert!(!Term::app(x.clone(), x.clone()).structurally_eq(&Term::app(x, y)));
+    }
+
+    #[test]
+    fn quote_with_max_nodes_gives_up_on_large_terms() {
+        // (f => x => f (f (f x))) (y => y y), whose normal form doubles the
+        // argument three times over
+        let three = Term::abs(
+            Name::new("f"),
+            Term::abs(
+                Name::new("x"),
+                Term::app(
+                    Term::index(1),
+                    Term::app(Term::index(1), Term::app(Term::index(1), Term::index(0))),
+                ),
+            ),
+        );
+        let double = Term::abs(Name::new("y"), Term::app(Term::index(0), Term::index(0)));
+        let value = Term::app(three, double).eval(&Env::new());
+
+        assert_eq!(
+            value.quote_with_max_nodes(10).unwrap_err(),
+            TooLarge { max_nodes: 10 }
+        );
+        assert!(value.quote_with_max_nodes(100).is_ok());
+    }
+
+    #[test]
+    fn norm_eta_collapses_eta_redexes() {
+        // f => x => f x
+        let term = Term::abs(
+            Name::new("f"),
+            Term::abs(Name::new("x"), Term::app(Term::index(1), Term::index(0))),
+        );
+
+        let expected = Term::abs(Name::new("f"), Term::index(0));
+        assert!(term.norm_eta().alpha_eq(&expected));
+    }
+
+    #[test]
+    fn norm_eta_leaves_self_applications_alone() {
+        // x => x x
+        let term = Term::abs(Name::new("x"), Term::app(Term::index(0), Term::index(0)));
+
+        assert!(term.norm_eta().alpha_eq(&term));
+    }
+
+    #[test]
+    fn to_source_collapses_consecutive_binders() {
+        // s => z => s (s z)
+        let two = Term::abs(
+            Name::new("s"),
+            Term::abs(
+                Name::new("z"),
+                Term::app(Term::index(1), Term::app(Term::index(1), Term::index(0))),
+            ),
+        );
+
+        assert_eq!(two.to_source(), "(s, z) => s (s z)");
+    }
+
+    #[test]
+    fn to_source_parenthesizes_only_where_needed() {
+        // f => x => f x (f x) (y => y)
+        let term = Term::abs(
+            Name::new("f"),
+            Term::abs(
+                Name::new("x"),
+                Term::app(
+                    Term::app(
+                        Term::app(Term::index(1), Term::index(0)),
+                        Term::app(Term::index(1), Term::index(0)),
+                    ),
+                    Term::abs(Name::new("y"), Term::index(0)),
+                ),
+            ),
+        );
+        assert_eq!(term.to_source(), "(f, x) => f x (f x) (y => y)");
+
+        // f => (x => x) f
+        let term = Term::abs(
+            Name::new("f"),
+            Term::app(Term::abs(Name::new("x"), Term::index(0)), Term::index(0)),
+        );
+        assert_eq!(term.to_source(), "f => (x => x) f");
+    }
+
+    #[test]
+    fn to_source_treats_application_as_left_associative() {
+        let global = |name| Term::global(Name::new(name));
+
+        // f (g h)
+        let right = Term::app(global("f"), Term::app(global("g"), global("h")));
+        assert_eq!(right.to_source(), "f (g h)");
+
+        // (f g) h
+        let left = Term::app(Term::app(global("f"), global("g")), global("h"));
+        assert_eq!(left.to_source(), "f g h");
+    }
+
+    #[test]
+    fn to_source_renames_shadowing_binders() {
+        // x => x => x, where the body refers to the outer `x`
+        let term = Term::abs(Name::new("x"), Term::abs(Name::new("x"), Term::index(1)));
+
+        assert_eq!(term.to_source(), "(x, x') => x");
+    }
+
+    #[test]
+    fn displays_terms_in_surface_syntax() {
+        // (x => y => x) (a => a), normalized
+        let k = Term::abs(Name::new("x"), Term::abs(Name::new("y"), Term::index(1)));
+        let id = Term::abs(Name::new("a"), Term::index(0));
+        let term = Term::app(k, id);
+
+        assert_eq!(term.to_string(), "((x, y) => x) (a => a)");
+        assert_eq!(term.norm().to_string(), "(y, a) => a");
+        assert!(format!("{:?}", term.norm()).ends_with("Term(0)))"));
+    }
+
+    #[test]
+    fn classifies_church_encodings() {
+        let tru = Term::abs(Name::new("t"), Term::abs(Name::new("f"), Term::index(1)));
+        let fls = Term::abs(Name::new("t"), Term::abs(Name::new("f"), Term::index(0)));
+        let fst = Term::abs(Name::new("p"), Term::app(Term::index(0), tru.clone()));
+        let snd = Term::abs(Name::new("p"), Term::app(Term::index(0), fls.clone()));
+        let pair = Term::abs(
+            Name::new("f"),
+            Term::app(Term::app(Term::index(0), tru.clone()), fls.clone()),
+        );
+
+        let classified: Vec<String> = [&tru, &fls, &fst, &snd, &pair]
+            .iter()
+            .map(|term| term.classify().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            classified,
+            vec!["true", "false", "fst", "snd", "⟨(t, f) => t, (t, f) => f⟩"]
+        );
+    }
+
+    #[test]
+    fn classify_requires_an_exact_match() {
+        // x => x
+        let id = Term::abs(Name::new("x"), Term::index(0));
+        assert!(id.classify().is_none());
+
+        // f => f f f, whose components refer to its binder
+        let term = Term::abs(
+            Name::new("f"),
+            Term::app(Term::app(Term::index(0), Term::index(0)), Term::index(0)),
+        );
+        assert!(term.classify().is_none());
+
+        // p => p (x => x)
+        let term = Term::abs(Name::new("p"), Term::app(Term::index(0), id));
+        assert!(term.classify().is_none());
+    }
 }
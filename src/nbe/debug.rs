@@ -0,0 +1,97 @@
+//! ## Diagnostic renderings of terms.
+//!
+//! These are meant for debugging `nbe` itself, and have no bearing on
+//! evaluation.
+
+use super::{_Term, Prec, Term};
+
+impl Term {
+    /// Renders this term with De Bruijn _levels_ rather than indices: each
+    /// binder is annotated with its level (0 for the outermost binder), and
+    /// each bound variable is shown as `#level`. Unlike an index, a variable's
+    /// level doesn't depend on how many binders lie between it and its binder,
+    /// so a variable that refers to the wrong binder (e.g. after a capturing
+    /// substitution) stands out. Free variables are shown as `free#index`.
+    ///
+    /// For example, `x => y => x y` is rendered as `x#0 => y#1 => #0 #1`.
+    pub fn to_debug_levels(&self) -> String {
+        let mut out = String::new();
+        self.write_levels(0, &mut out);
+        out
+    }
+
+    /// Writes this term, which occurs under `depth` binders.
+    fn write_levels(&self, depth: usize, out: &mut String) {
+        match &*self.0 {
+            _Term::Index { index } => match depth.checked_sub(index + 1) {
+                Some(level) => out.push_str(&format!("#{}", level)),
+                None => out.push_str(&format!("free#{}", index - depth)),
+            },
+            _Term::Global { name } => out.push_str(&name.0),
+            _Term::Abs { name, body } => {
+                out.push_str(&format!("{}#{} => ", name.0, depth));
+                body.write_levels_at(Prec::Abs, depth + 1, out);
+            }
+            // As in `write_source`, the operator of an application needn't be
+            // parenthesized if it's an application itself, but the operand
+            // must be
+            _Term::App { rator, rand } => {
+                rator.write_levels_at(Prec::App, depth, out);
+                out.push(' ');
+                rand.write_levels_at(Prec::Atom, depth, out);
+            }
+        }
+    }
+
+    /// Writes this term where a term of at least precedence `min` is
+    /// expected, parenthesizing it if its own precedence is lower.
+    fn write_levels_at(&self, min: Prec, depth: usize, out: &mut String) {
+        if self.prec() < min {
+            out.push('(');
+            self.write_levels(depth, out);
+            out.push(')');
+        } else {
+            self.write_levels(depth, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Name;
+    use super::*;
+
+    #[test]
+    fn renders_variables_by_level() {
+        // x => y => x y
+        let term = Term::abs(
+            Name::new("x"),
+            Term::abs(Name::new("y"), Term::app(Term::index(1), Term::index(0))),
+        );
+
+        assert_eq!(term.to_debug_levels(), "x#0 => y#1 => #0 #1");
+    }
+
+    #[test]
+    fn levels_are_stable_under_binders() {
+        // x => x (y => x y) Zero 0, where `0` is free
+        let term = Term::abs(
+            Name::new("x"),
+            Term::app(
+                Term::app(
+                    Term::app(
+                        Term::index(0),
+                        Term::abs(Name::new("y"), Term::app(Term::index(1), Term::index(0))),
+                    ),
+                    Term::global(Name::new("Zero")),
+                ),
+                Term::index(1),
+            ),
+        );
+
+        assert_eq!(
+            term.to_debug_levels(),
+            "x#0 => #0 (y#1 => #0 #1) Zero free#0"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! ## Addressing subterms by the path from the root.
+//!
+//! These are the basis for structural editing: a subterm is selected by its
+//! path, and replaced by rebuilding the spine above it.
+
+use super::{_Term, Term};
+
+/// A selector for one child of a term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    AppRator,
+    AppRand,
+    AbsBody,
+}
+
+/// A sequence of steps leading from the root of a term to one of its
+/// subterms. The empty path selects the root itself.
+pub type Path = Vec<Step>;
+
+impl Term {
+    /// Produces every subterm of this term (including the term itself) in
+    /// preorder, each along with its path.
+    pub fn subterms(&self) -> impl Iterator<Item = (Path, &Term)> {
+        let mut pending = vec![(Path::new(), self)];
+        std::iter::from_fn(move || {
+            let (path, term) = pending.pop()?;
+            // Children are pushed in reverse, so that the leftmost is visited
+            // first
+            match &*term.0 {
+                _Term::Index { .. } | _Term::Global { .. } => {}
+                _Term::Abs { body, .. } => pending.push((extend(&path, Step::AbsBody), body)),
+                _Term::App { rator, rand } => {
+                    pending.push((extend(&path, Step::AppRand), rand));
+                    pending.push((extend(&path, Step::AppRator), rator));
+                }
+            }
+            Some((path, term))
+        })
+    }
+
+    /// Produces a copy of this term in which the subterm at `path` has been
+    /// replaced by `new`, or `None` if there is no such subterm. Note that
+    /// `new` is placed as-is: its indices aren't adjusted.
+    pub fn replace_at(&self, path: &[Step], new: Term) -> Option<Term> {
+        let (step, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return Some(new),
+        };
+
+        match (&*self.0, step) {
+            (_Term::Abs { name, body }, Step::AbsBody) => {
+                Some(Term::abs(name.clone(), body.replace_at(rest, new)?))
+            }
+            (_Term::App { rator, rand }, Step::AppRator) => {
+                Some(Term::app(rator.replace_at(rest, new)?, rand.clone()))
+            }
+            (_Term::App { rator, rand }, Step::AppRand) => {
+                Some(Term::app(rator.clone(), rand.replace_at(rest, new)?))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn extend(path: &[Step], step: Step) -> Path {
+    let mut path = path.to_vec();
+    path.push(step);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbe::Name;
+
+    // f => x => f x
+    fn apply() -> Term {
+        Term::abs(
+            Name::new("f"),
+            Term::abs(Name::new("x"), Term::app(Term::index(1), Term::index(0))),
+        )
+    }
+
+    #[test]
+    fn subterms_are_produced_in_preorder_with_their_paths() {
+        use Step::*;
+
+        let term = apply();
+        let paths: Vec<Path> = term.subterms().map(|(path, _)| path).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                vec![],
+                vec![AbsBody],
+                vec![AbsBody, AbsBody],
+                vec![AbsBody, AbsBody, AppRator],
+                vec![AbsBody, AbsBody, AppRand],
+            ]
+        );
+    }
+
+    #[test]
+    fn replaces_the_subterm_at_a_path() {
+        use Step::*;
+
+        let term = apply();
+        let path = vec![AbsBody, AbsBody, AppRand];
+        let replaced = term.replace_at(&path, Term::index(1)).unwrap();
+
+        // f => x => f f
+        let expected = Term::abs(
+            Name::new("f"),
+            Term::abs(Name::new("x"), Term::app(Term::index(1), Term::index(1))),
+        );
+        assert!(replaced.alpha_eq(&expected));
+    }
+
+    #[test]
+    fn replacing_at_a_missing_path_fails() {
+        let path = vec![Step::AbsBody, Step::AppRator];
+
+        assert!(apply().replace_at(&path, Term::index(0)).is_none());
+    }
+}
@@ -0,0 +1,205 @@
+//! ## A canonical S-expression form of terms.
+//!
+//! Unlike the surface syntax, this form is unambiguous and independent of the
+//! parser, which makes it suitable for snapshots and exchange with other
+//! tools:
+//!
+//! ```text
+//! term := (idx <index>) | (global <name>) | (abs <name> <term>) | (app <term> <term>)
+//! ```
+
+use super::{_Term, Name, Term};
+use std::fmt;
+
+/// The error produced when `Term::from_sexp` is given malformed input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseSexpError {
+    pub message: String,
+    /// The byte offset at which the problem was found.
+    pub offset: usize,
+}
+
+impl fmt::Display for ParseSexpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl Term {
+    /// Renders this term as an S-expression, e.g. `(abs x (app (idx 0) (idx 1)))`.
+    pub fn to_sexp(&self) -> String {
+        let mut out = String::new();
+        self.write_sexp(&mut out);
+        out
+    }
+
+    fn write_sexp(&self, out: &mut String) {
+        match &*self.0 {
+            _Term::Index { index } => out.push_str(&format!("(idx {})", index)),
+            _Term::Global { name } => {
+                out.push_str("(global ");
+                out.push_str(&name.0);
+                out.push(')');
+            }
+            _Term::Abs { name, body } => {
+                out.push_str("(abs ");
+                out.push_str(&name.0);
+                out.push(' ');
+                body.write_sexp(out);
+                out.push(')');
+            }
+            _Term::App { rator, rand } => {
+                out.push_str("(app ");
+                rator.write_sexp(out);
+                out.push(' ');
+                rand.write_sexp(out);
+                out.push(')');
+            }
+        }
+    }
+
+    /// Reads a term from the S-expression form produced by `to_sexp`.
+    pub fn from_sexp(text: &str) -> Result<Term, ParseSexpError> {
+        let mut reader = Reader { text, pos: 0 };
+        let term = reader.term()?;
+        reader.skip_whitespace();
+        if reader.pos < text.len() {
+            return Err(reader.error("expected the end of input"));
+        }
+        Ok(term)
+    }
+}
+
+struct Reader<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn term(&mut self) -> Result<Term, ParseSexpError> {
+        self.expect('(')?;
+        let start = self.skip_whitespace();
+        let term = match self.atom()? {
+            "idx" => {
+                let start = self.skip_whitespace();
+                let index = self.atom()?.parse().map_err(|_| ParseSexpError {
+                    message: String::from("expected an index"),
+                    offset: start,
+                })?;
+                Term::index(index)
+            }
+            "global" => Term::global(Name::new(self.atom()?)),
+            "abs" => {
+                let name = Name::new(self.atom()?);
+                let body = self.term()?;
+                Term::abs(name, body)
+            }
+            "app" => {
+                let rator = self.term()?;
+                let rand = self.term()?;
+                Term::app(rator, rand)
+            }
+            _ => {
+                return Err(ParseSexpError {
+                    message: String::from("expected 'idx', 'global', 'abs', or 'app'"),
+                    offset: start,
+                })
+            }
+        };
+        self.expect(')')?;
+        Ok(term)
+    }
+
+    /// Reads a run of characters other than whitespace and parentheses.
+    fn atom(&mut self) -> Result<&str, ParseSexpError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let len = self.rest().find(['(', ')', ' ', '\t', '\n', '\r']);
+        self.pos += len.unwrap_or(self.rest().len());
+        if self.pos == start {
+            return Err(self.error("expected an atom"));
+        }
+        Ok(&self.text[start..self.pos])
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseSexpError> {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", c)))
+        }
+    }
+
+    /// Skips any whitespace, returning the offset of what follows it.
+    fn skip_whitespace(&mut self) -> usize {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+        self.pos
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseSexpError {
+        ParseSexpError {
+            message: message.into(),
+            offset: self.pos,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_terms_as_sexps() {
+        // x => x 0, where `0` is free
+        let term = Term::abs(Name::new("x"), Term::app(Term::index(0), Term::index(1)));
+
+        assert_eq!(term.to_sexp(), "(abs x (app (idx 0) (idx 1)))");
+    }
+
+    #[test]
+    fn sexps_round_trip() {
+        // (s, z) => s (s' => s') Zero
+        let term = Term::abs(
+            Name::new("s"),
+            Term::abs(
+                Name::new("z"),
+                Term::app(
+                    Term::app(Term::index(1), Term::abs(Name::new("s'"), Term::index(0))),
+                    Term::global(Name::new("Zero")),
+                ),
+            ),
+        );
+
+        let read = Term::from_sexp(&term.to_sexp()).unwrap();
+        assert_eq!(format!("{:?}", read), format!("{:?}", term));
+
+        let spaced = Term::from_sexp(" ( abs  x\n(idx 0) ) ").unwrap();
+        assert_eq!(spaced.to_sexp(), "(abs x (idx 0))");
+    }
+
+    #[test]
+    fn reports_malformed_sexps() {
+        let error = |text| Term::from_sexp(text).unwrap_err();
+
+        assert_eq!(
+            error("(idx x)"),
+            ParseSexpError {
+                message: String::from("expected an index"),
+                offset: 5,
+            }
+        );
+        assert_eq!(error("(lam x (idx 0))").offset, 1);
+        assert_eq!(error("(abs x (idx 0)").message, "expected ')'");
+        assert_eq!(
+            error("(idx 0) (idx 1)").message,
+            "expected the end of input"
+        );
+    }
+}
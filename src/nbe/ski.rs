@@ -0,0 +1,234 @@
+//! ## Translation of closed terms to the `S`, `K`, `I` combinator basis.
+//!
+//! Abstractions are eliminated by classical bracket abstraction. Since terms
+//! use de Bruijn indices, abstracting "the" variable always means abstracting
+//! index 0 and lowering every other free index by one.
+
+use super::{_Term, Name, Term};
+use std::fmt;
+use std::rc::Rc;
+
+/// A tree of applications of the `S`, `K`, and `I` combinators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkiTerm {
+    S,
+    K,
+    I,
+    /// A global, which is left as-is.
+    Global(Name),
+    App(Rc<SkiTerm>, Rc<SkiTerm>),
+}
+
+impl Term {
+    /// Translates this (closed) term to an equivalent combinator expression.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the term has free variables.
+    pub fn to_ski(&self) -> SkiTerm {
+        match Comb::from(self).to_ski() {
+            Some(ski) => ski,
+            None => panic!("only closed terms can be translated to combinators"),
+        }
+    }
+}
+
+impl SkiTerm {
+    pub fn app(rator: SkiTerm, rand: SkiTerm) -> Self {
+        SkiTerm::App(Rc::new(rator), Rc::new(rand))
+    }
+
+    /// Expands each combinator into its definition as an abstraction.
+    pub fn to_term(&self) -> Term {
+        let abs = |name: &str, body| Term::abs(Name::new(name), body);
+        match self {
+            // x => y => z => x z (y z)
+            SkiTerm::S => abs(
+                "x",
+                abs(
+                    "y",
+                    abs(
+                        "z",
+                        Term::app(
+                            Term::app(Term::index(2), Term::index(0)),
+                            Term::app(Term::index(1), Term::index(0)),
+                        ),
+                    ),
+                ),
+            ),
+            // x => y => x
+            SkiTerm::K => abs("x", abs("y", Term::index(1))),
+            // x => x
+            SkiTerm::I => abs("x", Term::index(0)),
+            SkiTerm::Global(name) => Term::global(name.clone()),
+            SkiTerm::App(rator, rand) => Term::app(rator.to_term(), rand.to_term()),
+        }
+    }
+}
+
+impl fmt::Display for SkiTerm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkiTerm::S => write!(f, "S"),
+            SkiTerm::K => write!(f, "K"),
+            SkiTerm::I => write!(f, "I"),
+            SkiTerm::Global(name) => write!(f, "{}", name.0),
+            SkiTerm::App(rator, rand) => match &**rand {
+                SkiTerm::App(..) => write!(f, "{} ({})", rator, rand),
+                _ => write!(f, "{} {}", rator, rand),
+            },
+        }
+    }
+}
+
+/// A combinator expression that may still mention (free) variables, which is
+/// what bracket abstraction works over.
+#[derive(Clone)]
+enum Comb {
+    S,
+    K,
+    I,
+    Global(Name),
+    Var(usize),
+    App(Rc<Comb>, Rc<Comb>),
+}
+
+impl From<&Term> for Comb {
+    fn from(term: &Term) -> Self {
+        match &*term.0 {
+            _Term::Index { index } => Comb::Var(*index),
+            _Term::Global { name } => Comb::Global(name.clone()),
+            _Term::Abs { body, .. } => Comb::from(body).abstract_top(),
+            _Term::App { rator, rand } => Comb::app(Comb::from(rator), Comb::from(rand)),
+        }
+    }
+}
+
+impl Comb {
+    fn app(rator: Comb, rand: Comb) -> Self {
+        Comb::App(Rc::new(rator), Rc::new(rand))
+    }
+
+    /// Eliminates variable 0 from this expression:
+    /// - `[x] x = I`
+    /// - `[x] M = K M`, if `x` doesn't occur in `M`
+    /// - `[x] (M x) = M`, if `x` doesn't occur in `M`
+    /// - `[x] (M N) = S ([x] M) ([x] N)`
+    fn abstract_top(&self) -> Comb {
+        match self {
+            Comb::Var(0) => Comb::I,
+            _ if !self.mentions(0) => Comb::app(Comb::K, self.lower()),
+            Comb::App(rator, rand) => match &**rand {
+                Comb::Var(0) if !rator.mentions(0) => rator.lower(),
+                _ => Comb::app(
+                    Comb::app(Comb::S, rator.abstract_top()),
+                    rand.abstract_top(),
+                ),
+            },
+            _ => unreachable!("only vars and applications can mention a var"),
+        }
+    }
+
+    fn mentions(&self, target: usize) -> bool {
+        match self {
+            Comb::Var(index) => *index == target,
+            Comb::App(rator, rand) => rator.mentions(target) || rand.mentions(target),
+            _ => false,
+        }
+    }
+
+    /// Decrements every variable, which must all be nonzero.
+    fn lower(&self) -> Comb {
+        match self {
+            Comb::Var(index) => Comb::Var(index - 1),
+            Comb::App(rator, rand) => Comb::app(rator.lower(), rand.lower()),
+            _ => self.clone(),
+        }
+    }
+
+    fn to_ski(&self) -> Option<SkiTerm> {
+        match self {
+            Comb::S => Some(SkiTerm::S),
+            Comb::K => Some(SkiTerm::K),
+            Comb::I => Some(SkiTerm::I),
+            Comb::Global(name) => Some(SkiTerm::Global(name.clone())),
+            Comb::Var(..) => None,
+            Comb::App(rator, rand) => {
+                let rator = rator.to_ski()?;
+                let rand = rand.to_ski()?;
+                Some(SkiTerm::app(rator, rand))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abs(name: &str, body: Term) -> Term {
+        Term::abs(Name::new(name), body)
+    }
+
+    fn assert_round_trips(term: &Term) {
+        let expanded = term.to_ski().to_term();
+
+        assert_eq!(
+            format!("{:?}", expanded.norm().canonicalize()),
+            format!("{:?}", term.norm().canonicalize())
+        );
+    }
+
+    #[test]
+    fn identity_abstracts_to_i() {
+        assert_eq!(abs("x", Term::index(0)).to_ski(), SkiTerm::I);
+    }
+
+    #[test]
+    fn constant_function_abstracts_to_k() {
+        // x => y => x
+        let term = abs("x", abs("y", Term::index(1)));
+
+        assert_eq!(term.to_ski(), SkiTerm::K);
+    }
+
+    #[test]
+    fn applications_abstract_with_s() {
+        // f => x => x f
+        let term = abs("f", abs("x", Term::app(Term::index(0), Term::index(1))));
+
+        assert_eq!(term.to_ski().to_string(), "S (K (S I)) K");
+    }
+
+    #[test]
+    fn expansions_are_equivalent_to_the_original() {
+        // s => z => s (s z)
+        let two = abs(
+            "s",
+            abs(
+                "z",
+                Term::app(Term::index(1), Term::app(Term::index(1), Term::index(0))),
+            ),
+        );
+        assert_round_trips(&two);
+
+        // f => x => y => f y x
+        let flip = abs(
+            "f",
+            abs(
+                "x",
+                abs(
+                    "y",
+                    Term::app(Term::app(Term::index(2), Term::index(0)), Term::index(1)),
+                ),
+            ),
+        );
+        assert_round_trips(&flip);
+    }
+
+    #[test]
+    #[should_panic]
+    fn open_terms_cannot_be_translated() {
+        Term::index(0).to_ski();
+    }
+}
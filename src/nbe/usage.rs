@@ -0,0 +1,113 @@
+//! ## How often abstractions use their arguments.
+//!
+//! Counting the indices that refer to a binder says whether an abstraction
+//! discards, uses, or duplicates its argument, which is useful both for
+//! optimization (an unused argument need never be evaluated) and for teaching.
+
+use super::{_Term, Term};
+
+/// How an abstraction uses its argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Usage {
+    /// The argument is never referred to, e.g. `y` in `x => y => x`.
+    Unused,
+    /// The argument is referred to exactly once, and not from within another
+    /// abstraction, e.g. `x` in `(f, x) => f x`.
+    Once,
+    /// The argument is referred to exactly once, but from within another
+    /// abstraction, e.g. `x` in `x => y => x`. It's used only if (and as often
+    /// as) that abstraction is applied.
+    OnceUnderBinder,
+    /// The argument is referred to more than once, e.g. `x` in `x => x x`.
+    Many,
+}
+
+impl Usage {
+    /// Classifies a binder from the number of times it's referred to, and
+    /// whether any of those references occur within another abstraction.
+    pub fn from_count(count: usize, nested: bool) -> Self {
+        match count {
+            0 => Usage::Unused,
+            1 if nested => Usage::OnceUnderBinder,
+            1 => Usage::Once,
+            _ => Usage::Many,
+        }
+    }
+}
+
+impl Term {
+    /// Produces the usage of this abstraction's argument, or `None` if this
+    /// isn't an abstraction.
+    pub fn usage(&self) -> Option<Usage> {
+        match &*self.0 {
+            _Term::Abs { body, .. } => {
+                let mut count = 0;
+                let mut nested = false;
+                body.count_occurrences(0, 0, &mut count, &mut nested);
+                Some(Usage::from_count(count, nested))
+            }
+            _ => None,
+        }
+    }
+
+    /// Counts the occurrences of `index` (which is shifted as we pass under
+    /// binders), noting whether any occur under at least one abstraction.
+    fn count_occurrences(&self, index: usize, depth: usize, count: &mut usize, nested: &mut bool) {
+        match &*self.0 {
+            _Term::Index { index: i } => {
+                if *i == index + depth {
+                    *count += 1;
+                    *nested |= depth > 0;
+                }
+            }
+            _Term::Global { .. } => {}
+            _Term::Abs { body, .. } => body.count_occurrences(index, depth + 1, count, nested),
+            _Term::App { rator, rand } => {
+                rator.count_occurrences(index, depth, count, nested);
+                rand.count_occurrences(index, depth, count, nested);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Name;
+    use super::*;
+
+    fn abs(name: &str, body: Term) -> Term {
+        Term::abs(Name::new(name), body)
+    }
+
+    fn app(rator: Term, rand: Term) -> Term {
+        Term::app(rator, rand)
+    }
+
+    #[test]
+    fn classifies_abstractions_by_usage() {
+        // x => y => x
+        let k = abs("x", abs("y", Term::index(1)));
+        // f => x => f x
+        let apply = abs("f", abs("x", app(Term::index(1), Term::index(0))));
+        // x => x x
+        let dup = abs("x", app(Term::index(0), Term::index(0)));
+        // x => x (y => x)
+        let nested_dup = abs("x", app(Term::index(0), abs("y", Term::index(1))));
+
+        assert_eq!(Term::index(0).usage(), None);
+        assert_eq!(abs("x", Term::index(0)).usage(), Some(Usage::Once));
+        assert_eq!(k.usage(), Some(Usage::OnceUnderBinder));
+        assert_eq!(abs("z", k).usage(), Some(Usage::Unused));
+        assert_eq!(apply.usage(), Some(Usage::OnceUnderBinder));
+        assert_eq!(dup.usage(), Some(Usage::Many));
+        assert_eq!(nested_dup.usage(), Some(Usage::Many));
+    }
+
+    #[test]
+    fn ignores_free_indices_and_shadowed_binders() {
+        // x => 0 (x => x), where `0` is free
+        let term = abs("x", app(Term::index(1), abs("x", Term::index(0))));
+
+        assert_eq!(term.usage(), Some(Usage::Unused));
+    }
+}
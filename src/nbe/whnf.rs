@@ -0,0 +1,157 @@
+//! ## Evaluation to weak head normal form.
+//!
+//! A value is in weak head normal form (WHNF) once it's an abstraction or a
+//! stuck application of a variable; its body and arguments may still contain
+//! redexes. Reading such a value back to a term must therefore substitute into
+//! those parts without evaluating them, since they may not terminate.
+
+use super::{_Stuck, _Term, _Value, Env, List, Name, Stuck, Term, ThunkContent, Value};
+
+impl Term {
+    /// Reduces this (closed) term only until its head is an abstraction or a
+    /// stuck variable, leaving its arguments unevaluated.
+    pub fn whnf(&self) -> Value {
+        self.eval(&Env::new()).whnf()
+    }
+}
+
+impl Value {
+    /// Forces this value until it's no longer a thunk, i.e. until it's in
+    /// weak head normal form.
+    pub fn whnf(&self) -> Value {
+        let mut value = self.clone();
+        while let _Value::Thunk(thunk) = &*value.0 {
+            value = thunk.thaw();
+        }
+        value
+    }
+
+    /// Reads this value back to a term without performing any further
+    /// reductions: abstraction bodies and arguments are quoted as they are,
+    /// with the values bound by their environments substituted in. Unlike
+    /// `quote`, this never forces a thunk, so it terminates even if the value
+    /// contains divergent subterms.
+    pub fn quote_whnf(&self) -> Term {
+        self.read_back(0, &List::new())
+    }
+
+    fn read_back(&self, binder_count: usize, used_names: &List<Name>) -> Term {
+        match &*self.0 {
+            _Value::Closure { name, body, env } => {
+                read_back_abs(name, body, env, binder_count, used_names)
+            }
+            _Value::Stuck(stuck) => stuck.read_back(binder_count, used_names),
+            _Value::Thunk(thunk) => match &*thunk.0.borrow() {
+                ThunkContent::Frozen { term, env } => {
+                    read_back_in(term, env, binder_count, used_names)
+                }
+                ThunkContent::Thawed(value) => value.read_back(binder_count, used_names),
+            },
+        }
+    }
+}
+
+impl Stuck {
+    fn read_back(&self, binder_count: usize, used_names: &List<Name>) -> Term {
+        match &*self.0 {
+            _Stuck::Index {
+                binder_count: creation_binder_count,
+            } => Term::index(binder_count - creation_binder_count),
+            _Stuck::Global { name } => Term::global(name.clone()),
+            _Stuck::App { op, arg } => Term::app(
+                op.read_back(binder_count, used_names),
+                arg.read_back(binder_count, used_names),
+            ),
+        }
+    }
+}
+
+/// Reads back `term`, in which the free indices refer to the values in `env`.
+fn read_back_in(term: &Term, env: &Env, binder_count: usize, used_names: &List<Name>) -> Term {
+    match &*term.0 {
+        _Term::Index { index } => env.get(*index).unwrap().read_back(binder_count, used_names),
+        // Unfolding a global would be a reduction
+        _Term::Global { .. } => term.clone(),
+        _Term::Abs { name, body } => read_back_abs(name, body, env, binder_count, used_names),
+        _Term::App { rator, rand } => Term::app(
+            read_back_in(rator, env, binder_count, used_names),
+            read_back_in(rand, env, binder_count, used_names),
+        ),
+    }
+}
+
+fn read_back_abs(
+    name: &Name,
+    body: &Term,
+    env: &Env,
+    binder_count: usize,
+    used_names: &List<Name>,
+) -> Term {
+    let new_binder_count = binder_count + 1;
+    let env = env.push(Value::stuck(Stuck::index(new_binder_count)));
+    let name = name.freshen_in(used_names);
+    let used_names = used_names.push(name.clone());
+    let body = read_back_in(body, &env, new_binder_count, &used_names);
+    Term::abs(name, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abs(name: &str, body: Term) -> Term {
+        Term::abs(Name::new(name), body)
+    }
+
+    fn app(rator: Term, rand: Term) -> Term {
+        Term::app(rator, rand)
+    }
+
+    /// (x => x x) (x => x x)
+    fn omega() -> Term {
+        let half = abs("x", app(Term::index(0), Term::index(0)));
+        app(half.clone(), half)
+    }
+
+    /// x => y => x
+    fn konst() -> Term {
+        abs("x", abs("y", Term::index(1)))
+    }
+
+    #[test]
+    fn whnf_leaves_divergent_arguments_unevaluated() {
+        // (x => y => x) (a => a) ((x => x x) (x => x x))
+        let term = app(app(konst(), abs("a", Term::index(0))), omega());
+
+        let whnf = term.whnf().quote_whnf();
+
+        assert!(whnf.alpha_eq(&abs("a", Term::index(0))));
+    }
+
+    #[test]
+    fn whnf_doesnt_reduce_under_binders() {
+        // (x => y => x) (f => f ((x => x) f)) ((x => x x) (x => x x))
+        let body = app(
+            Term::index(0),
+            app(abs("x", Term::index(0)), Term::index(0)),
+        );
+        let term = app(app(konst(), abs("f", body.clone())), omega());
+
+        let whnf = term.whnf().quote_whnf();
+
+        assert!(whnf.alpha_eq(&abs("f", body)));
+    }
+
+    #[test]
+    fn whnf_of_a_stuck_head_keeps_its_arguments() {
+        // (x => y => x) (a ((x => x x) (x => x x))) ((x => x x) (x => x x)),
+        // where `a` is a free var
+        let term = app(app(konst(), app(Term::index(0), omega())), omega());
+        let env = Env::new().push(Value::stuck(Stuck::index(1)));
+
+        let whnf = term.eval(&env).whnf();
+
+        let quoted = whnf.read_back(1, &List::new());
+        assert!(quoted.alpha_eq(&app(Term::index(0), omega())));
+    }
+}
@@ -1,6 +1,11 @@
+use std::cell::OnceCell;
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
 
 #[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     pub start: usize,
     pub end: usize,
@@ -11,6 +16,15 @@ impl Span {
         Span { start, end }
     }
 
+    /// Creates an empty span at `at`, e.g. for something that's missing.
+    pub fn point(at: usize) -> Self {
+        Span::new(at, at)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
     pub fn combine_with(self, other: Self) -> Self {
         let start = usize::min(self.start, other.start);
         let end = usize::max(self.end, other.end);
@@ -29,10 +43,209 @@ impl fmt::Debug for Span {
 pub struct Source {
     pub filename: String,
     pub text: String,
+    /// The byte offsets at which each line begins, computed on first use.
+    line_starts: OnceCell<Vec<usize>>,
 }
 
 impl Source {
     pub fn new(filename: String, text: String) -> Self {
-        Source { filename, text }
+        Source {
+            filename,
+            text,
+            line_starts: OnceCell::new(),
+        }
+    }
+
+    /// Reads the file at `path`, using the path itself as the filename.
+    pub fn from_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        Ok(Source::new(path.display().to_string(), text))
+    }
+
+    /// Creates a source that didn't come from a file, e.g. a line of REPL
+    /// input.
+    pub fn anonymous(text: impl Into<String>) -> Self {
+        Source::new(String::from("<anonymous>"), text.into())
+    }
+
+    /// Maps a byte offset into the source text to a 1-based line and column.
+    /// Columns count chars (not bytes), and offsets past the end of the text
+    /// are clamped to it.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = usize::min(offset, self.text.len());
+        let line_starts = self.line_starts();
+        let line = line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = line_starts[line];
+
+        let col = self.text[line_start..]
+            .char_indices()
+            .take_while(|(i, _)| line_start + i < offset)
+            .count();
+
+        (line + 1, col + 1)
+    }
+
+    /// Produces the text under `span`.
+    pub fn snippet(&self, span: &Span) -> &str {
+        debug_assert!(
+            span.start <= span.end && span.end <= self.text.len(),
+            "span {:?} is out of bounds",
+            span
+        );
+        debug_assert!(
+            self.text.is_char_boundary(span.start) && self.text.is_char_boundary(span.end),
+            "span {:?} splits a char",
+            span
+        );
+        &self.text[span.start..span.end]
+    }
+
+    /// Produces the full line containing `offset`, without its line break.
+    /// Offsets past the end of the text are clamped to it.
+    pub fn line_of(&self, offset: usize) -> &str {
+        let offset = usize::min(offset, self.text.len());
+        let line_starts = self.line_starts();
+        let line = line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = line_starts[line];
+        let line_end = line_starts
+            .get(line + 1)
+            .map_or(self.text.len(), |&next| next - 1);
+
+        self.text[line_start..line_end]
+            .strip_suffix('\r')
+            .unwrap_or(&self.text[line_start..line_end])
+    }
+
+    fn line_starts(&self) -> &[usize] {
+        self.line_starts.get_or_init(|| {
+            let mut starts = vec![0];
+            // A "\r\n" pair counts as a single line break, since the new line
+            // only begins after the '\n'.
+            starts.extend(
+                self.text
+                    .bytes()
+                    .enumerate()
+                    .filter(|(_, b)| *b == b'\n')
+                    .map(|(i, _)| i + 1),
+            );
+            starts
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(text: &str) -> Source {
+        Source::new(String::from("test.lmy"), String::from(text))
+    }
+
+    #[test]
+    fn from_path_reads_the_file_and_keeps_its_path() {
+        let path = std::env::temp_dir().join(format!("lammy-source-{}.lmy", std::process::id()));
+        fs::write(&path, "Id = x => x;\n").unwrap();
+
+        let src = Source::from_path(&path);
+        fs::remove_file(&path).unwrap();
+
+        let src = src.unwrap();
+        assert_eq!(src.filename, path.display().to_string());
+        assert_eq!(src.text, "Id = x => x;\n");
+    }
+
+    #[test]
+    fn from_path_reports_missing_files() {
+        let path = std::env::temp_dir().join("lammy-source-does-not-exist.lmy");
+
+        let error = Source::from_path(path).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn points_are_empty() {
+        assert!(Span::point(3).is_empty());
+        assert!(!Span::new(3, 4).is_empty());
+        assert_eq!(Span::point(3), Span::new(3, 3));
+    }
+
+    #[test]
+    fn snippet_produces_the_spanned_text() {
+        let src = source("Id = x => x;");
+
+        assert_eq!(src.snippet(&Span::new(5, 11)), "x => x");
+        assert_eq!(src.snippet(&Span::point(3)), "");
+    }
+
+    #[test]
+    fn snippet_respects_char_boundaries() {
+        let src = source("τϵστ x");
+
+        assert_eq!(src.snippet(&Span::new(0, 8)), "τϵστ");
+        assert_eq!(src.snippet(&Span::new(2, 6)), "ϵσ");
+    }
+
+    #[test]
+    #[should_panic(expected = "splits a char")]
+    #[cfg(debug_assertions)]
+    fn snippet_rejects_spans_that_split_a_char() {
+        source("τϵστ").snippet(&Span::new(0, 1));
+    }
+
+    #[test]
+    fn line_of_produces_the_whole_line() {
+        let src = source("Id = x => x;\r\nτϵστ x;\nK");
+
+        assert_eq!(src.line_of(0), "Id = x => x;");
+        assert_eq!(src.line_of(12), "Id = x => x;");
+        assert_eq!(src.line_of(14), "τϵστ x;");
+        assert_eq!(src.line_of(20), "τϵστ x;");
+        assert_eq!(src.line_of(src.text.len() + 10), "K");
+    }
+
+    #[test]
+    fn line_col_counts_lines_and_columns_from_one() {
+        let src = source("Id = x => x;\nK = x => y => x;\n");
+
+        assert_eq!(src.line_col(0), (1, 1));
+        assert_eq!(src.line_col(5), (1, 6));
+        assert_eq!(src.line_col(13), (2, 1));
+        assert_eq!(src.line_col(17), (2, 5));
+    }
+
+    #[test]
+    fn line_col_treats_crlf_as_one_line_break() {
+        let src = source("a\r\nb\r\nc");
+
+        assert_eq!(src.line_col(1), (1, 2));
+        assert_eq!(src.line_col(3), (2, 1));
+        assert_eq!(src.line_col(6), (3, 1));
+    }
+
+    #[test]
+    fn line_col_puts_the_line_after_a_crlf_on_the_next_line() {
+        let src = source("a\r\nb");
+
+        assert_eq!(src.line_col(3), (2, 1));
+        assert_eq!(src.line_col(2), (1, 3));
+    }
+
+    #[test]
+    fn line_col_accepts_the_end_of_the_text() {
+        let src = source("a\nbc");
+
+        assert_eq!(src.line_col(src.text.len()), (2, 3));
+        assert_eq!(src.line_col(src.text.len() + 10), (2, 3));
+    }
+
+    #[test]
+    fn line_col_counts_chars_not_bytes() {
+        let src = source("τϵστ x\nτ y");
+
+        assert_eq!(src.line_col(8), (1, 5));
+        assert_eq!(src.line_col(9), (1, 6));
+        assert_eq!(src.line_col(13), (2, 2));
     }
 }
@@ -2,5 +2,10 @@ mod lexer;
 mod parser;
 mod tokens;
 
-pub use self::parser::ast::{Def, Filepath, Import, Module, Name, ReplInput, Term};
-pub use self::parser::{parse_module, parse_repl_input, ParseResult};
+pub use self::lexer::{check_indentation, Interner, Lexer};
+pub use self::parser::ast::{
+    Cycle, Def, Filepath, Import, Module, Name, NameKind, ReplCommand, ReplInput, Term,
+};
+pub use self::parser::tree_builder::TreeBuilder;
+pub use self::parser::{parse_module, parse_repl_input, parse_repl_inputs, ParseResult};
+pub use self::tokens::{Keyword, Keywords, Token, TokenKind};
@@ -1,7 +1,8 @@
 mod interner;
 
-use self::interner::Interner;
-use super::tokens::{Token, TokenKind as Tk};
+pub use self::interner::Interner;
+use super::tokens::{Keywords, Token, TokenKind as Tk};
+use crate::errors::{Code, SimpleError};
 use crate::source::Span;
 use std::collections::VecDeque;
 use std::rc::Rc;
@@ -14,29 +15,99 @@ pub struct Lexer<'a> {
     source: &'a str,
     chars: Chars<'a>,
     interner: Interner<'a>,
+    /// The interned text of each keyword, which keyword tokens share.
+    keywords: Keywords,
     /// A collection of already peeked tokens.
     peeked: VecDeque<Token>,
+    /// The most recently popped token.
+    last: Option<Token>,
+    /// The furthest lookahead (as passed to `peek_ahead`) requested so far.
+    max_lookahead: usize,
+    /// Errors encountered while reading tokens (e.g. unknown escape
+    /// sequences in strings).
+    errors: Vec<SimpleError>,
+    config: LexerConfig,
+    /// Whether the last token read was a name (or alias) cut short by
+    /// `max_ident_len`, whose remainder has yet to be read.
+    truncated_ident: bool,
 }
 
+/// Options that change how a `Lexer` reads its input. The default reads any
+/// input the way `Lexer::from` always has.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LexerConfig {
+    /// The longest (in characters) a name or alias may be. Anything beyond
+    /// this is read as a separate `Unknown` token. This guards against
+    /// pathological (e.g. untrusted) input.
+    pub max_ident_len: Option<usize>,
+}
+
+/// The capacity the `peeked` buffer may retain once drained. Buffers grown
+/// beyond this by long lookaheads are shrunk back down.
+const RETAINED_PEEK_CAPACITY: usize = 16;
+
+/// A saved lexer position (see `Lexer::checkpoint`).
+pub struct Checkpoint<'a> {
+    chars: Chars<'a>,
+    peeked: VecDeque<Token>,
+    last: Option<Token>,
+    error_count: usize,
+    truncated_ident: bool,
+}
+
+const BYTE_ORDER_MARK: char = '\u{FEFF}';
+
 impl<'a> From<&'a str> for Lexer<'a> {
     fn from(source: &'a str) -> Self {
+        Self::with_config(source, LexerConfig::default())
+    }
+}
+
+impl<'a> Lexer<'a> {
+    /// Produces a lexer that reads `source` according to `config`.
+    pub fn with_config(source: &'a str, config: LexerConfig) -> Self {
+        let mut interner = Interner::default();
+        let keywords = interner.intern_keywords();
         Self {
             source,
             chars: source.chars(),
-            interner: Interner::default(),
+            interner,
+            keywords,
             peeked: VecDeque::new(),
+            last: None,
+            max_lookahead: 0,
+            errors: Vec::new(),
+            config,
+            truncated_ident: false,
         }
     }
-}
 
-impl<'a> Lexer<'a> {
     /// Returns the next token from the source text. Note that this token may
     /// have already been peeked.
     pub fn pop(&mut self) -> Token {
-        match self.peeked.pop_front() {
-            Some(next) => next,
+        let next = match self.peeked.pop_front() {
+            Some(next) => {
+                if self.peeked.is_empty() && self.peeked.capacity() > RETAINED_PEEK_CAPACITY {
+                    self.peeked.shrink_to(RETAINED_PEEK_CAPACITY);
+                }
+                next
+            }
             None => self.read_next(),
-        }
+        };
+        self.last = Some(next.clone());
+        next
+    }
+
+    /// The interned text of each keyword. Keyword tokens read by this lexer
+    /// share it, so `Token::is_keyword` can compare them by pointer.
+    pub fn keywords(&self) -> &Keywords {
+        &self.keywords
+    }
+
+    /// Returns a reference to the most recently popped token, if any. Peeking
+    /// doesn't affect this.
+    pub fn last(&self) -> Option<&Token> {
+        self.last.as_ref()
     }
 
     /// Returns a reference to the next token to be popped. `peek` is
@@ -48,12 +119,13 @@ impl<'a> Lexer<'a> {
             self.peeked.push_back(next);
         }
 
-        self.peeked.get(0).unwrap()
+        self.peeked.front().unwrap()
     }
 
     /// Returns a reference to the `n`th token to be popped. Like `peek`,
     /// `peek_ahead` is idempotent.
     pub fn peek_ahead(&mut self, n: usize) -> &Token {
+        self.max_lookahead = usize::max(self.max_lookahead, n);
         if let Some(need_to_peek) = n.checked_sub(self.peeked.len()) {
             for _ in 0..=need_to_peek {
                 let next = self.read_next();
@@ -64,25 +136,89 @@ impl<'a> Lexer<'a> {
         self.peeked.get(n).unwrap()
     }
 
+    /// Produces every remaining token (trivia included), ending with the
+    /// `Eof` token.
+    pub fn tokens(mut self) -> impl Iterator<Item = Token> + 'a {
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let next = self.pop();
+            done = next.kind == Tk::Eof;
+            Some(next)
+        })
+    }
+
+    /// Returns the furthest lookahead requested via `peek_ahead` so far. This
+    /// bounds the number of tokens the lexer has had to buffer at once.
+    pub fn max_lookahead(&self) -> usize {
+        self.max_lookahead
+    }
+
+    /// Captures the lexer's current position, so that tokens may be
+    /// speculatively consumed and later "unconsumed" via `restore`.
+    pub fn checkpoint(&self) -> Checkpoint<'a> {
+        Checkpoint {
+            chars: self.chars.clone(),
+            peeked: self.peeked.clone(),
+            last: self.last.clone(),
+            error_count: self.errors.len(),
+            truncated_ident: self.truncated_ident,
+        }
+    }
+
+    /// Rewinds the lexer to a previously captured position. Note that the
+    /// interner isn't rewound: it only ever grows, so strings interned since
+    /// the checkpoint are simply shared by the re-read tokens.
+    pub fn restore(&mut self, checkpoint: Checkpoint<'a>) {
+        self.chars = checkpoint.chars;
+        self.peeked = checkpoint.peeked;
+        self.last = checkpoint.last;
+        // Any errors recorded since the checkpoint will be recorded again as
+        // the same tokens are re-read.
+        self.errors.truncate(checkpoint.error_count);
+        self.truncated_ident = checkpoint.truncated_ident;
+    }
+
+    /// Removes and returns the errors encountered so far.
+    pub fn take_errors(&mut self) -> Vec<SimpleError> {
+        std::mem::take(&mut self.errors)
+    }
+
     fn read_next(&mut self) -> Token {
         let start = self.current_pos();
         let next = self.chars.next();
         if next.is_none() {
-            return Token::new(Tk::Eof, self.interner.intern(""), Span::new(start, start));
+            return Token::new(Tk::Eof, self.interner.intern(""), Span::point(start));
         }
 
         let kind = match next.unwrap() {
+            // The rest of a name or alias that was too long
+            c if self.truncated_ident && Self::is_name_continue(c) => {
+                self.truncated_ident = false;
+                self.eat_while(Self::is_name_continue);
+                Tk::Unknown
+            }
             '(' => Tk::LParen,
             ')' => Tk::RParen,
             '{' => Tk::LBrace,
             '}' => Tk::RBrace,
             ',' => Tk::Comma,
             ';' => Tk::Semi,
+            ':' => Tk::Colon,
+            '.' => Tk::Dot,
+            '\\' => self.read_lambda_or_line_continuation(),
+            // A leading byte order mark is trivia; it keeps its (original)
+            // position, so that later spans are unaffected.
+            BYTE_ORDER_MARK if start == 0 => Tk::Whitespace,
             '=' => self.read_equals_or_arrow(),
             '#' => self.read_comment(),
             '"' => self.read_string(),
+            '_' => self.read_wildcard(),
             c if Self::is_name_start(c) => self.read_name(),
             c if Self::is_alias_start(c) => self.read_alias(),
+            c if Self::is_digit(c) => self.read_number(),
             c if Self::is_whitespace(c) => self.read_whitespace(),
             _ => self.read_unknown(),
         };
@@ -101,7 +237,21 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// A '\\' immediately followed by a line break marks a continued line;
+    /// the line break itself is left to be read as whitespace.
+    fn read_lambda_or_line_continuation(&mut self) -> Tk {
+        match self.peek_char() {
+            Some('\n' | '\r') => Tk::LineContinuation,
+            _ => Tk::Lambda,
+        }
+    }
+
     fn read_comment(&mut self) -> Tk {
+        if let Some('{') = self.peek_char() {
+            self.chars.next();
+            return self.read_block_comment();
+        }
+
         self.eat_while(|c| match c {
             '\n' | '\r' => false,
             _ => true,
@@ -109,6 +259,29 @@ impl<'a> Lexer<'a> {
         Tk::Comment
     }
 
+    /// Reads the remainder of a (possibly nested) block comment, whose opening
+    /// `#{` has already been consumed.
+    fn read_block_comment(&mut self) -> Tk {
+        let mut depth = 1;
+        while let Some(c) = self.chars.next() {
+            match (c, self.peek_char()) {
+                ('#', Some('{')) => {
+                    self.chars.next();
+                    depth += 1;
+                }
+                ('}', Some('#')) => {
+                    self.chars.next();
+                    depth -= 1;
+                    if depth == 0 {
+                        return Tk::Comment;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Tk::UnterminatedComment
+    }
+
     fn read_string(&mut self) -> Tk {
         let mut escape_next = false;
         while let Some(c) = self.peek_char() {
@@ -133,15 +306,50 @@ impl<'a> Lexer<'a> {
     }
 
     fn read_name(&mut self) -> Tk {
-        self.eat_while(Self::is_name_continue);
+        self.eat_ident(Self::is_name_continue);
         Tk::Var
     }
 
+    /// A `_` on its own is a wildcard binder (see `Name::is_wildcard`). Names
+    /// can't otherwise start with `_`, so e.g. `_x` is unknown.
+    fn read_wildcard(&mut self) -> Tk {
+        match self.peek_char() {
+            Some(c) if Self::is_name_continue(c) => {
+                self.eat_while(Self::is_name_continue);
+                Tk::Unknown
+            }
+            _ => Tk::Var,
+        }
+    }
+
     fn read_alias(&mut self) -> Tk {
-        self.eat_while(Self::is_alias_continue);
+        self.eat_ident(Self::is_alias_continue);
         Tk::Alias
     }
 
+    /// Consumes the rest of a name or alias (whose first character has
+    /// already been consumed), stopping at `max_ident_len` characters.
+    fn eat_ident(&mut self, pred: impl Fn(char) -> bool) {
+        let max_len = self.config.max_ident_len.unwrap_or(usize::MAX);
+        let mut len = 1;
+        while let Some(c) = self.peek_char() {
+            if !pred(c) {
+                return;
+            }
+            if len >= max_len {
+                self.truncated_ident = true;
+                return;
+            }
+            self.chars.next();
+            len += 1;
+        }
+    }
+
+    fn read_number(&mut self) -> Tk {
+        self.eat_while(Self::is_digit);
+        Tk::Number
+    }
+
     fn read_whitespace(&mut self) -> Tk {
         self.eat_while(Self::is_whitespace);
         Tk::Whitespace
@@ -194,6 +402,13 @@ impl<'a> Lexer<'a> {
         Self::is_name_continue(c)
     }
 
+    fn is_digit(c: char) -> bool {
+        match c {
+            '0'..='9' => true,
+            _ => false,
+        }
+    }
+
     fn is_whitespace(c: char) -> bool {
         match c {
             ' ' | '\t' | '\n' | '\r' => true,
@@ -203,10 +418,11 @@ impl<'a> Lexer<'a> {
 
     fn is_unknown(c: char) -> bool {
         match c {
-            '(' | ')' | '{' | '}' | ',' | ';' | '=' | '\\' | '#' => false,
+            '(' | ')' | '{' | '}' | ',' | ';' | ':' | '.' | '=' | '\\' | '#' | '_' => false,
             '\n' | '\r' => false,
             c if Self::is_name_start(c) => false,
             c if Self::is_alias_start(c) => false,
+            c if Self::is_digit(c) => false,
             c if Self::is_whitespace(c) => false,
             _ => true,
         }
@@ -221,13 +437,103 @@ impl<'a> Lexer<'a> {
             Tk::String => end - 1,
             _ => end,
         };
-        self.interner.intern(&self.source[start..end])
+        let text = &self.source[start..end];
+        if let Tk::String | Tk::UnterminatedString = kind {
+            self.check_escapes(text, start);
+        }
+        self.interner.intern(text)
+    }
+
+    /// Records an error for each unknown escape sequence in `text` (which
+    /// begins at `offset` in the source). A string's text is kept as written;
+    /// see `decode_escapes`.
+    fn check_escapes(&mut self, text: &str, offset: usize) {
+        let mut chars = text.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c != '\\' {
+                continue;
+            }
+
+            match chars.next() {
+                Some((_, 'n' | 't' | '\\' | '"')) | None => {}
+                Some((j, other)) => {
+                    let span = Span::new(offset + i, offset + j + other.len_utf8());
+                    let error = SimpleError::new("unknown escape sequence", span);
+                    self.errors.push(error.with_code(Code::UnknownEscape));
+                }
+            }
+        }
     }
 }
 
+/// Replaces the escape sequences `\n`, `\t`, `\\`, and `\"` in the text of a
+/// string token with the characters they denote. Unknown escape sequences
+/// (which the lexer has already reported) are left as-is.
+pub fn decode_escapes(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => decoded.push('\n'),
+            Some('t') => decoded.push('\t'),
+            Some('\\') => decoded.push('\\'),
+            Some('"') => decoded.push('"'),
+            Some(other) => {
+                decoded.push('\\');
+                decoded.push(other);
+            }
+            None => decoded.push('\\'),
+        }
+    }
+    decoded
+}
+
+/// Flags lines whose leading whitespace mixes tabs and spaces. This is an
+/// opt-in style lint: the parser doesn't care about indentation, but mixed
+/// indentation misaligns across editors with different tab widths.
+pub fn check_indentation(source: &str) -> Vec<SimpleError> {
+    let mut lexer = Lexer::from(source);
+    let mut warnings = Vec::new();
+    let mut at_line_start = true;
+
+    loop {
+        let token = lexer.pop();
+        match token.kind {
+            Tk::Eof => break,
+            Tk::Whitespace => {
+                // Only the text following the last line break is indentation;
+                // anything before it belongs to blank lines.
+                let indent_start = match token.text.rfind('\n') {
+                    Some(i) => i + 1,
+                    None if at_line_start => 0,
+                    None => token.text.len(),
+                };
+                let indent = &token.text[indent_start..];
+
+                if indent.contains(' ') && indent.contains('\t') && lexer.peek().kind != Tk::Eof {
+                    let span = Span::new(token.span.start + indent_start, token.span.end);
+                    let warning = SimpleError::warning("indentation mixes tabs and spaces", span);
+                    warnings.push(warning.with_code(Code::MixedIndentation));
+                }
+            }
+            _ => {}
+        }
+        at_line_start = false;
+    }
+
+    warnings
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::errors::Severity;
+    use crate::syntax::tokens::Keyword;
     use Tk::*;
 
     impl<'a> Iterator for Lexer<'a> {
@@ -309,6 +615,16 @@ mod tests {
         assert_eq!(l.collect_kinds(), vec![Equals, Var, Arrow, Alias]);
     }
 
+    #[test]
+    fn reads_only_a_lone_underscore_as_a_wildcard() {
+        let l = Lexer::from("_ _x _1 (_)");
+
+        assert_eq!(
+            l.collect_kinds(),
+            vec![Var, Whitespace, Unknown, Whitespace, Unknown, Whitespace, LParen, Var, RParen]
+        );
+    }
+
     #[test]
     fn reads_unterminated_strings() {
         let l = Lexer::from(
@@ -324,9 +640,12 @@ var Alias"#,
 
     #[test]
     fn reads_unknown_tokens() {
-        let l = Lexer::from("**-^^%<>:: unknown");
+        let l = Lexer::from("**-^^%<>::. unknown");
 
-        assert_eq!(l.collect_kinds(), vec![Unknown, Whitespace, Var]);
+        assert_eq!(
+            l.collect_kinds(),
+            vec![Unknown, Colon, Colon, Dot, Whitespace, Var]
+        );
     }
 
     #[test]
@@ -370,4 +689,303 @@ Quux = foo bar;
             ]
         );
     }
+
+    #[test]
+    fn reads_numbers() {
+        let l = Lexer::from("Three = 3;");
+
+        assert_eq!(
+            l.collect_kinds(),
+            vec![Alias, Whitespace, Equals, Whitespace, Number, Semi]
+        );
+    }
+
+    #[test]
+    fn reads_digits_inside_names_as_part_of_the_name() {
+        let l = Lexer::from("x2 42 X3");
+
+        assert_eq!(
+            l.collect_kinds(),
+            vec![Var, Whitespace, Number, Whitespace, Alias]
+        );
+    }
+
+    #[test]
+    fn reads_numbers_in_applications() {
+        let mut l = Lexer::from("Suc 10");
+
+        assert_eq!(l.pop().kind, Alias);
+        assert_eq!(l.pop().kind, Whitespace);
+
+        let next = l.pop();
+        assert_eq!(next.kind, Number);
+        assert_eq!(*next.text, "10");
+        assert_eq!(next.span, Span::new(4, 6));
+    }
+
+    #[test]
+    fn flags_indentation_mixing_tabs_and_spaces() {
+        let warnings = check_indentation("Y = f =>\n\t  (x => f (x x));");
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity(), Severity::Warning);
+        assert_eq!(warnings[0].code(), Code::MixedIndentation);
+        assert_eq!(*warnings[0].span(), Span::new(9, 12));
+    }
+
+    #[test]
+    fn accepts_consistent_indentation() {
+        assert!(check_indentation("Y = f =>\n\t\t(x => f (x x));").is_empty());
+        assert!(check_indentation("Y = f =>\n    (x => f (x x));").is_empty());
+        assert!(check_indentation("Y = f => \t(x => f (x x));").is_empty());
+    }
+
+    #[test]
+    fn reads_block_comments() {
+        let l = Lexer::from("#{ Id = x => x;\nK = x => y => x; }#\nI");
+
+        assert_eq!(l.collect_kinds(), vec![Comment, Whitespace, Alias]);
+    }
+
+    #[test]
+    fn reads_nested_block_comments() {
+        let mut l = Lexer::from("#{ outer #{ inner }# still outer }# x");
+
+        let next = l.pop();
+        assert_eq!(next.kind, Comment);
+        assert_eq!(next.span, Span::new(0, 35));
+        assert_eq!(l.collect_kinds(), vec![Whitespace, Var]);
+    }
+
+    #[test]
+    fn reads_unterminated_block_comments() {
+        let l = Lexer::from("x #{ never #{ closed }#");
+
+        assert_eq!(
+            l.collect_kinds(),
+            vec![Var, Whitespace, UnterminatedComment]
+        );
+    }
+
+    #[test]
+    fn keeps_escape_sequences_in_strings_as_written() {
+        let mut l = Lexer::from(r#""./a\"b\\c\n\t" x"#);
+
+        let next = l.pop();
+        assert_eq!(next.kind, String);
+        assert_eq!(*next.text, r#"./a\"b\\c\n\t"#);
+        assert_eq!(next.span, Span::new(0, 15));
+        assert!(l.take_errors().is_empty());
+
+        assert_eq!(decode_escapes(&next.text), "./a\"b\\c\n\t");
+    }
+
+    #[test]
+    fn records_unknown_escape_sequences() {
+        let mut l = Lexer::from(r#""./a\qb""#);
+
+        let next = l.pop();
+        assert_eq!(*next.text, r"./a\qb");
+        let errors = l.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(*errors[0].span(), Span::new(4, 6));
+        assert_eq!(decode_escapes(&next.text), r"./a\qb");
+    }
+
+    #[test]
+    fn restore_rewinds_to_checkpoint() {
+        let mut l = Lexer::from("first second third");
+
+        let checkpoint = l.checkpoint();
+        let first = l.pop();
+        let space = l.pop();
+        l.restore(checkpoint);
+
+        assert_eq!(l.pop(), first);
+        assert_eq!(l.pop(), space);
+        assert_eq!(*l.pop().text, "second");
+    }
+
+    #[test]
+    fn restore_rewinds_peeked_tokens() {
+        let mut l = Lexer::from("first second third");
+        l.peek_ahead(3);
+
+        let checkpoint = l.checkpoint();
+        l.pop();
+        l.pop();
+        l.restore(checkpoint);
+
+        assert_eq!(*l.pop().text, "first");
+        assert_eq!(*l.peek_ahead(3).text, "third");
+    }
+
+    #[test]
+    fn tracks_max_lookahead() {
+        let mut l = Lexer::from("a b c d e f g h i j k l m");
+
+        l.peek_ahead(10);
+        for _ in 0..12 {
+            l.pop();
+        }
+        l.peek_ahead(2);
+
+        assert_eq!(l.max_lookahead(), 10);
+    }
+
+    #[test]
+    fn tokens_include_trivia_and_eof() {
+        let kinds: Vec<Tk> = Lexer::from("I # identity")
+            .tokens()
+            .map(|t| t.kind)
+            .collect();
+
+        assert_eq!(kinds, vec![Alias, Whitespace, Comment, Eof]);
+    }
+
+    #[test]
+    fn reads_backslash_as_lambda() {
+        let l = Lexer::from(r"\x => x");
+
+        assert_eq!(
+            l.collect_kinds(),
+            vec![Lambda, Var, Whitespace, Arrow, Whitespace, Var]
+        );
+    }
+
+    #[test]
+    fn reads_underscore_as_a_name() {
+        let l = Lexer::from("(x, _)");
+
+        assert_eq!(
+            l.collect_kinds(),
+            vec![LParen, Var, Comma, Whitespace, Var, RParen]
+        );
+    }
+
+    #[test]
+    fn reads_backslash_before_a_line_break_as_a_continuation() {
+        let l = Lexer::from("foo \\\nbar \\\r\nbaz \\ x");
+
+        assert_eq!(
+            l.collect_kinds(),
+            vec![
+                Var,
+                Whitespace,
+                LineContinuation,
+                Whitespace,
+                Var,
+                Whitespace,
+                LineContinuation,
+                Whitespace,
+                Var,
+                Whitespace,
+                Lambda,
+                Whitespace,
+                Var
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_crlf_as_a_single_whitespace_token() {
+        let mut l = Lexer::from("a\r\nb");
+
+        assert_eq!(l.pop().kind, Var);
+
+        let next = l.pop();
+        assert_eq!(next.kind, Whitespace);
+        assert_eq!(next.span, Span::new(1, 3));
+        assert_eq!(*next.text, "\r\n");
+
+        let next = l.pop();
+        assert_eq!(next.kind, Var);
+        assert_eq!(next.span, Span::new(3, 4));
+    }
+
+    #[test]
+    fn leading_byte_order_mark_is_whitespace() {
+        let mut l = Lexer::from("\u{FEFF}Id");
+
+        let next = l.pop();
+        assert_eq!(next.kind, Whitespace);
+        assert_eq!(next.span, Span::new(0, 3));
+
+        let next = l.pop();
+        assert_eq!(next.kind, Alias);
+        assert_eq!(next.span, Span::new(3, 5));
+    }
+
+    #[test]
+    fn byte_order_mark_is_only_skipped_at_the_start() {
+        let l = Lexer::from("Id \u{FEFF}");
+
+        assert_eq!(l.collect_kinds(), vec![Alias, Whitespace, Unknown]);
+    }
+
+    #[test]
+    fn keyword_tokens_share_their_text() {
+        let mut l = Lexer::from("let in");
+        let keywords = l.keywords().clone();
+
+        let first = l.pop();
+        assert!(Rc::ptr_eq(&first.text, &keywords[Keyword::Let]));
+        assert!(first.is_keyword(&keywords[Keyword::Let]));
+        assert!(!first.is_keyword(&keywords[Keyword::In]));
+        l.pop();
+        assert!(Rc::ptr_eq(&l.pop().text, &keywords[Keyword::In]));
+
+        // Tokens with their own copy of the text are compared by text.
+        let constructed = Token::new(Var, Rc::new("in".to_string()), Span::new(0, 2));
+        assert!(constructed.is_keyword(&keywords[Keyword::In]));
+        let other = Lexer::from("in").pop();
+        assert!(!Rc::ptr_eq(&other.text, &keywords[Keyword::In]));
+        assert!(other.is_keyword(&keywords[Keyword::In]));
+    }
+
+    #[test]
+    fn cuts_off_identifiers_longer_than_the_limit() {
+        let config = LexerConfig {
+            max_ident_len: Some(3),
+        };
+        let l = Lexer::with_config("abcdef Ab x", config);
+
+        let tokens: Vec<Token> = l.tokens().collect();
+        let found: Vec<(Tk, &str)> = tokens
+            .iter()
+            .map(|token| (token.kind, token.text.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Var, "abc"),
+                (Unknown, "def"),
+                (Whitespace, " "),
+                (Alias, "Ab"),
+                (Whitespace, " "),
+                (Var, "x"),
+                (Eof, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn remembers_the_last_popped_token() {
+        let mut l = Lexer::from("x => y");
+        // The test-only `Iterator` impl's `last` would otherwise take priority
+        let last = |l: &Lexer| Lexer::last(l).cloned();
+        assert_eq!(last(&l), None);
+
+        l.pop();
+        assert_eq!(*last(&l).unwrap().text, "x");
+
+        // Peeking doesn't count
+        l.peek_ahead(2);
+        assert_eq!(*last(&l).unwrap().text, "x");
+
+        l.pop();
+        l.pop();
+        assert_eq!(last(&l).unwrap().kind, Arrow);
+        assert_eq!(last(&l).unwrap().span, Span::new(2, 4));
+    }
 }
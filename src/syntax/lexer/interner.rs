@@ -1,3 +1,4 @@
+use crate::syntax::tokens::Keywords;
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -9,12 +10,19 @@ pub struct Interner<'a> {
 }
 
 impl<'a> Interner<'a> {
+    /// Interns the text of every keyword up front, so that tokens read
+    /// later share it and can be compared to it by pointer.
+    pub fn intern_keywords(&mut self) -> Keywords {
+        Keywords::new(|text| self.intern(text))
+    }
+
     /// Produces an `Rc<String>` whose content is equal (`==`) to that of `text`.
     /// Additionally, if `text` has already been interned it doesn't allocate a
     /// new `String`; instead, it simply returns a clone of the pointer to the
     /// previously allocated `String`.
     ///
     /// ```no_run
+    /// # use lammy::syntax::Interner;
     /// let mut i = Interner::default();
     ///
     /// // Since this is the first time we've interned the slice `"apples"`, a
@@ -32,11 +40,27 @@ impl<'a> Interner<'a> {
             new
         })
     }
+
+    /// The number of distinct strings interned (since the last `clear`).
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Forgets every interned string, so that interning one again allocates a
+    /// new `String`. Previously produced `Rc<String>`s remain valid.
+    pub fn clear(&mut self) {
+        self.seen.clear();
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::syntax::tokens::Keyword;
 
     #[test]
     fn interner_shares_duplicate_strings() {
@@ -55,4 +79,33 @@ mod tests {
         assert_eq!(Rc::strong_count(&b1), 2);
         assert_eq!(Rc::strong_count(&c1), 1);
     }
+
+    #[test]
+    fn clearing_forgets_interned_strings() {
+        let mut i = Interner::default();
+
+        let a1 = i.intern("apple");
+        i.intern("banana");
+        i.intern("apple");
+        assert_eq!(i.len(), 2);
+
+        i.clear();
+        assert!(i.is_empty());
+
+        let a2 = i.intern("apple");
+        assert_eq!(i.len(), 1);
+        assert_eq!(a1, a2);
+        assert!(!Rc::ptr_eq(&a1, &a2));
+    }
+
+    #[test]
+    fn keywords_are_interned_up_front() {
+        let mut i = Interner::default();
+        let keywords = i.intern_keywords();
+        assert_eq!(i.len(), Keyword::ALL.len());
+
+        let import = i.intern("import");
+        assert_eq!(i.len(), Keyword::ALL.len());
+        assert!(Rc::ptr_eq(&import, &keywords[Keyword::Import]));
+    }
 }
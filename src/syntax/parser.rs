@@ -4,13 +4,36 @@ mod untyped_tree;
 
 use self::ast::{Module, ReplInput};
 use self::tree_builder::TreeBuilder;
-use crate::errors::SimpleError;
+use crate::errors::{Code, SimpleError};
+use crate::source::Span;
 
-pub fn parse_repl_input<'a>(source: &'a str) -> ParseResult<ReplInput> {
-    TreeBuilder::parse_repl_input(source).map(ReplInput::from)
+/// Parses a single REPL input. If the source contains several `;`-separated
+/// inputs, the first is produced, along with an error covering the rest.
+pub fn parse_repl_input(source: &str) -> ParseResult<ReplInput> {
+    let mut inputs = parse_repl_inputs(source).into_iter();
+    let (_, mut first) = inputs.next().expect("parsed no REPL inputs");
+    if let Some((rest_start, _)) = inputs.next() {
+        let rest = Span::new(rest_start.start, source.len());
+        let error = SimpleError::new("expected a single input", rest);
+        first.errors.push(error.with_code(Code::ExtraneousInput));
+    }
+    first
+}
+
+/// Parses any number of `;`-separated REPL inputs (e.g. several definitions
+/// pasted onto one line). Each input is produced along with its span (which
+/// includes its `;`) and the errors encountered while parsing it.
+pub fn parse_repl_inputs(source: &str) -> Vec<(Span, ParseResult<ReplInput>)> {
+    TreeBuilder::parse_repl_inputs(source)
+        .into_iter()
+        .map(|parsed| {
+            let span = parsed.result.span().clone();
+            (span, parsed.map(ReplInput::from))
+        })
+        .collect()
 }
 
-pub fn parse_module<'a>(source: &'a str) -> ParseResult<Module> {
+pub fn parse_module(source: &str) -> ParseResult<Module> {
     TreeBuilder::parse_module(source).map(Module::from)
 }
 
@@ -19,11 +42,32 @@ pub fn parse_module<'a>(source: &'a str) -> ParseResult<Module> {
 /// incomplete/incorrect, errors will be returned as well.
 #[derive(Debug)]
 pub struct ParseResult<T> {
-    result: T,
-    errors: Vec<SimpleError>,
+    pub(crate) result: T,
+    pub(crate) errors: Vec<SimpleError>,
 }
 
 impl<T> ParseResult<T> {
+    /// Splits this result into the (possibly incomplete) parsed construct and
+    /// the errors encountered while parsing it.
+    ///
+    /// ```
+    /// # use lammy::syntax::parse_module;
+    /// let (module, errors) = parse_module("Id = x => x;").into_parts();
+    /// assert!(errors.is_empty());
+    /// assert_eq!(module.defs.len(), 1);
+    /// ```
+    pub fn into_parts(self) -> (T, Vec<SimpleError>) {
+        (self.result, self.errors)
+    }
+
+    pub fn result(&self) -> &T {
+        &self.result
+    }
+
+    pub fn errors(&self) -> &[SimpleError] {
+        &self.errors
+    }
+
     pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> ParseResult<U> {
         let ParseResult { result, errors } = self;
 
@@ -33,3 +77,283 @@ impl<T> ParseResult<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ast::{ReplCommand, Term};
+    use super::*;
+
+    fn repl_input(source: &str) -> (ReplInput, Vec<SimpleError>) {
+        parse_repl_input(source).into_parts()
+    }
+
+    #[test]
+    fn parse_results_expose_both_halves() {
+        let parsed = parse_module("Id = x => x; K = (x => y => x;");
+
+        assert_eq!(parsed.result().defs.len(), 2);
+        assert_eq!(parsed.errors().len(), 2);
+        assert_eq!(parsed.errors()[0].message(), "unmatched '('");
+
+        let (module, errors) = parsed.into_parts();
+        assert_eq!(module.defs.len(), 2);
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn blank_repl_input_is_empty() {
+        let (input, errors) = repl_input("   ");
+
+        assert!(matches!(input, ReplInput::Empty));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn comment_only_repl_input_is_empty() {
+        let (input, errors) = repl_input("# just a comment");
+
+        assert!(matches!(input, ReplInput::Empty));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn stray_punctuation_is_not_empty() {
+        let (input, errors) = repl_input(";");
+
+        assert!(matches!(input, ReplInput::Unknown));
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn unknown_tokens_are_not_empty() {
+        for source in ["%%%", ".", " . # a comment"] {
+            let (input, errors) = repl_input(source);
+
+            assert!(matches!(input, ReplInput::Unknown), "{}", source);
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].message(), "unknown token");
+        }
+    }
+
+    #[test]
+    fn parses_commands_without_arguments() {
+        let (input, errors) = repl_input("  :quit ");
+        assert!(matches!(input, ReplInput::Command(ReplCommand::Quit)));
+        assert!(errors.is_empty());
+
+        let (input, errors) = repl_input(":reset");
+        assert!(matches!(input, ReplInput::Command(ReplCommand::Reset)));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn parses_commands_with_arguments() {
+        let (input, errors) = repl_input(r#":load "./nats""#);
+        assert!(errors.is_empty());
+        match input {
+            ReplInput::Command(ReplCommand::Load(Some(filepath))) => {
+                assert_eq!(*filepath.text, "./nats")
+            }
+            other => panic!("expected a :load command, found {:?}", other),
+        }
+
+        let (input, errors) = repl_input(":norm K x");
+        assert!(errors.is_empty());
+        assert!(matches!(
+            input,
+            ReplInput::Command(ReplCommand::Norm(Some(Term::App { .. })))
+        ));
+    }
+
+    #[test]
+    fn decodes_escapes_in_filepaths() {
+        let (module, errors) = parse_module(r#"import { A } from "./a\"b\\c";"#).into_parts();
+        assert!(errors.is_empty());
+        let filepath = module.imports[0].filepath.as_ref().unwrap();
+        assert_eq!(*filepath.text, r#"./a"b\c"#);
+    }
+
+    #[test]
+    fn reports_missing_command_arguments() {
+        let (input, errors) = repl_input(":norm");
+
+        assert!(matches!(input, ReplInput::Command(ReplCommand::Norm(None))));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "expected a term before this");
+    }
+
+    #[test]
+    fn reports_unknown_commands() {
+        let (input, errors) = repl_input(":frob x => x");
+
+        assert!(matches!(input, ReplInput::Unknown));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "unknown command `:frob`");
+        assert_eq!(*errors[0].span(), Span::new(0, 5));
+    }
+
+    #[test]
+    fn colons_elsewhere_are_unknown_tokens() {
+        let (input, errors) = repl_input("f : x");
+
+        assert!(matches!(input, ReplInput::Term(Term::App { .. })));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "unknown token");
+    }
+
+    #[test]
+    fn parses_several_separated_inputs() {
+        let source = "Zero = s => z => z; Suc = n => s => z => s (n s z);";
+        let inputs = parse_repl_inputs(source);
+
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs[0].0, Span::new(0, 19));
+        assert!(matches!(inputs[0].1.result, ReplInput::Def(..)));
+        assert_eq!(inputs[1].0, Span::new(19, source.len()));
+        assert!(matches!(inputs[1].1.result, ReplInput::Def(..)));
+        assert!(inputs.iter().all(|(_, input)| input.errors.is_empty()));
+    }
+
+    #[test]
+    fn the_last_separated_input_needs_no_semicolon() {
+        let inputs = parse_repl_inputs("Id = x => x; Id y");
+
+        assert_eq!(inputs.len(), 2);
+        assert!(matches!(inputs[1].1.result, ReplInput::Term(..)));
+        assert!(inputs[1].1.errors.is_empty());
+    }
+
+    #[test]
+    fn separated_inputs_have_their_own_errors() {
+        let inputs = parse_repl_inputs("Id = x => ; K = (x, y) => x");
+
+        assert_eq!(inputs[0].1.errors.len(), 1);
+        assert_eq!(
+            inputs[0].1.errors[0].message(),
+            "abstraction body is missing after this '=>'"
+        );
+        assert!(inputs[1].1.errors.is_empty());
+    }
+
+    #[test]
+    fn a_single_input_may_not_be_followed_by_more() {
+        let (input, errors) = repl_input("Id = x => x; K = (x, y) => x");
+
+        assert!(matches!(input, ReplInput::Def(..)));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "expected a single input");
+        assert_eq!(*errors[0].span(), Span::new(12, 28));
+
+        let (_, errors) = repl_input("Id = x => x;  ");
+        assert!(errors.is_empty());
+    }
+
+    fn term(source: &str) -> Term {
+        match repl_input(source) {
+            (ReplInput::Term(term), _) => term,
+            (other, _) => panic!("expected a term, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn structural_equality_ignores_spans() {
+        let spaced = term("  (x, y)   =>  let z = x in   General.K  z y");
+        let tight = term("(x,y)=>let z=x in General.K z y");
+
+        assert!(spaced.structurally_eq(&tight));
+        assert!(!spaced.structurally_eq(&term("(x, y) => let z = x in General.K y z")));
+        assert!(!spaced.structurally_eq(&term("(x, w) => let z = x in General.K z y")));
+        assert!(!term("x => x").structurally_eq(&term("x =>")));
+    }
+
+    #[test]
+    fn applications_with_missing_terms_are_missing() {
+        for source in ["x ( )", "( ) x y", "K ( ) (z => z) (w => w)"] {
+            let (input, errors) = repl_input(source);
+            assert_eq!(errors.len(), 1, "{}", source);
+            assert!(matches!(input, ReplInput::Unknown), "{}", source);
+        }
+
+        let (input, errors) = repl_input("x => f ( ) y");
+        assert_eq!(errors.len(), 1);
+        match input {
+            ReplInput::Term(Term::Abs { body, .. }) => assert!(body.is_none()),
+            other => panic!("expected an abstraction, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn line_continuations_are_skipped() {
+        let (_, errors) = repl_input("foo \\\n  bar");
+        assert!(errors.is_empty());
+        assert!(term("foo \\\n  bar").structurally_eq(&term("foo bar")));
+
+        let (_, errors) = repl_input("\\x => x");
+        assert!(errors.is_empty());
+    }
+
+    fn var_texts(terms: &[&Term]) -> Vec<String> {
+        terms
+            .iter()
+            .map(|term| match term {
+                Term::Var { text, .. } => text.to_string(),
+                other => panic!("expected a var, found {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spines_flatten_nested_applications() {
+        for source in ["f a b c", "(f a) b c", "((f a) b) c"] {
+            let app = term(source);
+            let (head, args) = app.spine();
+            assert_eq!(var_texts(&[head]), vec!["f"], "{:?}", source);
+            assert_eq!(var_texts(&args), vec!["a", "b", "c"], "{:?}", source);
+        }
+
+        let var = term("f");
+        let (head, args) = var.spine();
+        assert!(head.structurally_eq(&var));
+        assert!(args.is_empty());
+    }
+
+    #[test]
+    fn apply_rebuilds_flat_applications() {
+        let app = Term::apply(term("f a"), vec![term("b"), term("c")]);
+        assert!(app.structurally_eq(&term("f a b c")));
+        // Each part was parsed separately, so every span starts at 0
+        assert_eq!(*app.span(), Span::new(0, 3));
+
+        assert!(Term::apply(term("f"), Vec::new()).structurally_eq(&term("f")));
+    }
+
+    #[test]
+    fn attaches_preceding_line_comments_to_defs() {
+        let source = "# The identity function.
+# Returns its argument unchanged.
+Id = x => x;
+
+# Unattached, thanks to the blank line
+
+K = (x, y) => x; # Not a doc comment for S
+S = (x, y, z) => x z (y z);
+#{ A block comment }#
+I = S K K;
+#Tight
+T = (t, f) => t;";
+        let (module, errors) = parse_module(source).into_parts();
+        assert!(errors.is_empty());
+
+        let docs: Vec<Option<&str>> = module.defs.iter().map(|def| def.doc.as_deref()).collect();
+        assert_eq!(
+            docs,
+            vec![
+                Some("The identity function.\nReturns its argument unchanged."),
+                None,
+                None,
+                None,
+                Some("Tight"),
+            ]
+        );
+    }
+}
@@ -1,4 +1,8 @@
+mod checks;
 mod from_untyped;
+mod order;
+
+pub use self::order::Cycle;
 
 use crate::source::Span;
 use std::rc::Rc;
@@ -10,11 +14,30 @@ pub enum ReplInput {
     Def(Def),
     /// A term to reduce, e.g. `(x => x x) x => x x`.
     Term(Term),
+    /// A command to the REPL itself, e.g. `:load "./nats"`.
+    Command(ReplCommand),
+    /// Input consisting only of whitespace and comments.
+    Empty,
     Unknown,
 }
 
+/// A possibly incomplete REPL command. The arguments of incomplete commands
+/// are missing, in which case errors have already been recorded.
+#[derive(Debug)]
+pub enum ReplCommand {
+    /// `:load "path"`, which loads the module at `path`.
+    Load(Option<Filepath>),
+    /// `:norm term`, which normalizes `term`.
+    Norm(Option<Term>),
+    /// `:quit`, which exits the REPL.
+    Quit,
+    /// `:reset`, which forgets every definition made so far.
+    Reset,
+}
+
 /// A module (file).
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Module {
     /// All of the module's imports.
     pub imports: Vec<Import>,
@@ -25,6 +48,7 @@ pub struct Module {
 
 /// A possibly incomplete/incorrect import declaration.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Import {
     /// The aliases (and vars, potentially) mentioned in the import.
     /// In the import `import { Id, K, bad } from "./common";`, the aliases
@@ -38,16 +62,22 @@ pub struct Import {
 
 /// A possibly incomplete/incorrect alias definition.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Def {
     /// The alias being defined (e.g. `"Id"` in `Id = x => x`).
     pub alias: Option<Name>,
     /// The term being associated with the alias (e.g. `x => x` in `Id = x => x`).
     pub body: Option<Term>,
+    /// The text of the line comments (if any) directly preceding the
+    /// definition, each on its own line and with no blank lines between them,
+    /// with their `#` markers stripped.
+    pub doc: Option<String>,
     pub span: Span,
 }
 
 /// An import filepath.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Filepath {
     pub text: Rc<String>,
     pub span: Span,
@@ -55,11 +85,20 @@ pub struct Filepath {
 
 /// A possibly incomplete/incorrect lambda calculus term.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Term {
     /// A variable reference (i.e. _not_ a bound variable).
     Var { text: Rc<String>, span: Span },
     /// An alias reference.
     Alias { text: Rc<String>, span: Span },
+    /// A reference to an alias in an imported module, e.g. `General.K`.
+    /// Note that the alias may be missing, in which case an error has already
+    /// been recorded.
+    Qualified {
+        module: Name,
+        name: Option<Name>,
+        span: Span,
+    },
     /// An abstraction.
     /// Note that the abstraction may or may not contain a body, and that its
     /// `vars` may be empty. The second of these has already been addressed
@@ -70,8 +109,19 @@ pub enum Term {
         body: Option<Box<Term>>,
         span: Span,
     },
+    /// A local binding, e.g. `let n = Suc Zero in Suc n`, which is equivalent
+    /// to `(n => Suc n) (Suc Zero)`.
+    /// Note that any of its parts may be missing, in which case errors have
+    /// already been recorded during the parsing process.
+    Let {
+        var: Option<Name>,
+        value: Option<Box<Term>>,
+        body: Option<Box<Term>>,
+        span: Span,
+    },
     /// An application.
-    /// Note that the operands (`rands`) may contain no terms.
+    /// Note that the operator (`rator`) may itself be an application (e.g. in
+    /// `(f a) b`); see `spine` for the flattened form.
     App {
         rator: Box<Term>,
         rands: Vec<Term>,
@@ -81,12 +131,218 @@ pub enum Term {
 
 /// A representation of a "name" (text), used for both aliases and vars.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Name {
     /// The name's text.
     pub text: Rc<String>,
     pub span: Span,
-    /// Whether or not the name is "bad": this is `true` if the name is an alias
-    /// appearing where a var is expected (e.g. in an abstraction's bound vars),
-    /// or a var where an alias is expected (e.g. in an import declaration).
-    pub bad: bool,
+    pub kind: NameKind,
+}
+
+/// What sort of name a `Name` is, and whether it's the sort expected where it
+/// appears.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NameKind {
+    Var,
+    Alias,
+    /// A var appearing where an alias is expected (e.g. in an import
+    /// declaration).
+    MisusedVar,
+    /// An alias appearing where a var is expected (e.g. in an abstraction's
+    /// bound vars).
+    MisusedAlias,
+}
+
+impl Module {
+    /// Tests if this module has neither imports nor definitions, e.g. because
+    /// its source is blank or holds only comments.
+    pub fn is_empty(&self) -> bool {
+        self.imports.is_empty() && self.defs.is_empty()
+    }
+}
+
+impl Name {
+    /// Tests if this name is "bad", i.e. of the wrong sort for where it
+    /// appears.
+    pub fn is_bad(&self) -> bool {
+        match self.kind {
+            NameKind::MisusedVar | NameKind::MisusedAlias => true,
+            NameKind::Var | NameKind::Alias => false,
+        }
+    }
+
+    /// Tests if this name is the wildcard `_`, which may be bound (e.g. in
+    /// `(x, _) => x`, to document an ignored argument) but never referred to.
+    pub fn is_wildcard(&self) -> bool {
+        self.text.as_str() == "_"
+    }
+}
+
+impl Term {
+    pub fn span(&self) -> &Span {
+        match self {
+            Term::Var { span, .. }
+            | Term::Alias { span, .. }
+            | Term::Qualified { span, .. }
+            | Term::Abs { span, .. }
+            | Term::Let { span, .. }
+            | Term::App { span, .. } => span,
+        }
+    }
+
+    /// Produces the head of this term's application spine, along with every
+    /// operand it's applied to (in order), looking through nested
+    /// applications. For example, `f a b c` and `(f a) b c` both produce `f`
+    /// and `[a, b, c]`. Terms other than applications are heads without any
+    /// operands.
+    pub fn spine(&self) -> (&Term, Vec<&Term>) {
+        match self {
+            Term::App { rator, rands, .. } => {
+                let (head, mut args) = rator.spine();
+                args.extend(rands);
+                (head, args)
+            }
+            _ => (self, Vec::new()),
+        }
+    }
+
+    /// Applies `head` to `args`, producing `head` itself if there aren't any.
+    /// If `head` is already an application, `args` are added to its operands
+    /// rather than nesting another application.
+    pub fn apply(head: Term, args: Vec<Term>) -> Term {
+        let last_span = match args.last() {
+            Some(last) => last.span().clone(),
+            None => return head,
+        };
+
+        match head {
+            Term::App {
+                rator,
+                mut rands,
+                span,
+            } => {
+                rands.extend(args);
+                Term::App {
+                    rator,
+                    rands,
+                    span: span.combine_with(last_span),
+                }
+            }
+            head => {
+                let span = head.span().clone().combine_with(last_span);
+                Term::App {
+                    rator: Box::new(head),
+                    rands: args,
+                    span,
+                }
+            }
+        }
+    }
+
+    /// Tests if this term and `other` have the same shape and names,
+    /// regardless of where in the source either appears.
+    pub fn structurally_eq(&self, other: &Term) -> bool {
+        match (self, other) {
+            (Term::Var { text, .. }, Term::Var { text: other, .. })
+            | (Term::Alias { text, .. }, Term::Alias { text: other, .. }) => text == other,
+            (
+                Term::Qualified { module, name, .. },
+                Term::Qualified {
+                    module: other_module,
+                    name: other_name,
+                    ..
+                },
+            ) => {
+                module.structurally_eq(other_module)
+                    && options_eq(name, other_name, Name::structurally_eq)
+            }
+            (
+                Term::Abs { vars, body, .. },
+                Term::Abs {
+                    vars: other_vars,
+                    body: other_body,
+                    ..
+                },
+            ) => {
+                vars.len() == other_vars.len()
+                    && vars
+                        .iter()
+                        .zip(other_vars)
+                        .all(|(var, other)| var.structurally_eq(other))
+                    && options_eq(body, other_body, |body, other| body.structurally_eq(other))
+            }
+            (
+                Term::Let {
+                    var, value, body, ..
+                },
+                Term::Let {
+                    var: other_var,
+                    value: other_value,
+                    body: other_body,
+                    ..
+                },
+            ) => {
+                options_eq(var, other_var, Name::structurally_eq)
+                    && options_eq(value, other_value, |value, other| {
+                        value.structurally_eq(other)
+                    })
+                    && options_eq(body, other_body, |body, other| body.structurally_eq(other))
+            }
+            (
+                Term::App { rator, rands, .. },
+                Term::App {
+                    rator: other_rator,
+                    rands: other_rands,
+                    ..
+                },
+            ) => {
+                rator.structurally_eq(other_rator)
+                    && rands.len() == other_rands.len()
+                    && rands
+                        .iter()
+                        .zip(other_rands)
+                        .all(|(rand, other)| rand.structurally_eq(other))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Name {
+    /// Tests if this name and `other` have the same text and kind.
+    pub fn structurally_eq(&self, other: &Name) -> bool {
+        self.text == other.text && self.kind == other.kind
+    }
+}
+
+/// Tests if both options are missing, or both are present and `eq`.
+fn options_eq<T>(left: &Option<T>, right: &Option<T>, eq: impl Fn(&T, &T) -> bool) -> bool {
+    match (left, right) {
+        (Some(left), Some(right)) => eq(left, right),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::syntax::{parse_module, ParseResult};
+
+    #[test]
+    fn modules_round_trip_through_json() {
+        let source = r#"import { Pair } from "./pairs"; Fst = p => p ((x, y) => x);"#;
+        let ParseResult { result, .. } = parse_module(source);
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(
+            json["span"],
+            serde_json::json!({ "start": 0, "end": source.len() })
+        );
+        assert_eq!(json["imports"][0]["filepath"]["text"], "./pairs");
+
+        let module: Module = serde_json::from_value(json).unwrap();
+        assert_eq!(format!("{:?}", module), format!("{:?}", result));
+    }
 }
@@ -0,0 +1,494 @@
+//! ## Structural checks and lints over modules and terms.
+//!
+//! None of these checks evaluate anything; they only inspect the shape of a
+//! (possibly incomplete) module or term.
+
+use super::{Module, Name, NameKind, Term};
+use crate::errors::{Code, LabeledError, SimpleError};
+use crate::source::Span;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+impl Module {
+    /// Reports every definition of an alias that was already defined earlier
+    /// in this module, pointing out the first definition. Definitions whose
+    /// alias is missing or bad are skipped.
+    pub fn check_duplicate_defs(&self) -> Vec<LabeledError> {
+        let mut defined: HashMap<&Rc<String>, &Span> = HashMap::new();
+        let mut errors = Vec::new();
+        let aliases = self.defs.iter().filter_map(|def| def.alias.as_ref());
+        for alias in aliases.filter(|alias| !alias.is_bad()) {
+            match defined.get(&alias.text) {
+                Some(first) => {
+                    let message = format!("alias `{}` already defined", alias.text);
+                    let error = LabeledError::new(message, alias.span.clone())
+                        .with_code(Code::DuplicateDef)
+                        .with_label((*first).clone(), "first defined here");
+                    errors.push(error);
+                }
+                None => {
+                    defined.insert(&alias.text, &alias.span);
+                }
+            }
+        }
+        errors
+    }
+
+    /// Finds the definitions whose body is nothing but a reference to another
+    /// alias (e.g. `Id2 = Id`), which may be accidental duplicates. Produces
+    /// each such definition's alias along with the alias it refers to.
+    pub fn redundant_alias_defs(&self) -> Vec<(Name, Name)> {
+        self.defs
+            .iter()
+            .filter_map(|def| match (&def.alias, &def.body) {
+                (Some(alias), Some(Term::Alias { text, span })) => {
+                    let target = Name {
+                        text: text.clone(),
+                        span: span.clone(),
+                        kind: NameKind::Alias,
+                    };
+                    Some((alias.clone(), target))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Collects the text of every alias referenced by this module's
+    /// definitions. References to other modules' aliases (e.g. `General.K`)
+    /// aren't included.
+    pub fn referenced_aliases(&self) -> HashSet<Rc<String>> {
+        let mut aliases = HashSet::new();
+        for body in self.defs.iter().filter_map(|def| def.body.as_ref()) {
+            body.collect_aliases(&mut aliases);
+        }
+        aliases
+    }
+
+    /// Warns about every imported alias that no definition in this module
+    /// refers to. An empty import list (e.g. `import {} from "./nats"`) is
+    /// never warned about, and bad names have already been reported.
+    pub fn check_unused_imports(&self) -> Vec<SimpleError> {
+        let referenced = self.referenced_aliases();
+        self.imports
+            .iter()
+            .flat_map(|import| &import.aliases)
+            .filter(|alias| !alias.is_bad() && !referenced.contains(&alias.text))
+            .map(|alias| {
+                let message = format!("unused import `{}`", alias.text);
+                SimpleError::warning(message, alias.span.clone()).with_code(Code::UnusedImport)
+            })
+            .collect()
+    }
+}
+
+impl Term {
+    /// Adds the text of every alias this term references to `aliases`.
+    pub(super) fn collect_aliases(&self, aliases: &mut HashSet<Rc<String>>) {
+        match self {
+            Term::Var { .. } | Term::Qualified { .. } => {}
+            Term::Alias { text, .. } => {
+                aliases.insert(Rc::clone(text));
+            }
+            Term::Abs { body, .. } => {
+                if let Some(body) = body {
+                    body.collect_aliases(aliases);
+                }
+            }
+            Term::Let { value, body, .. } => {
+                for term in value.iter().chain(body) {
+                    term.collect_aliases(aliases);
+                }
+            }
+            Term::App { rator, rands, .. } => {
+                rator.collect_aliases(aliases);
+                for rand in rands {
+                    rand.collect_aliases(aliases);
+                }
+            }
+        }
+    }
+
+    /// Collects the text of every var that occurs free in this term, i.e.
+    /// outside the scope of any binder with the same text.
+    pub fn free_vars(&self) -> HashSet<Rc<String>> {
+        match self {
+            Term::Var { text, .. } => HashSet::from([Rc::clone(text)]),
+            Term::Alias { .. } | Term::Qualified { .. } => HashSet::new(),
+            Term::Abs { vars, body, .. } => {
+                let mut free = match body {
+                    Some(body) => body.free_vars(),
+                    None => HashSet::new(),
+                };
+                for var in vars {
+                    free.remove(&var.text);
+                }
+                free
+            }
+            Term::Let {
+                var, value, body, ..
+            } => {
+                let mut free = match body {
+                    Some(body) => body.free_vars(),
+                    None => HashSet::new(),
+                };
+                if let Some(var) = var {
+                    free.remove(&var.text);
+                }
+                if let Some(value) = value {
+                    free.extend(value.free_vars());
+                }
+                free
+            }
+            Term::App { rator, rands, .. } => {
+                let mut free = rator.free_vars();
+                for rand in rands {
+                    free.extend(rand.free_vars());
+                }
+                free
+            }
+        }
+    }
+
+    /// Warns about every bound var that shadows an enclosing bound var with
+    /// the same text (e.g. the second `x` in `x => x => x`).
+    pub fn warn_shadowed(&self) -> Vec<LabeledError> {
+        let mut warnings = Vec::new();
+        self.collect_shadowed(&mut Vec::new(), &mut warnings);
+        warnings
+    }
+
+    fn collect_shadowed<'t>(
+        &'t self,
+        bound_vars: &mut Vec<&'t Name>,
+        warnings: &mut Vec<LabeledError>,
+    ) {
+        match self {
+            Term::Var { .. } | Term::Alias { .. } | Term::Qualified { .. } => {}
+            Term::Abs { vars, body, .. } => {
+                for var in vars {
+                    bind_checking_shadowing(var, bound_vars, warnings);
+                }
+                if let Some(body) = body {
+                    body.collect_shadowed(bound_vars, warnings);
+                }
+                bound_vars.truncate(bound_vars.len() - vars.len());
+            }
+            Term::Let {
+                var, value, body, ..
+            } => {
+                if let Some(value) = value {
+                    value.collect_shadowed(bound_vars, warnings);
+                }
+                if let Some(var) = var {
+                    bind_checking_shadowing(var, bound_vars, warnings);
+                    if let Some(body) = body {
+                        body.collect_shadowed(bound_vars, warnings);
+                    }
+                    bound_vars.pop();
+                }
+            }
+            Term::App { rator, rands, .. } => {
+                rator.collect_shadowed(bound_vars, warnings);
+                for rand in rands {
+                    rand.collect_shadowed(bound_vars, warnings);
+                }
+            }
+        }
+    }
+
+    /// Finds the bound vars that are never referred to in the body of their
+    /// abstraction (e.g. `y` in `(x, y) => x`), which may indicate a typo.
+    /// This lint is opt-in, since constant functions are perfectly valid.
+    pub fn warn_unused(&self) -> Vec<SimpleError> {
+        let mut warnings = Vec::new();
+        self.collect_unused(&mut Vec::new(), &mut warnings);
+        warnings
+    }
+
+    /// `bound_vars` holds each enclosing bound var along with whether it's
+    /// been referred to so far.
+    fn collect_unused<'t>(
+        &'t self,
+        bound_vars: &mut Vec<(&'t Name, bool)>,
+        warnings: &mut Vec<SimpleError>,
+    ) {
+        match self {
+            Term::Var { text, .. } => {
+                if let Some((_, used)) = bound_vars
+                    .iter_mut()
+                    .rev()
+                    .find(|(var, _)| var.text == *text)
+                {
+                    *used = true;
+                }
+            }
+            Term::Alias { .. } | Term::Qualified { .. } => {}
+            Term::Abs { vars, body, .. } => {
+                // Without a body, every var would seem unused
+                let body = match body {
+                    Some(body) => body,
+                    None => return,
+                };
+
+                bound_vars.extend(vars.iter().map(|var| (var, false)));
+                body.collect_unused(bound_vars, warnings);
+                for (var, used) in bound_vars.drain(bound_vars.len() - vars.len()..) {
+                    if !used && !var.is_bad() && !var.is_wildcard() {
+                        let message = format!("unused variable `{}`", var.text);
+                        let warning = SimpleError::warning(message, var.span.clone());
+                        warnings.push(warning.with_code(Code::UnusedVar));
+                    }
+                }
+            }
+            Term::Let {
+                var, value, body, ..
+            } => {
+                if let Some(value) = value {
+                    value.collect_unused(bound_vars, warnings);
+                }
+                let (var, body) = match (var, body) {
+                    (Some(var), Some(body)) => (var, body),
+                    _ => return,
+                };
+
+                bound_vars.push((var, false));
+                body.collect_unused(bound_vars, warnings);
+                if let Some((var, false)) = bound_vars.pop() {
+                    if !var.is_bad() && !var.is_wildcard() {
+                        let message = format!("unused variable `{}`", var.text);
+                        let warning = SimpleError::warning(message, var.span.clone());
+                        warnings.push(warning.with_code(Code::UnusedVar));
+                    }
+                }
+            }
+            Term::App { rator, rands, .. } => {
+                rator.collect_unused(bound_vars, warnings);
+                for rand in rands {
+                    rand.collect_unused(bound_vars, warnings);
+                }
+            }
+        }
+    }
+}
+
+/// Pushes `var` onto `bound_vars`, warning if it shadows one of them.
+fn bind_checking_shadowing<'t>(
+    var: &'t Name,
+    bound_vars: &mut Vec<&'t Name>,
+    warnings: &mut Vec<LabeledError>,
+) {
+    if !var.is_bad() && !var.is_wildcard() {
+        if let Some(shadowed) = bound_vars.iter().rev().find(|bound| bound.text == var.text) {
+            let message = format!("`{}` shadows an enclosing variable", var.text);
+            let warning = LabeledError::warning(message, var.span.clone())
+                .with_code(Code::ShadowedVar)
+                .with_label(shadowed.span.clone(), "enclosing variable bound here");
+            warnings.push(warning);
+        }
+    }
+    bound_vars.push(var);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Severity;
+    use crate::syntax::{parse_module, parse_repl_input, ReplInput};
+
+    fn module(source: &str) -> Module {
+        let (module, _) = parse_module(source).into_parts();
+        module
+    }
+
+    #[test]
+    fn flags_defs_that_only_reference_another_alias() {
+        let m = module("Id = x => x; Id2 = Id;");
+
+        let redundant: Vec<(String, String)> = m
+            .redundant_alias_defs()
+            .into_iter()
+            .map(|(def, target)| (def.text.to_string(), target.text.to_string()))
+            .collect();
+        assert_eq!(redundant, vec![(String::from("Id2"), String::from("Id"))]);
+    }
+
+    #[test]
+    fn accepts_defs_that_apply_another_alias() {
+        let m = module("Id = x => x; Id2 = Id x;");
+
+        assert!(m.redundant_alias_defs().is_empty());
+    }
+
+    #[test]
+    fn reports_redefinitions_of_an_alias() {
+        let m = module("Id = x => x; K = (x, y) => x; Id = y => y; Id = z => z;");
+
+        let errors = m.check_duplicate_defs();
+        let found: Vec<(&str, Span)> = errors
+            .iter()
+            .map(|error| (error.message(), error.span().clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("alias `Id` already defined", Span::new(30, 32)),
+                ("alias `Id` already defined", Span::new(43, 45)),
+            ]
+        );
+        for error in &errors {
+            assert_eq!(error.code(), Code::DuplicateDef);
+            let first_def = (Span::new(0, 2), String::from("first defined here"));
+            assert_eq!(error.labels(), &[first_def]);
+        }
+    }
+
+    #[test]
+    fn names_record_how_they_were_misused() {
+        let m = module(r#"import { foo } from "./common"; id = (X, y) => y;"#);
+
+        assert_eq!(m.imports[0].aliases[0].kind, NameKind::MisusedVar);
+        assert_eq!(m.defs[0].alias.as_ref().unwrap().kind, NameKind::MisusedVar);
+        match &m.defs[0].body {
+            Some(Term::Abs { vars, .. }) => {
+                let kinds: Vec<NameKind> = vars.iter().map(|var| var.kind).collect();
+                assert_eq!(kinds, vec![NameKind::MisusedAlias, NameKind::Var]);
+            }
+            other => panic!("expected an abstraction, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn skips_bad_aliases() {
+        let m = module("id = x => x; id = y => y;");
+
+        assert!(m.check_duplicate_defs().is_empty());
+    }
+
+    fn term(source: &str) -> Term {
+        let (input, _) = parse_repl_input(source).into_parts();
+        match input {
+            ReplInput::Term(term) => term,
+            other => panic!("expected a term, found {:?}", other),
+        }
+    }
+
+    fn unused(source: &str) -> Vec<String> {
+        term(source)
+            .warn_unused()
+            .iter()
+            .map(|warning| warning.message().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn warns_about_unused_bound_vars() {
+        assert_eq!(unused("(x, y) => x"), vec!["unused variable `y`"]);
+        assert_eq!(unused("f => x => f"), vec!["unused variable `x`"]);
+    }
+
+    #[test]
+    fn warns_about_shadowing_binders() {
+        let warnings = term("x => (y, x) => let y = x in y").warn_shadowed();
+
+        let found: Vec<(&str, Span)> = warnings
+            .iter()
+            .map(|warning| (warning.message(), warning.span().clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("`x` shadows an enclosing variable", Span::new(9, 10)),
+                ("`y` shadows an enclosing variable", Span::new(19, 20)),
+            ]
+        );
+        let shadowed: Vec<&Span> = warnings
+            .iter()
+            .flat_map(|warning| warning.labels())
+            .map(|(span, _)| span)
+            .collect();
+        assert_eq!(shadowed, vec![&Span::new(0, 1), &Span::new(6, 7)]);
+        assert!(warnings
+            .iter()
+            .all(|warning| warning.severity() == Severity::Warning));
+    }
+
+    #[test]
+    fn sibling_binders_dont_shadow_each_other() {
+        assert!(term("f (x => x) (x => x)").warn_shadowed().is_empty());
+    }
+
+    #[test]
+    fn shadowed_vars_are_unused() {
+        assert_eq!(unused("x => x => x"), vec!["unused variable `x`"]);
+    }
+
+    #[test]
+    fn accepts_vars_used_anywhere_in_their_body() {
+        assert!(unused("(f, x) => f (y => y x)").is_empty());
+    }
+
+    fn free_vars(source: &str) -> Vec<String> {
+        let mut free: Vec<String> = term(source)
+            .free_vars()
+            .iter()
+            .map(|var| var.to_string())
+            .collect();
+        free.sort();
+        free
+    }
+
+    #[test]
+    fn collects_free_vars() {
+        assert_eq!(free_vars("f (x => x y) x"), vec!["f", "x", "y"]);
+        assert_eq!(free_vars("(x, y) => x y Id"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn let_binds_its_var_in_the_body_only() {
+        assert_eq!(free_vars("let x = x in x y"), vec!["x", "y"]);
+        assert_eq!(unused("let x = Id in Id"), vec!["unused variable `x`"]);
+    }
+
+    #[test]
+    fn shadowing_binders_bind_their_vars() {
+        assert!(free_vars("(x) => (x) => x").is_empty());
+    }
+
+    #[test]
+    fn collects_the_aliases_a_module_references() {
+        let module = module("A = B (x => C x); D = let y = E in General.K y; F = ; G = A;");
+
+        let mut aliases: Vec<String> = module
+            .referenced_aliases()
+            .iter()
+            .map(|alias| alias.to_string())
+            .collect();
+        aliases.sort();
+        assert_eq!(aliases, vec!["A", "B", "C", "E"]);
+    }
+
+    #[test]
+    fn warns_about_unused_imports() {
+        let m = module(
+            r#"import { Zero, Suc, Add } from "./nats"; import {} from "./bools";
+            Two = Suc One; One = Suc Zero;"#,
+        );
+
+        let warnings = m.check_unused_imports();
+        let found: Vec<(&str, Span, Severity)> = warnings
+            .iter()
+            .map(|error| (error.message(), error.span().clone(), error.severity()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("unused import `Add`", Span::new(20, 23), Severity::Warning)]
+        );
+    }
+
+    #[test]
+    fn wildcards_are_neither_unused_nor_shadowing() {
+        assert!(unused("(x, _, _) => let _ = x in x").is_empty());
+        assert!(term("(_, x) => _ => x").warn_shadowed().is_empty());
+    }
+}
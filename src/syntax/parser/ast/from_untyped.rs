@@ -5,8 +5,10 @@
 //! Any panics here are the result of a breached contract between the two.
 
 use super::super::untyped_tree::{SyntaxKind as Sk, UntypedTree};
-use super::{Def, Filepath, Import, Module, Name, ReplInput, Term};
-use crate::syntax::tokens::Token;
+use super::{Def, Filepath, Import, Module, Name, NameKind, ReplCommand, ReplInput, Term};
+use crate::syntax::lexer::decode_escapes;
+use crate::syntax::tokens::{Token, TokenKind as Tk};
+use std::rc::Rc;
 
 use UntypedTree::*;
 
@@ -18,6 +20,10 @@ impl From<UntypedTree> for ReplInput {
                 children,
                 ..
             } => {
+                if children.iter().all(is_trivial_leaf) {
+                    return ReplInput::Empty;
+                }
+
                 let mut children: Vec<UntypedTree> = skip_concrete(children).collect();
 
                 children
@@ -29,6 +35,9 @@ impl From<UntypedTree> for ReplInput {
                         } else if input.has_kind(&Sk::Tms) {
                             let term: Option<Term> = input.into();
                             term.map(ReplInput::Term)
+                        } else if input.has_kind(&Sk::Command) {
+                            let command: Option<ReplCommand> = input.into();
+                            command.map(ReplInput::Command)
                         } else {
                             None
                         }
@@ -46,6 +55,39 @@ impl From<UntypedTree> for ReplInput {
     }
 }
 
+impl From<UntypedTree> for Option<ReplCommand> {
+    fn from(tree: UntypedTree) -> Option<ReplCommand> {
+        match tree {
+            Inner {
+                kind: Sk::Command,
+                children,
+                ..
+            } => {
+                let mut children: Vec<UntypedTree> = skip_concrete(children).collect();
+
+                // Note the ordering here
+                let argument = if children.len() > 1 {
+                    children.pop()
+                } else {
+                    None
+                };
+                let name = children.pop().and_then(<Option<Name>>::from)?;
+
+                match name.text.as_str() {
+                    "load" => Some(ReplCommand::Load(
+                        argument.and_then(<Option<Filepath>>::from),
+                    )),
+                    "norm" => Some(ReplCommand::Norm(argument.and_then(<Option<Term>>::from))),
+                    "quit" => Some(ReplCommand::Quit),
+                    "reset" => Some(ReplCommand::Reset),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
 impl From<UntypedTree> for Module {
     fn from(tree: UntypedTree) -> Module {
         match tree {
@@ -54,22 +96,43 @@ impl From<UntypedTree> for Module {
                 span,
                 children,
             } => {
-                let (imports, defs): (Vec<UntypedTree>, Vec<UntypedTree>) =
-                    skip_concrete(children).partition(|tree| tree.is_import());
-
-                let imports = imports
-                    .into_iter()
-                    .map(<Option<Import>>::from)
-                    .collect::<Option<Vec<Import>>>();
+                let mut imports = Vec::new();
+                let mut defs = Vec::new();
+                let mut doc = DocLines::default();
+                for child in children {
+                    match child {
+                        Leaf(token) => doc.see(&token),
+                        Inner {
+                            kind: Sk::Trivia,
+                            children,
+                            ..
+                        } => {
+                            for child in children {
+                                if let Leaf(token) = child {
+                                    doc.see(&token);
+                                }
+                            }
+                        }
+                        tree if tree.is_import() => {
+                            doc.clear();
+                            imports.push(<Option<Import>>::from(tree));
+                        }
+                        tree => {
+                            let mut def = <Option<Def>>::from(tree);
+                            if let Some(def) = &mut def {
+                                def.doc = doc.take();
+                            }
+                            defs.push(def);
+                        }
+                    }
+                }
 
-                let defs = defs
-                    .into_iter()
-                    .map(<Option<Def>>::from)
-                    .collect::<Option<Vec<Def>>>();
+                let imports = imports.into_iter().collect::<Option<Vec<Import>>>();
+                let defs = defs.into_iter().collect::<Option<Vec<Def>>>();
 
                 Module {
-                    imports: imports.unwrap_or(Vec::new()),
-                    defs: defs.unwrap_or(Vec::new()),
+                    imports: imports.unwrap_or_default(),
+                    defs: defs.unwrap_or_default(),
                     span,
                 }
             }
@@ -96,7 +159,7 @@ impl From<UntypedTree> for Option<Import> {
                 let filepath = children.pop();
                 let aliases = children.pop();
 
-                let aliases = aliases.map(<Vec<Name>>::from).unwrap_or(Vec::new());
+                let aliases = aliases.map(<Vec<Name>>::from).unwrap_or_default();
                 let filepath = filepath.and_then(<Option<Filepath>>::from);
 
                 Some(Import {
@@ -127,7 +190,12 @@ impl From<UntypedTree> for Option<Def> {
                 let alias = alias.and_then(<Option<Name>>::from);
                 let body = body.and_then(<Option<Term>>::from);
 
-                Some(Def { alias, body, span })
+                Some(Def {
+                    alias,
+                    body,
+                    doc: None,
+                    span,
+                })
             }
             _ => None,
         }
@@ -144,11 +212,19 @@ impl From<UntypedTree> for Option<Name> {
         {
             match kind {
                 Sk::Name | Sk::BadName => match children.pop() {
-                    Some(Leaf(Token { text, .. })) => Some(Name {
+                    Some(Leaf(Token {
+                        kind: token_kind,
                         text,
-                        span,
-                        bad: kind == Sk::BadName,
-                    }),
+                        ..
+                    })) => {
+                        let kind = match (kind, token_kind) {
+                            (Sk::Name, Tk::Alias) => NameKind::Alias,
+                            (Sk::Name, _) => NameKind::Var,
+                            (_, Tk::Alias) => NameKind::MisusedAlias,
+                            (_, _) => NameKind::MisusedVar,
+                        };
+                        Some(Name { text, span, kind })
+                    }
                     _ => None,
                 },
                 _ => None,
@@ -167,7 +243,14 @@ impl From<UntypedTree> for Option<Filepath> {
                 span,
                 mut children,
             } => match children.pop() {
-                Some(Leaf(Token { text, .. })) => Some(Filepath { text, span }),
+                Some(Leaf(Token { text, .. })) => {
+                    let text = if text.contains('\\') {
+                        Rc::new(decode_escapes(&text))
+                    } else {
+                        text
+                    };
+                    Some(Filepath { text, span })
+                }
                 _ => None,
             },
             _ => None,
@@ -183,26 +266,24 @@ impl From<UntypedTree> for Option<Term> {
                 span,
                 children,
             } => {
-                let mut children: Vec<UntypedTree> = skip_concrete(children).collect();
+                // If any term is missing (e.g. the contents of `()`), then so
+                // is the application: leaving it out would shift the
+                // remaining operands into the wrong positions
+                let mut terms = skip_concrete(children)
+                    .map(UntypedTree::into_term)
+                    .collect::<Option<Vec<Term>>>()?
+                    .into_iter();
+                let rator = terms.next()?;
+                let rands: Vec<Term> = terms.collect();
 
-                match children.len() {
-                    0 => None,
-                    1 => children.pop().and_then(UntypedTree::to_term),
-                    _ => {
-                        let rator = children
-                            .remove(0)
-                            .to_term()
-                            .map(Box::new)
-                            .expect("parsed application doesn't include operator term");
-
-                        let rands = children
-                            .into_iter()
-                            .map(UntypedTree::to_term)
-                            .collect::<Option<Vec<Term>>>()
-                            .unwrap_or(Vec::new());
-
-                        Some(Term::App { rator, rands, span })
-                    }
+                if rands.is_empty() {
+                    Some(rator)
+                } else {
+                    Some(Term::App {
+                        rator: Box::new(rator),
+                        rands,
+                        span,
+                    })
                 }
             }
             _ => None,
@@ -211,7 +292,7 @@ impl From<UntypedTree> for Option<Term> {
 }
 
 impl UntypedTree {
-    fn to_term(self) -> Option<Term> {
+    fn into_term(self) -> Option<Term> {
         match self {
             Inner {
                 kind,
@@ -226,6 +307,13 @@ impl UntypedTree {
                     Some(Leaf(Token { text, .. })) => Some(Term::Alias { text, span }),
                     _ => None,
                 },
+                Sk::Qualified => {
+                    let mut children = skip_concrete(children);
+                    let module = children.next().and_then(<Option<Name>>::from)?;
+                    let name = children.next().and_then(<Option<Name>>::from);
+
+                    Some(Term::Qualified { module, name, span })
+                }
                 Sk::Abs => {
                     let mut children: Vec<UntypedTree> = skip_concrete(children).collect();
 
@@ -234,10 +322,29 @@ impl UntypedTree {
                     let vars = children.pop();
 
                     let body = body.and_then(<Option<Term>>::from).map(Box::new);
-                    let vars = vars.map(<Vec<Name>>::from).unwrap_or(Vec::new());
+                    let vars = vars.map(<Vec<Name>>::from).unwrap_or_default();
 
                     Some(Term::Abs { vars, body, span })
                 }
+                Sk::Let => {
+                    let mut children: Vec<UntypedTree> = skip_concrete(children).collect();
+
+                    // Note the ordering here
+                    let body = children.pop();
+                    let value = children.pop();
+                    let var = children.pop();
+
+                    let var = var.and_then(<Option<Name>>::from);
+                    let value = value.and_then(<Option<Term>>::from).map(Box::new);
+                    let body = body.and_then(<Option<Term>>::from).map(Box::new);
+
+                    Some(Term::Let {
+                        var,
+                        value,
+                        body,
+                        span,
+                    })
+                }
                 Sk::Tms => {
                     let terms = Inner {
                         kind,
@@ -275,20 +382,81 @@ impl From<UntypedTree> for Vec<Name> {
     fn from(tree: UntypedTree) -> Vec<Name> {
         match tree {
             Inner {
-                kind: Sk::AbsVars,
+                kind: Sk::AbsVars | Sk::ImportAliases,
                 children,
                 ..
             } => {
                 let names: Option<Vec<Name>> =
                     skip_concrete(children).map(<Option<Name>>::from).collect();
-                names.unwrap_or(Vec::new())
+                names.unwrap_or_default()
             }
             _ => Vec::new(),
         }
     }
 }
 
-/// Skips unimportant leaf nodes, leaving an iterator over the important ones.
+/// Collects the doc comment for the next definition in a module: a run of
+/// line comments, each on its own line, with no blank lines between them or
+/// after the last of them. Block comments and anything else reset the run.
+#[derive(Default)]
+struct DocLines {
+    lines: Vec<String>,
+    /// Whether the trivia seen since the last token ended with a line break
+    /// (so that a following comment starts its own line).
+    mid_line: bool,
+}
+
+impl DocLines {
+    fn see(&mut self, token: &Token) {
+        match token.kind {
+            Tk::Whitespace => {
+                let breaks = token.text.matches('\n').count();
+                if breaks > 1 {
+                    self.lines.clear();
+                }
+                if breaks > 0 {
+                    self.mid_line = false;
+                }
+            }
+            Tk::Comment if !self.mid_line && !token.text.starts_with("#{") => {
+                let text = &token.text[1..];
+                let text = text.strip_prefix(' ').unwrap_or(text);
+                self.lines.push(String::from(text));
+                self.mid_line = true;
+            }
+            _ => {
+                self.lines.clear();
+                self.mid_line = true;
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.lines.clear();
+        self.mid_line = true;
+    }
+
+    fn take(&mut self) -> Option<String> {
+        let doc = match self.lines.as_slice() {
+            [] => None,
+            lines => Some(lines.join("\n")),
+        };
+        self.clear();
+        doc
+    }
+}
+
+fn is_trivial_leaf(tree: &UntypedTree) -> bool {
+    match tree {
+        Leaf(token) => token.is_trivial(),
+        Inner { kind, .. } => *kind == Sk::Trivia,
+    }
+}
+
+/// Skips unimportant leaf nodes (and grouped trivia), leaving an iterator over
+/// the important ones.
 fn skip_concrete(children: Vec<UntypedTree>) -> impl Iterator<Item = UntypedTree> {
-    children.into_iter().filter(|child| !child.is_leaf())
+    children
+        .into_iter()
+        .filter(|child| !child.is_leaf() && !child.has_kind(&Sk::Trivia))
 }
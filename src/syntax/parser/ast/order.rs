@@ -0,0 +1,178 @@
+//! ## Ordering a module's definitions by their dependencies.
+
+use super::{Def, Module};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+/// A group of definitions that (directly or indirectly) refer to each other,
+/// so that none of them can be defined before the rest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    /// The aliases involved, each referring to the next (and the last to the
+    /// first). An alias that refers to itself forms a cycle on its own.
+    pub aliases: Vec<Rc<String>>,
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.aliases.as_slice() {
+            [alias] => write!(f, "`{}` refers to itself", alias),
+            aliases => {
+                let aliases: Vec<String> =
+                    aliases.iter().map(|alias| format!("`{}`", alias)).collect();
+                write!(f, "{} refer to each other", aliases.join(", "))
+            }
+        }
+    }
+}
+
+/// Where a definition is in the depth-first traversal.
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Unvisited,
+    InProgress,
+    Done,
+}
+
+impl Module {
+    /// Orders this module's definitions so that each comes after every
+    /// definition it refers to, keeping the source order wherever the
+    /// references allow. Only references to aliases defined in this module
+    /// count (imported aliases are defined elsewhere), and an alias defined
+    /// more than once is taken to refer to its first definition.
+    pub fn topo_order(&self) -> Result<Vec<&Def>, Cycle> {
+        let mut indices = HashMap::new();
+        for (i, def) in self.defs.iter().enumerate() {
+            if let Some(alias) = def.alias.as_ref().filter(|alias| !alias.is_bad()) {
+                indices.entry(Rc::clone(&alias.text)).or_insert(i);
+            }
+        }
+
+        let deps: Vec<Vec<usize>> = self
+            .defs
+            .iter()
+            .map(|def| {
+                let mut aliases = HashSet::new();
+                if let Some(body) = &def.body {
+                    body.collect_aliases(&mut aliases);
+                }
+                let mut deps: Vec<usize> = aliases
+                    .iter()
+                    .filter_map(|alias| indices.get(alias).copied())
+                    .collect();
+                // Visit dependencies in source order, for a stable result
+                deps.sort_unstable();
+                deps
+            })
+            .collect();
+
+        let mut marks = vec![Mark::Unvisited; self.defs.len()];
+        let mut order = Vec::with_capacity(self.defs.len());
+        for i in 0..self.defs.len() {
+            self.visit(i, &deps, &mut marks, &mut Vec::new(), &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// Visits the definition at index `i` depth-first, adding it to `order`
+    /// after its dependencies. `path` holds the definitions being visited,
+    /// from the outermost to the innermost.
+    fn visit<'m>(
+        &'m self,
+        i: usize,
+        deps: &[Vec<usize>],
+        marks: &mut [Mark],
+        path: &mut Vec<usize>,
+        order: &mut Vec<&'m Def>,
+    ) -> Result<(), Cycle> {
+        match marks[i] {
+            Mark::Done => return Ok(()),
+            Mark::InProgress => {
+                // Every definition on the path since the first visit to this
+                // one depends on the next, and the last depends on this one
+                let start = path.iter().position(|&j| j == i).unwrap_or(0);
+                let aliases = path[start..]
+                    .iter()
+                    .filter_map(|&j| self.defs[j].alias.as_ref())
+                    .map(|alias| Rc::clone(&alias.text))
+                    .collect();
+                return Err(Cycle { aliases });
+            }
+            Mark::Unvisited => {}
+        }
+
+        marks[i] = Mark::InProgress;
+        path.push(i);
+        for &dep in &deps[i] {
+            self.visit(dep, deps, marks, path, order)?;
+        }
+        path.pop();
+        marks[i] = Mark::Done;
+        order.push(&self.defs[i]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::parse_module;
+
+    fn ordered_aliases(source: &str) -> Result<Vec<String>, Cycle> {
+        let (module, errors) = parse_module(source).into_parts();
+        assert!(errors.is_empty());
+
+        let order = module.topo_order()?;
+        Ok(order
+            .iter()
+            .map(|def| def.alias.as_ref().unwrap().text.to_string())
+            .collect())
+    }
+
+    fn cycle(aliases: &[&str]) -> Cycle {
+        Cycle {
+            aliases: aliases
+                .iter()
+                .map(|alias| Rc::new(String::from(*alias)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn orders_a_chain_of_definitions() {
+        assert_eq!(
+            ordered_aliases("C = B B; B = A; A = x => x; D = x => x;"),
+            Ok(vec![
+                String::from("A"),
+                String::from("B"),
+                String::from("C"),
+                String::from("D"),
+            ])
+        );
+    }
+
+    #[test]
+    fn ignores_aliases_defined_elsewhere() {
+        assert_eq!(
+            ordered_aliases(r#"import { Zero } from "./nats"; One = Suc Zero;"#),
+            Ok(vec![String::from("One")])
+        );
+    }
+
+    #[test]
+    fn reports_mutually_recursive_definitions() {
+        let error = ordered_aliases("Id = x => x; Even = Odd Id; Odd = Even;").unwrap_err();
+
+        assert_eq!(error, cycle(&["Even", "Odd"]));
+        assert_eq!(error.to_string(), "`Even`, `Odd` refer to each other");
+    }
+
+    #[test]
+    fn reports_self_reference() {
+        let error = ordered_aliases("Loop = x => Loop x;").unwrap_err();
+
+        assert_eq!(error, cycle(&["Loop"]));
+        assert_eq!(error.to_string(), "`Loop` refers to itself");
+    }
+}
@@ -2,15 +2,19 @@
 
 use super::untyped_tree::{SyntaxKind as Sk, UntypedTree};
 use super::ParseResult;
-use crate::errors::SimpleError;
+use crate::errors::{Code, SimpleError};
 use crate::source::Span;
 use crate::syntax::lexer::Lexer;
-use crate::syntax::tokens::{Token, TokenKind as Tk};
+use crate::syntax::tokens::{Keyword, Keywords, Token, TokenKind as Tk};
+use std::fmt;
 
 /// A stateful tree building device.
 pub struct TreeBuilder<'a> {
     /// The source of tokens used to construct a tree.
     tokens: Lexer<'a>,
+    /// The interned text of each keyword, shared with the `tokens`' keyword
+    /// tokens.
+    keywords: Keywords,
     /// A stack of in-progress and completed tree nodes. In-progress nodes are
     /// pushed onto the stack when the appropriate tokens are encountered, and
     /// then later "completed".
@@ -22,48 +26,148 @@ pub struct TreeBuilder<'a> {
     /// The end position of the `Span` of the last token that was popped. We
     /// keep track of this in order to construct spans for entire trees.
     pos: usize,
+    /// The number of terms currently being parsed within one another.
+    depth: usize,
+    /// The most terms that may be nested within one another (see
+    /// `with_max_depth`).
+    max_depth: usize,
+    /// Whether we've given up on the current declaration (or REPL input)
+    /// because it's nested too deeply. Errors are suppressed until it ends,
+    /// since the enclosing terms are bound to seem incomplete.
+    bailing: bool,
+    /// Whether runs of trivia are grouped into `Trivia` nodes (see
+    /// `with_trivia_nodes`).
+    trivia_nodes: bool,
+    /// Whether we're parsing the bound term of a `let` (outside of any
+    /// parentheses), which an `in` ends.
+    in_let_value: bool,
+    /// The first way in which the tree was found to be malformed while
+    /// building it (see `close`), which is produced by `try_take`.
+    malformed: Option<TakeError>,
 }
 
+/// The default for `TreeBuilder::with_max_depth`, which is far deeper than
+/// any hand-written term, but shallow enough to parse without overflowing
+/// the stack.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// The vars that can't be bound or referred to, since they delimit
+/// `let`-expressions.
+const RESERVED_WORDS: [Keyword; 2] = [Keyword::Let, Keyword::In];
+
 impl<'a> TreeBuilder<'a> {
     /// Parses input to the REPL (e.g. definitions, terms, special commands).
     pub fn parse_repl_input(source: &'a str) -> ParseResult<UntypedTree> {
         let mut builder = TreeBuilder::from(source);
-        builder._parse_repl_input();
+        builder._parse_repl_input(false);
         builder.take()
     }
 
+    /// Parses a line of REPL input containing any number of `;`-separated
+    /// inputs, producing a separate tree (and set of errors) for each. The
+    /// last input needn't be followed by a `;`.
+    pub fn parse_repl_inputs(source: &'a str) -> Vec<ParseResult<UntypedTree>> {
+        let mut builder = TreeBuilder::from(source);
+        let mut inputs = Vec::new();
+        loop {
+            builder._parse_repl_input(true);
+            inputs.push(builder.take_input());
+            if builder.tokens.peek().kind == Tk::Eof {
+                break inputs;
+            }
+        }
+    }
+
+    /// Like `parse_repl_input`, but produces an error instead of panicking if
+    /// the parser builds a malformed tree (see `try_take`).
+    pub fn try_parse_repl_input(source: &'a str) -> Result<ParseResult<UntypedTree>, TakeError> {
+        let mut builder = TreeBuilder::from(source);
+        builder._parse_repl_input(false);
+        builder.try_take()
+    }
+
     /// Parses a module (file).
     pub fn parse_module(source: &'a str) -> ParseResult<UntypedTree> {
+        TreeBuilder::from(source).into_module()
+    }
+
+    /// Like `parse_module`, but produces an error instead of panicking if the
+    /// parser builds a malformed tree (see `try_take`).
+    pub fn try_parse_module(source: &'a str) -> Result<ParseResult<UntypedTree>, TakeError> {
         let mut builder = TreeBuilder::from(source);
         builder._parse_module();
-        builder.take()
+        builder.try_take()
+    }
+
+    /// Limits the number of terms that may be nested within one another (e.g.
+    /// by parentheses). A declaration that nests more deeply is reported and
+    /// skipped, rather than risking a stack overflow.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
     }
 
-    fn _parse_repl_input(&mut self) {
+    /// Groups each run of trivia (e.g. a comment and the whitespace around
+    /// it) into a `Trivia` node, so that tools like formatters can address it
+    /// as a unit. By default, trivia is left as bare leaves.
+    pub fn with_trivia_nodes(mut self) -> Self {
+        self.trivia_nodes = true;
+        self
+    }
+
+    /// Parses this builder's source as a module (see `parse_module`).
+    pub fn into_module(mut self) -> ParseResult<UntypedTree> {
+        self._parse_module();
+        self.take()
+    }
+
+    /// Parses a single REPL input. If `separated`, the input ends at a `;`
+    /// (which is included in its tree, along with any trivia that ends the
+    /// source); otherwise it ends at the end of the source.
+    fn _parse_repl_input(&mut self, separated: bool) {
         self.open(Sk::ReplInput);
-        self.skip_trivia();
+        self.skip_trivia_until(Tk::Colon);
         let peek = self.tokens.peek();
         let kind = peek.kind;
         let span = peek.span.clone();
         match kind {
+            Tk::Colon => self.parse_command(),
             Tk::Alias | Tk::Var if self.starts_def() => self.parse_def(),
             Tk::Equals => self.parse_def(),
-            Tk::Var | Tk::Alias | Tk::LParen | Tk::Comma | Tk::Arrow => self.parse_tms(),
-            _ => self.error("expected a definition or term before this", span),
+            Tk::Var | Tk::Alias | Tk::LParen | Tk::Comma | Tk::Arrow | Tk::Lambda => {
+                self.parse_tms()
+            }
+            // Blank lines and lone comments are no-ops, not errors.
+            Tk::Eof => {}
+            _ => self.error(
+                Code::ExpectedDef,
+                "expected a definition or term before this",
+                span,
+            ),
         }
+        self.bailing = false;
 
         self.skip_trivia();
-        let start_span = self.tokens.peek().span.clone();
-        let end_span = loop {
+        let start = self.tokens.peek().span.start;
+        let end = loop {
             let peek = self.tokens.peek();
             match peek.kind {
-                Tk::Eof => break peek.span.clone(),
+                Tk::Eof => break peek.span.start,
+                Tk::Semi if separated => break peek.span.start,
                 _ => self.pop_leaf(),
             }
         };
 
-        if start_span != end_span {
-            self.error("extraneous input", start_span.combine_with(end_span));
+        let extraneous = Span::new(start, end);
+        if !extraneous.is_empty() {
+            self.error(Code::ExtraneousInput, "extraneous input", extraneous);
+        }
+
+        if separated && self.tokens.peek().kind == Tk::Semi {
+            self.pop_leaf();
+            if self.only_trivia_remains() {
+                self.skip_trivia();
+            }
         }
 
         self.close(Sk::ReplInput);
@@ -78,31 +182,38 @@ impl<'a> TreeBuilder<'a> {
             let span = peek.span.clone();
             match kind {
                 Tk::Eof => break,
-                Tk::Var if *peek.text == "import" => self.parse_import(),
+                Tk::Var if peek.is_keyword(&self.keywords[Keyword::Import]) => self.parse_import(),
+                Tk::Var if self.starts_misspelled_import() => self.parse_import(),
                 Tk::LBrace | Tk::RBrace | Tk::String | Tk::UnterminatedString => {
                     self.parse_import()
                 }
                 Tk::Alias | Tk::Var if self.starts_def() => self.parse_def(),
                 Tk::Equals => self.parse_def(),
-                Tk::Semi => self.error("extraneous ';'", span),
+                Tk::Semi => self.error(Code::ExtraneousInput, "extraneous ';'", span),
                 _ => {
                     let span = self.skip_to_decl_separator();
-                    self.error("expected definition or import declaration here", span);
+                    self.error(
+                        Code::ExpectedDef,
+                        "expected definition or import declaration here",
+                        span,
+                    );
                 }
             }
 
+            self.bailing = false;
+
             self.skip_trivia();
             let peek = self.tokens.peek();
             match peek.kind {
                 Tk::Semi => self.pop_leaf(),
                 Tk::Eof => {
                     let span = peek.span.clone();
-                    self.error("missing a ';'", span);
+                    self.error(Code::MissingSemicolon, "missing a ';'", span);
                     break;
                 }
                 _ => {
                     let span = self.skip_to_decl_separator();
-                    self.error("extraneous input", span);
+                    self.error(Code::ExtraneousInput, "extraneous input", span);
 
                     debug_assert!(match self.tokens.peek().kind {
                         Tk::Semi | Tk::Eof => true,
@@ -144,14 +255,18 @@ impl<'a> TreeBuilder<'a> {
             }
             Tk::Var => {
                 let span = peek.span.clone();
-                self.error("expected an alias, not a var", span);
+                self.error(Code::MisusedName, "expected an alias, not a var", span);
                 self.open(Sk::BadName);
                 self.pop_leaf();
                 self.close(Sk::BadName);
             }
             Tk::Equals => {
                 let span = peek.span.clone();
-                self.error("expected an alias name before this", span);
+                self.error(
+                    Code::ExpectedName,
+                    "expected an alias name before this",
+                    span,
+                );
                 self.missing();
             }
             _ => unreachable!(),
@@ -161,21 +276,33 @@ impl<'a> TreeBuilder<'a> {
         let peek = self.tokens.peek();
         match peek.kind {
             Tk::Equals => self.pop_leaf(),
-            Tk::Var | Tk::Alias | Tk::LParen | Tk::Comma | Tk::Arrow => {
+            Tk::Var | Tk::Alias | Tk::LParen | Tk::Comma | Tk::Arrow | Tk::Lambda => {
                 let span = peek.span.clone();
-                self.error("expected an '=' before this", span);
+                self.error(Code::ExpectedToken, "expected an '=' before this", span);
             }
             _ => {
                 let span = peek.span.clone();
-                self.error("expected an '=', followed by a term before this", span);
+                self.error(
+                    Code::ExpectedToken,
+                    "expected an '=', followed by a term before this",
+                    span,
+                );
                 self.missing();
                 self.close(Sk::Def);
                 return;
             }
         }
 
+        let body_start = self.pos;
         self.skip_trivia();
-        self.parse_tms();
+        if self.starts_tm() {
+            self.parse_tms();
+        } else {
+            // Point at the (possibly blank) stretch where the body should be
+            let span = Span::new(body_start, self.tokens.peek().span.start);
+            self.error(Code::MissingTerm, "definition body is missing", span);
+            self.missing();
+        }
         self.close(Sk::Def);
     }
 
@@ -189,7 +316,12 @@ impl<'a> TreeBuilder<'a> {
 
         let peek = self.tokens.peek();
         match peek.kind {
-            Tk::Var if *peek.text == "import" => self.pop_leaf(),
+            Tk::Var if peek.is_keyword(&self.keywords[Keyword::Import]) => self.pop_leaf(),
+            Tk::Var if is_import_typo(&peek.text) => {
+                let span = peek.span.clone();
+                self.error(Code::ImportTypo, "did you mean `import`?", span);
+                self.pop_leaf();
+            }
             Tk::LBrace
             | Tk::Alias
             | Tk::Var
@@ -198,7 +330,7 @@ impl<'a> TreeBuilder<'a> {
             | Tk::String
             | Tk::UnterminatedString => {
                 let span = peek.span.clone();
-                self.error("expected 'import' before this", span);
+                self.error(Code::ExpectedToken, "expected 'import' before this", span);
             }
             _ => unreachable!(),
         }
@@ -209,14 +341,18 @@ impl<'a> TreeBuilder<'a> {
         self.skip_trivia();
         let peek = self.tokens.peek();
         match peek.kind {
-            Tk::Var if *peek.text == "from" => self.pop_leaf(),
+            Tk::Var if peek.is_keyword(&self.keywords[Keyword::From]) => self.pop_leaf(),
             Tk::String | Tk::UnterminatedString => {
                 let span = peek.span.clone();
-                self.error("expected 'from' before this", span);
+                self.error(Code::ExpectedToken, "expected 'from' before this", span);
             }
             _ => {
                 let span = peek.span.clone();
-                self.error("expected 'from', followed by a filepath before this", span);
+                self.error(
+                    Code::ExpectedToken,
+                    "expected 'from', followed by a filepath before this",
+                    span,
+                );
                 self.missing();
                 self.close(Sk::Import);
                 return;
@@ -233,14 +369,18 @@ impl<'a> TreeBuilder<'a> {
             }
             Tk::UnterminatedString => {
                 let span = peek.span.clone();
-                self.error("unterminated filepath", span);
+                self.error(Code::UnterminatedString, "unterminated filepath", span);
                 self.open(Sk::ImportFilepath);
                 self.pop_leaf();
                 self.close(Sk::ImportFilepath);
             }
             _ => {
                 let span = peek.span.clone();
-                self.error("expected a filepath before this", span);
+                self.error(
+                    Code::MissingFilepath,
+                    "expected a filepath before this",
+                    span,
+                );
                 self.missing();
                 self.close(Sk::Import);
                 return;
@@ -251,7 +391,7 @@ impl<'a> TreeBuilder<'a> {
     }
 
     fn parse_import_aliases(&mut self) {
-        debug_assert!(self.tokens.peek().is_nontrivial());
+        debug_assert!(!self.tokens.peek().is_skippable());
 
         let peek = self.tokens.peek();
         let span = peek.span.clone();
@@ -262,10 +402,11 @@ impl<'a> TreeBuilder<'a> {
             }
             Tk::Alias | Tk::Var | Tk::Comma | Tk::RBrace => {
                 self.open(Sk::ImportAliases);
-                self.error("expected a '{' before this", span);
+                self.error(Code::ExpectedToken, "expected a '{' before this", span);
             }
             _ => {
                 self.error(
+                    Code::ExpectedToken,
                     "expected a list of aliases enclosed in '{..}' before this",
                     span,
                 );
@@ -285,7 +426,11 @@ impl<'a> TreeBuilder<'a> {
                 }
                 Tk::Var => {
                     let span = peek.span.clone();
-                    self.error("expected an alias here, not a name", span);
+                    let message = format!(
+                        "imported names must be capitalized aliases, found var `{}`",
+                        peek.text
+                    );
+                    self.error(Code::MisusedName, message, span);
                     self.open(Sk::BadName);
                     self.pop_leaf();
                     self.close(Sk::BadName);
@@ -296,11 +441,11 @@ impl<'a> TreeBuilder<'a> {
                 }
                 Tk::Comma => {
                     let span = peek.span.clone();
-                    self.error("extraneous ','", span);
+                    self.error(Code::ExtraneousInput, "extraneous ','", span);
                 }
                 _ => {
                     let span = peek.span.clone();
-                    self.error("expected a '}' before this", span);
+                    self.error(Code::ExpectedToken, "expected a '}' before this", span);
                     break;
                 }
             }
@@ -315,11 +460,11 @@ impl<'a> TreeBuilder<'a> {
                 }
                 Tk::Alias | Tk::Var => {
                     let span = peek.span.clone();
-                    self.error("expected a ',' before this", span);
+                    self.error(Code::ExpectedToken, "expected a ',' before this", span);
                 }
                 _ => {
                     let span = peek.span.clone();
-                    self.error("expected a '}' before this", span);
+                    self.error(Code::ExpectedToken, "expected a '}' before this", span);
                     break;
                 }
             }
@@ -328,8 +473,107 @@ impl<'a> TreeBuilder<'a> {
         self.close(Sk::ImportAliases);
     }
 
+    /// Parses a REPL command, e.g. `:load "./nats"` or `:quit`. The command's
+    /// name must immediately follow the ':'. Any input following an unknown
+    /// command is skipped, since there's no telling what it should be.
+    fn parse_command(&mut self) {
+        debug_assert!(self.tokens.peek().kind == Tk::Colon);
+        self.open(Sk::Command);
+        let colon_span = self.tokens.peek().span.clone();
+        self.pop_leaf();
+
+        let peek = self.tokens.peek();
+        let name = peek.text.clone();
+        let span = colon_span.clone().combine_with(peek.span.clone());
+        match peek.kind {
+            Tk::Var | Tk::Alias => {
+                self.open(Sk::Name);
+                self.pop_leaf();
+                self.close(Sk::Name);
+            }
+            _ => {
+                self.error(
+                    Code::ExpectedName,
+                    "expected a command name after this ':'",
+                    colon_span,
+                );
+                self.missing();
+                self.skip_to_eof();
+                self.close(Sk::Command);
+                return;
+            }
+        }
+
+        match name.as_str() {
+            "load" => {
+                self.skip_trivia();
+                let peek = self.tokens.peek();
+                let kind = peek.kind;
+                let span = peek.span.clone();
+                match kind {
+                    Tk::String => {
+                        self.open(Sk::ImportFilepath);
+                        self.pop_leaf();
+                        self.close(Sk::ImportFilepath);
+                    }
+                    Tk::UnterminatedString => {
+                        self.error(Code::UnterminatedString, "unterminated filepath", span);
+                        self.open(Sk::ImportFilepath);
+                        self.pop_leaf();
+                        self.close(Sk::ImportFilepath);
+                    }
+                    _ => {
+                        self.error(
+                            Code::MissingFilepath,
+                            "expected a filepath before this",
+                            span,
+                        );
+                        self.missing();
+                    }
+                }
+            }
+            "norm" => {
+                self.skip_trivia();
+                let span = self.tokens.peek().span.clone();
+                if self.starts_tm() {
+                    self.parse_tms();
+                } else {
+                    self.error(Code::MissingTerm, "expected a term before this", span);
+                    self.missing();
+                }
+            }
+            "quit" | "reset" => {}
+            _ => {
+                self.error(
+                    Code::UnknownCommand,
+                    format!("unknown command `:{}`", name),
+                    span,
+                );
+                self.skip_to_eof();
+            }
+        }
+
+        self.close(Sk::Command);
+    }
+
+    fn skip_to_eof(&mut self) {
+        while self.tokens.peek().kind != Tk::Eof {
+            self.pop_leaf();
+        }
+    }
+
     fn parse_tms(&mut self) {
-        debug_assert!(self.tokens.peek().is_nontrivial());
+        debug_assert!(!self.tokens.peek().is_skippable());
+        if self.depth >= self.max_depth {
+            let span = self.tokens.peek().span.clone();
+            self.error(Code::NestedTooDeeply, "expression nested too deeply", span);
+            self.bailing = true;
+            self.missing();
+            self.skip_to_decl_separator();
+            return;
+        }
+        self.depth += 1;
+
         self.open(Sk::Tms);
         self.parse_tm();
 
@@ -337,37 +581,140 @@ impl<'a> TreeBuilder<'a> {
             self.skip_trivia();
             let peek = self.tokens.peek();
             match peek.kind {
-                Tk::Var | Tk::Alias | Tk::LParen | Tk::Comma | Tk::Arrow => self.parse_tm(),
+                // An 'in' ends the bound term of an enclosing `let`
+                Tk::Var if self.in_let_value && peek.is_keyword(&self.keywords[Keyword::In]) => {
+                    break
+                }
+                Tk::Var | Tk::Alias | Tk::LParen | Tk::Comma | Tk::Arrow | Tk::Lambda => {
+                    self.parse_tm()
+                }
                 _ => break,
             }
         }
 
         self.close(Sk::Tms);
+        self.depth -= 1;
     }
 
     fn parse_tm(&mut self) {
-        debug_assert!(self.tokens.peek().is_nontrivial());
+        debug_assert!(!self.tokens.peek().is_skippable());
         let peek = self.tokens.peek();
+        let kind = peek.kind;
         let span = peek.span.clone();
-        match peek.kind.clone() {
+        match kind {
+            Tk::Var if peek.is_keyword(&self.keywords[Keyword::Let]) && self.starts_let() => {
+                self.parse_let()
+            }
             Tk::Var if self.starts_single_abs() => self.parse_single_abs(),
             Tk::Var => self.parse_name(),
+            Tk::Alias if self.starts_qualified() => self.parse_qualified(),
             Tk::Alias => self.parse_alias(),
             Tk::LParen if self.starts_abs_names() => self.parse_multi_abs(),
             Tk::LParen => self.parse_parend(),
             Tk::Comma => self.parse_multi_abs(),
             Tk::Arrow => self.parse_abs_from_arrow(),
-            _ => self.error("expected a term before this", span),
+            Tk::Lambda => self.parse_lambda_abs(),
+            _ => self.error(Code::MissingTerm, "expected a term before this", span),
+        }
+    }
+
+    /// Parses `let x = e in body`. The resulting tree always contains three
+    /// important children (the var, the bound term, and the body), any of
+    /// which may be `Missing`.
+    fn parse_let(&mut self) {
+        debug_assert!(self.at_keyword(Keyword::Let));
+        self.open(Sk::Let);
+        self.pop_leaf();
+
+        self.skip_trivia();
+        let peek = self.tokens.peek();
+        let kind = peek.kind;
+        let span = peek.span.clone();
+        match kind {
+            Tk::Var => self.parse_binder(),
+            Tk::Alias => {
+                self.error(Code::MisusedName, "expected a var here, not an alias", span);
+                self.open(Sk::BadName);
+                self.pop_leaf();
+                self.close(Sk::BadName);
+            }
+            _ => {
+                self.error(Code::ExpectedName, "expected a var before this", span);
+                self.missing();
+            }
+        }
+
+        self.skip_trivia();
+        let peek = self.tokens.peek();
+        let kind = peek.kind;
+        let span = peek.span.clone();
+        match kind {
+            Tk::Equals => self.pop_leaf(),
+            _ if self.starts_tm() => {
+                self.error(Code::ExpectedToken, "expected an '=' before this", span)
+            }
+            _ => {
+                self.error(
+                    Code::ExpectedToken,
+                    "expected an '=', followed by a term before this",
+                    span,
+                );
+                self.missing();
+                self.missing();
+                self.close(Sk::Let);
+                return;
+            }
+        }
+
+        self.skip_trivia();
+        let span = self.tokens.peek().span.clone();
+        if self.starts_tm() && !self.at_keyword(Keyword::In) {
+            let in_let_value = std::mem::replace(&mut self.in_let_value, true);
+            self.parse_tms();
+            self.in_let_value = in_let_value;
+        } else {
+            self.error(Code::MissingTerm, "expected a term before this", span);
+            self.missing();
+        }
+
+        self.skip_trivia();
+        let span = self.tokens.peek().span.clone();
+        if self.at_keyword(Keyword::In) {
+            self.pop_leaf();
+
+            self.skip_trivia();
+            if !self.starts_tm() {
+                self.error(
+                    Code::MissingTerm,
+                    "let body is missing after this 'in'",
+                    span,
+                );
+                self.missing();
+                self.close(Sk::Let);
+                return;
+            }
+        } else if self.starts_tm() {
+            self.error(Code::ExpectedToken, "expected 'in' before this", span);
+        } else {
+            self.error(
+                Code::ExpectedToken,
+                "expected 'in', followed by a term before this",
+                span,
+            );
+            self.missing();
+            self.close(Sk::Let);
+            return;
         }
+
+        self.parse_tms();
+        self.close(Sk::Let);
     }
 
     fn parse_single_abs(&mut self) {
         debug_assert!(self.tokens.peek().kind == Tk::Var);
         self.open(Sk::Abs);
         self.open(Sk::AbsVars);
-        self.open(Sk::Name);
-        self.pop_leaf();
-        self.close(Sk::Name);
+        self.parse_binder();
         self.close(Sk::AbsVars);
 
         self.skip_trivia();
@@ -391,6 +738,38 @@ impl<'a> TreeBuilder<'a> {
         self.close(Sk::Abs);
     }
 
+    fn parse_lambda_abs(&mut self) {
+        debug_assert!(self.tokens.peek().kind == Tk::Lambda);
+
+        self.open(Sk::Abs);
+        self.pop_leaf();
+
+        self.skip_trivia();
+        let peek = self.tokens.peek();
+        match peek.kind {
+            Tk::Var => {
+                self.open(Sk::AbsVars);
+                self.parse_binder();
+                self.close(Sk::AbsVars);
+            }
+            Tk::LParen | Tk::Comma => self.parse_abs_names(),
+            _ => {
+                let span = peek.span.clone();
+                self.error(
+                    Code::ExpectedName,
+                    "expected abstraction var(s) after '\\'",
+                    span,
+                );
+                self.missing();
+            }
+        }
+
+        self.skip_trivia();
+        self.parse_abs_after_names();
+
+        self.close(Sk::Abs);
+    }
+
     fn parse_abs_from_arrow(&mut self) {
         debug_assert!(self.tokens.peek().kind == Tk::Arrow);
 
@@ -399,6 +778,7 @@ impl<'a> TreeBuilder<'a> {
 
         let arrow_span = self.tokens.peek().span.clone();
         self.error(
+            Code::ExpectedName,
             "expected abstraction var(s) enclosed in '(..)' before this",
             arrow_span,
         );
@@ -410,23 +790,41 @@ impl<'a> TreeBuilder<'a> {
     }
 
     fn parse_abs_after_names(&mut self) {
-        debug_assert!(self.tokens.peek().is_nontrivial());
+        debug_assert!(!self.tokens.peek().is_skippable());
         let peek = self.tokens.peek();
         match peek.kind {
-            Tk::Arrow => self.pop_leaf(),
+            Tk::Arrow => {
+                let arrow_span = peek.span.clone();
+                self.pop_leaf();
+
+                self.skip_trivia();
+                if !self.starts_tm() {
+                    self.error(
+                        Code::MissingTerm,
+                        "abstraction body is missing after this '=>'",
+                        arrow_span,
+                    );
+                    self.missing();
+                    return;
+                }
+            }
             Tk::Var | Tk::Alias | Tk::LParen | Tk::Comma => {
                 let span = peek.span.clone();
-                self.error("expected an '=>' before this", span);
+                self.error(Code::ExpectedToken, "expected an '=>' before this", span);
+                self.skip_trivia();
             }
             _ => {
                 let span = peek.span.clone();
-                self.error("expected an '=>', followed by a term before this", span);
+                self.error(
+                    Code::ExpectedToken,
+                    "expected an '=>', followed by a term before this",
+                    span,
+                );
                 self.missing();
                 return;
             }
         }
 
-        self.skip_trivia();
         self.parse_tms();
     }
 
@@ -442,7 +840,7 @@ impl<'a> TreeBuilder<'a> {
             Tk::LParen => self.pop_leaf(),
             Tk::Comma => {
                 let span = peek.span.clone();
-                self.error("expected a '(' before this", span);
+                self.error(Code::ExpectedToken, "expected a '(' before this", span);
             }
             _ => unreachable!(),
         }
@@ -453,37 +851,45 @@ impl<'a> TreeBuilder<'a> {
             let peek = self.tokens.peek();
             match peek.kind {
                 Tk::Var => {
-                    self.open(Sk::Name);
-                    self.pop_leaf();
-                    self.close(Sk::Name);
+                    self.parse_binder();
                     seen_name = true;
                 }
                 Tk::Alias => {
                     let span = peek.span.clone();
-                    self.error("expected a var here, not an alias", span);
+                    self.error(Code::MisusedName, "expected a var here, not an alias", span);
                     self.open(Sk::BadName);
                     self.pop_leaf();
                     self.close(Sk::BadName);
                     seen_name = true;
                 }
+                // Reached directly after a ',', this is a (permitted)
+                // trailing comma
                 Tk::RParen => {
                     if !seen_name {
                         let span = peek.span.clone();
-                        self.error("expected at least one var before this", span);
+                        self.error(
+                            Code::ExpectedName,
+                            "expected at least one var before this",
+                            span,
+                        );
                     }
                     self.pop_leaf();
                     break;
                 }
                 Tk::Comma => {
                     let span = peek.span.clone();
-                    self.error("extraneous ','", span);
+                    self.error(Code::ExtraneousInput, "extraneous ','", span);
                 }
                 _ => {
                     let span = peek.span.clone();
                     if !seen_name {
-                        self.error("expected at least one var before this", span.clone());
+                        self.error(
+                            Code::ExpectedName,
+                            "expected at least one var before this",
+                            span.clone(),
+                        );
                     }
-                    self.error("expected a ')' before this", span);
+                    self.error(Code::ExpectedToken, "expected a ')' before this", span);
                     break;
                 }
             }
@@ -498,11 +904,11 @@ impl<'a> TreeBuilder<'a> {
                 }
                 Tk::Var | Tk::Alias => {
                     let span = peek.span.clone();
-                    self.error("expected a ',' before this", span);
+                    self.error(Code::ExpectedToken, "expected a ',' before this", span);
                 }
                 _ => {
                     let span = peek.span.clone();
-                    self.error("expected a ')' before this", span);
+                    self.error(Code::ExpectedToken, "expected a ')' before this", span);
                     break;
                 }
             }
@@ -513,11 +919,42 @@ impl<'a> TreeBuilder<'a> {
 
     fn parse_name(&mut self) {
         debug_assert!(self.tokens.peek().kind == Tk::Var);
+        if self.at_reserved_word() {
+            self.parse_reserved_word();
+            return;
+        }
+
         self.open(Sk::Var);
         self.pop_leaf();
         self.close(Sk::Var);
     }
 
+    /// Parses a var that's being bound (e.g. by an abstraction).
+    fn parse_binder(&mut self) {
+        debug_assert!(self.tokens.peek().kind == Tk::Var);
+        if self.at_reserved_word() {
+            self.parse_reserved_word();
+            return;
+        }
+
+        self.open(Sk::Name);
+        self.pop_leaf();
+        self.close(Sk::Name);
+    }
+
+    /// Reports a reserved word that's used as a var, which becomes a
+    /// `BadName`.
+    fn parse_reserved_word(&mut self) {
+        let peek = self.tokens.peek();
+        let message = format!("`{}` is a reserved word", peek.text);
+        let span = peek.span.clone();
+        self.error(Code::ReservedWord, message, span);
+
+        self.open(Sk::BadName);
+        self.pop_leaf();
+        self.close(Sk::BadName);
+    }
+
     fn parse_alias(&mut self) {
         debug_assert!(self.tokens.peek().kind == Tk::Alias);
         self.open(Sk::Alias);
@@ -525,6 +962,38 @@ impl<'a> TreeBuilder<'a> {
         self.close(Sk::Alias);
     }
 
+    /// Parses a qualified name like `General.K`, which may not contain any
+    /// trivia.
+    fn parse_qualified(&mut self) {
+        debug_assert!(self.tokens.peek().kind == Tk::Alias);
+        self.open(Sk::Qualified);
+        self.open(Sk::Name);
+        self.pop_leaf();
+        self.close(Sk::Name);
+
+        let dot = self.tokens.pop();
+        debug_assert!(dot.kind == Tk::Dot);
+        let dot_span = dot.span.clone();
+        self.leaf(dot);
+
+        match self.tokens.peek().kind {
+            Tk::Alias => {
+                self.open(Sk::Name);
+                self.pop_leaf();
+                self.close(Sk::Name);
+            }
+            _ => {
+                self.error(
+                    Code::ExpectedName,
+                    "expected an alias after this '.'",
+                    dot_span,
+                );
+                self.missing();
+            }
+        }
+        self.close(Sk::Qualified);
+    }
+
     fn parse_parend(&mut self) {
         debug_assert!(self.tokens.peek().kind == Tk::LParen);
         let lparen = self.tokens.pop();
@@ -532,15 +1001,91 @@ impl<'a> TreeBuilder<'a> {
         self.leaf(lparen);
 
         self.skip_trivia();
+        let peek = self.tokens.peek();
+        if peek.kind == Tk::RParen {
+            let span = lparen_span.combine_with(peek.span.clone());
+            self.error(Code::MissingTerm, "empty parentheses", span);
+            self.missing();
+            self.pop_leaf();
+            return;
+        }
+        // An 'in' can't end a `let`'s bound term from within parentheses
+        let in_let_value = std::mem::replace(&mut self.in_let_value, false);
         self.parse_tms();
+        self.in_let_value = in_let_value;
 
         self.skip_trivia();
-        match self.tokens.peek().kind {
+        let peek = self.tokens.peek();
+        match peek.kind {
             Tk::RParen => self.pop_leaf(),
-            _ => self.error("unmatched '('", lparen_span),
+            _ => {
+                // Point out where the ')' was expected as well, since the
+                // parenthesized term may be long
+                let span = peek.span.clone();
+                self.error(Code::UnmatchedParen, "unmatched '('", lparen_span);
+                self.push_error(
+                    SimpleError::hint("expected a ')' before this", span)
+                        .with_code(Code::UnmatchedParen),
+                );
+            }
+        }
+    }
+
+    fn at_keyword(&mut self, keyword: Keyword) -> bool {
+        self.tokens.peek().is_keyword(&self.keywords[keyword])
+    }
+
+    fn at_reserved_word(&mut self) -> bool {
+        let keywords = &self.keywords;
+        let peek = self.tokens.peek();
+        RESERVED_WORDS
+            .iter()
+            .any(|word| peek.is_keyword(&keywords[*word]))
+    }
+
+    /// Tests if the `let` that's next starts a `let`-expression, i.e. it's
+    /// followed by the var it binds (or by the '=', if that var is missing).
+    /// Otherwise it's a misused reserved word.
+    fn starts_let(&mut self) -> bool {
+        debug_assert!(self.at_keyword(Keyword::Let));
+
+        let mut peek_cursor = 1;
+        loop {
+            let peek = self.tokens.peek_ahead(peek_cursor);
+            match peek.kind {
+                _ if peek.is_skippable() => {}
+                Tk::Var | Tk::Alias | Tk::Equals => break true,
+                _ => break false,
+            }
+            peek_cursor += 1;
+        }
+    }
+
+    fn only_trivia_remains(&mut self) -> bool {
+        let mut peek_cursor = 0;
+        loop {
+            let peek = self.tokens.peek_ahead(peek_cursor);
+            match peek.kind {
+                Tk::Eof => break true,
+                _ if peek.is_skippable() => {}
+                _ => break false,
+            }
+            peek_cursor += 1;
         }
     }
 
+    fn starts_tm(&mut self) -> bool {
+        match self.tokens.peek().kind {
+            Tk::Var | Tk::Alias | Tk::LParen | Tk::Comma | Tk::Arrow | Tk::Lambda => true,
+            _ => false,
+        }
+    }
+
+    fn starts_qualified(&mut self) -> bool {
+        debug_assert!(self.tokens.peek().kind == Tk::Alias);
+        self.tokens.peek_ahead(1).kind == Tk::Dot
+    }
+
     fn starts_single_abs(&mut self) -> bool {
         debug_assert!(self.tokens.peek().kind == Tk::Var);
 
@@ -548,7 +1093,7 @@ impl<'a> TreeBuilder<'a> {
         loop {
             let peek = self.tokens.peek_ahead(peek_cursor);
             match peek.kind {
-                _ if peek.is_trivial() => {}
+                _ if peek.is_skippable() => {}
                 Tk::Arrow => break true,
                 _ => break false,
             }
@@ -564,7 +1109,7 @@ impl<'a> TreeBuilder<'a> {
         loop {
             let peek = self.tokens.peek_ahead(peek_cursor);
             match peek.kind {
-                _ if peek.is_trivial() => {}
+                _ if peek.is_skippable() => {}
                 Tk::Var | Tk::Alias => {
                     name_count += 1;
                 }
@@ -583,7 +1128,7 @@ impl<'a> TreeBuilder<'a> {
                     loop {
                         let peek = self.tokens.peek_ahead(peek_cursor);
                         match peek.kind {
-                            _ if peek.is_trivial() => {}
+                            _ if peek.is_skippable() => {}
                             Tk::Arrow => return true,
                             _ => return false,
                         }
@@ -606,7 +1151,7 @@ impl<'a> TreeBuilder<'a> {
         loop {
             let peek = self.tokens.peek_ahead(peek_cursor);
             match peek.kind {
-                _ if peek.is_trivial() => {}
+                _ if peek.is_skippable() => {}
                 Tk::Equals => break true,
                 _ => break false,
             }
@@ -614,21 +1159,78 @@ impl<'a> TreeBuilder<'a> {
         }
     }
 
-    fn skip_trivia(&mut self) {
+    /// Tests if the next token is a slight misspelling of `import` (e.g.
+    /// `improt`) that's followed by what looks like a list of aliases.
+    fn starts_misspelled_import(&mut self) -> bool {
+        debug_assert!(self.tokens.peek().kind == Tk::Var);
+        if !is_import_typo(&self.tokens.peek().text) {
+            return false;
+        }
+
+        let mut peek_cursor = 1;
         loop {
-            let peek = self.tokens.peek();
+            let peek = self.tokens.peek_ahead(peek_cursor);
             match peek.kind {
-                Tk::Whitespace | Tk::Comment => self.pop_leaf(),
-                Tk::Unknown => {
-                    let span = peek.span.clone();
-                    self.error("unknown token", span);
-                    self.pop_leaf();
-                }
-                _ => break,
+                _ if peek.is_skippable() => {}
+                Tk::LBrace | Tk::Alias => break true,
+                _ => break false,
+            }
+            peek_cursor += 1;
+        }
+    }
+
+    fn skip_trivia(&mut self) {
+        self.skip_trivia_until(Tk::Eof);
+    }
+
+    /// Like `skip_trivia`, but stops at the first token of kind `stop`, even
+    /// if it's trivia.
+    fn skip_trivia_until(&mut self, stop: Tk) {
+        let at_trivia = |peek: &Token| peek.kind != stop && peek.is_trivial();
+        while self.tokens.peek().kind != stop && self.tokens.peek().is_skippable() {
+            if !at_trivia(self.tokens.peek()) {
+                self.pop_stray();
+                continue;
+            }
+
+            if self.trivia_nodes {
+                self.open(Sk::Trivia);
+            }
+            while at_trivia(self.tokens.peek()) {
+                self.pop_trivia();
+            }
+            if self.trivia_nodes {
+                self.close(Sk::Trivia);
             }
         }
     }
 
+    /// Pops a trivial token, reporting it if it doesn't belong in the input.
+    fn pop_trivia(&mut self) {
+        let peek = self.tokens.peek();
+        debug_assert!(peek.is_trivial());
+        if peek.kind == Tk::UnterminatedComment {
+            let span = peek.span.clone();
+            self.error(
+                Code::UnterminatedComment,
+                "unterminated block comment",
+                span,
+            );
+        }
+        self.pop_leaf();
+    }
+
+    /// Pops (and reports) a token that's out of place wherever it appears.
+    /// Stray tokens aren't trivia, so they're never grouped into `Trivia`
+    /// nodes.
+    fn pop_stray(&mut self) {
+        let peek = self.tokens.peek();
+        debug_assert!(peek.is_skippable() && peek.is_nontrivial());
+        let span = peek.span.clone();
+        self.error(Code::UnknownToken, "unknown token", span);
+        self.pop_leaf();
+    }
+
     fn pop_leaf(&mut self) {
         let next = self.tokens.pop();
         self.leaf(next);
@@ -646,7 +1248,17 @@ impl<'a> TreeBuilder<'a> {
         });
     }
 
+    /// Completes the most recently opened node, which must be of kind `kind`.
+    /// If it isn't (or there's no such node), the first such problem is
+    /// recorded for `try_take` to produce, and the tree is left as intact as
+    /// possible.
     fn close(&mut self, kind: Sk) {
+        if let Err(error) = self.try_close(kind) {
+            self.malformed.get_or_insert(error);
+        }
+    }
+
+    fn try_close(&mut self, kind: Sk) -> Result<(), TakeError> {
         let mut children = Vec::new();
         while let Some(entry) = self.wip.pop() {
             match entry {
@@ -654,30 +1266,41 @@ impl<'a> TreeBuilder<'a> {
                     kind: open_kind,
                     start,
                 } => {
-                    if open_kind != kind {
-                        panic!(
-                            "`open` and `close` kinds don't match ({:?} != {:?})",
-                            open_kind, kind
-                        );
-                    }
-
+                    let result = if open_kind == kind {
+                        Ok(())
+                    } else {
+                        Err(TakeError::MismatchedClose {
+                            open: open_kind.clone(),
+                            close: kind,
+                        })
+                    };
                     children.reverse();
                     self.wip.push(Entry::Complete(UntypedTree::Inner {
-                        kind,
+                        kind: open_kind,
                         span: Span::new(start, self.pos),
                         children,
                     }));
-                    return;
+                    return result;
                 }
                 Entry::Complete(child) => {
                     children.push(child);
                 }
             }
         }
+
+        children.reverse();
+        self.wip.extend(children.into_iter().map(Entry::Complete));
+        Err(TakeError::UnmatchedClose(kind))
     }
 
-    fn error(&mut self, message: impl Into<String>, span: Span) {
-        self.errors.push(SimpleError::new(message, span));
+    fn error(&mut self, code: Code, message: impl Into<String>, span: Span) {
+        self.push_error(SimpleError::new(message, span).with_code(code));
+    }
+
+    fn push_error(&mut self, error: SimpleError) {
+        if !self.bailing {
+            self.errors.push(error);
+        }
     }
 
     fn missing(&mut self) {
@@ -685,39 +1308,134 @@ impl<'a> TreeBuilder<'a> {
         self.close(Sk::Missing);
     }
 
+    /// Extracts the most recently completed REPL input (see
+    /// `parse_repl_inputs`), along with every error encountered since the
+    /// previous one.
+    fn take_input(&mut self) -> ParseResult<UntypedTree> {
+        let tree = match self.wip.pop() {
+            Some(Entry::Complete(tree)) if self.wip.is_empty() => tree,
+            _ => panic!("no single, complete input to take"),
+        };
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.extend(self.tokens.take_errors());
+        ParseResult {
+            result: tree,
+            errors,
+        }
+    }
+
     /// Extracts a `ParseResult<UntypedTree>` from this builder.
     ///
     /// # Panics
     ///
-    /// This method panics in three separate situations:
-    /// 1. No tree has been started.
-    /// 2. The `open` method has been called without a corresponding call to `close`.
-    /// 3. Multiple toplevel trees have been created.
-    pub fn take(mut self) -> ParseResult<UntypedTree> {
+    /// This method panics in each of the situations described by `TakeError`
+    /// (see `try_take`).
+    pub fn take(self) -> ParseResult<UntypedTree> {
+        self.try_take().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Like `take`, but produces an error instead of panicking if this
+    /// builder's tree is malformed.
+    pub fn try_take(mut self) -> Result<ParseResult<UntypedTree>, TakeError> {
+        if let Some(error) = self.malformed {
+            return Err(error);
+        }
         match self.wip.pop() {
-            None => panic!("no tree to take"),
-            Some(Entry::InProgress { kind, .. }) => panic!("unmatched `open` ({:?})", kind),
+            None => Err(TakeError::NoTree),
+            Some(Entry::InProgress { kind, .. }) => Err(TakeError::UnmatchedOpen(kind)),
             Some(Entry::Complete(tree)) => {
                 if self.wip.is_empty() {
-                    ParseResult {
+                    let mut errors = self.errors;
+                    errors.extend(self.tokens.take_errors());
+                    Ok(ParseResult {
                         result: tree,
-                        errors: self.errors,
-                    }
+                        errors,
+                    })
                 } else {
-                    panic!("multiple toplevel trees")
+                    Err(TakeError::MultipleToplevelTrees)
                 }
             }
         }
     }
 }
 
+/// Tests if `text` is within an edit distance of 2 of (but not exactly)
+/// `import`.
+fn is_import_typo(text: &str) -> bool {
+    let distance = edit_distance(text, Keyword::Import.text());
+    distance > 0 && distance <= 2
+}
+
+/// Computes the Levenshtein distance between `a` and `b`, i.e. the fewest
+/// single-character insertions, deletions, and substitutions that turn one
+/// into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // The distances from the prefix of `a` seen so far to each prefix of `b`
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != *b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The ways in which a builder's tree may be malformed when it's taken. Each
+/// indicates a bug in the parser, rather than a problem with its input.
+#[derive(Debug, PartialEq)]
+pub enum TakeError {
+    /// No tree has been started.
+    NoTree,
+    /// The `open` method has been called (for a node of this kind) without a
+    /// corresponding call to `close`.
+    UnmatchedOpen(Sk),
+    /// Multiple toplevel trees have been created.
+    MultipleToplevelTrees,
+    /// The `close` method has been called for a node of kind `close`, but
+    /// the most recently opened node is of kind `open`.
+    MismatchedClose { open: Sk, close: Sk },
+    /// The `close` method has been called (for a node of this kind) without a
+    /// corresponding call to `open`.
+    UnmatchedClose(Sk),
+}
+
+impl fmt::Display for TakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TakeError::NoTree => write!(f, "no tree to take"),
+            TakeError::UnmatchedOpen(kind) => write!(f, "unmatched `open` ({:?})", kind),
+            TakeError::MultipleToplevelTrees => write!(f, "multiple toplevel trees"),
+            TakeError::MismatchedClose { open, close } => write!(
+                f,
+                "`open` and `close` kinds don't match ({:?} != {:?})",
+                open, close
+            ),
+            TakeError::UnmatchedClose(kind) => write!(f, "unmatched `close` ({:?})", kind),
+        }
+    }
+}
+
 impl<'a> From<&'a str> for TreeBuilder<'a> {
     fn from(source: &'a str) -> Self {
+        let tokens = Lexer::from(source);
+        let keywords = tokens.keywords().clone();
         TreeBuilder {
-            tokens: Lexer::from(source),
+            tokens,
+            keywords,
             wip: Vec::new(),
             errors: Vec::new(),
             pos: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            bailing: false,
+            trivia_nodes: false,
+            malformed: None,
+            in_let_value: false,
         }
     }
 }
@@ -730,6 +1448,8 @@ enum Entry {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::errors::Severity;
+    use crate::syntax::Module;
     use std::fmt;
     use std::rc::Rc;
 
@@ -781,9 +1501,9 @@ mod tests {
         }
     }
 
-    impl ToString for KindTree {
-        fn to_string(&self) -> String {
-            format!("{:?}", self)
+    impl fmt::Display for KindTree {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.fmt_debug(f, 0)
         }
     }
 
@@ -794,72 +1514,583 @@ mod tests {
         let ParseResult { result, errors } = TreeBuilder::parse_repl_input("Id = x => x");
 
         assert!(errors.is_empty());
-        let tree = KindTree::from(result);
-        let expected = r#"ReplInput
-  Def
-    Name
-      "Id"
-    " "
-    "="
-    " "
-    Tms
-      Abs
-        AbsVars
-          Name
-            "x"
-        " "
-        "=>"
-        " "
-        Tms
-          Var
-            "x"
+        let expected = r#"ReplInput@0..11
+  Def@0..11
+    Name@0..2
+      Alias("Id")@0..2
+    Equals("=")@3..4
+    Tms@5..11
+      Abs@5..11
+        AbsVars@5..6
+          Name@5..6
+            Var("x")@5..6
+        Arrow("=>")@7..9
+        Tms@10..11
+          Var@10..11
+            Var("x")@10..11
 "#;
 
-        assert_eq!(tree.to_string(), expected);
+        assert_eq!(result.debug_pruned(), expected);
+    }
+
+    #[test]
+    fn pruned_debug_keeps_every_token_but_whitespace_and_comments() {
+        let ParseResult { result, .. } = TreeBuilder::parse_repl_input(
+            "x # the identity
+ => x",
+        );
+
+        let pruned = result.debug_pruned();
+        assert!(!pruned.contains("Whitespace") && !pruned.contains("Comment"));
+        assert!(pruned.contains(r#"Arrow("=>")@18..20"#));
+        assert!(format!("{:?}", result).contains(r##"Comment("# the identity")"##));
     }
 
     #[test]
     fn single_abs_start_with_name_arrow() {
         let mut builder = TreeBuilder::from("x => x");
-        assert_eq!(builder.starts_single_abs(), true);
+        assert!(builder.starts_single_abs());
 
         let mut builder = TreeBuilder::from("x # A comment\n => x");
-        assert_eq!(builder.starts_single_abs(), true);
+        assert!(builder.starts_single_abs());
 
         let mut builder = TreeBuilder::from("several names =>");
-        assert_eq!(builder.starts_single_abs(), false);
+        assert!(!builder.starts_single_abs());
     }
 
     #[test]
     fn single_abs_enclosed_in_parens_is_parsed_correctly() {
         let mut builder = TreeBuilder::from("(x => x)");
-        assert_eq!(builder.starts_abs_names(), false);
+        assert!(!builder.starts_abs_names());
     }
 
     #[test]
     fn multi_abs_names_start_with_lparen_rparen_arrow() {
         let mut builder = TreeBuilder::from("(x, y) => x");
-        assert_eq!(builder.starts_abs_names(), true);
+        assert!(builder.starts_abs_names());
 
         let mut builder = TreeBuilder::from("(X y # a comment\n => z");
-        assert_eq!(builder.starts_abs_names(), true);
+        assert!(builder.starts_abs_names());
 
         let mut builder = TreeBuilder::from("(X y) z =>");
-        assert_eq!(builder.starts_abs_names(), false);
+        assert!(!builder.starts_abs_names());
     }
 
     #[test]
     fn defs_start_with_a_name_followed_by_equals() {
         let mut builder = TreeBuilder::from("Id = x => x;");
-        assert_eq!(builder.starts_def(), true);
+        assert!(builder.starts_def());
 
         let mut builder = TreeBuilder::from("Foo\n\n= On Another Line;");
-        assert_eq!(builder.starts_def(), true);
+        assert!(builder.starts_def());
 
         let mut builder = TreeBuilder::from("bar = bad");
-        assert_eq!(builder.starts_def(), true);
+        assert!(builder.starts_def());
 
         let mut builder = TreeBuilder::from("Quux ( => =");
-        assert_eq!(builder.starts_def(), false);
+        assert!(!builder.starts_def());
+    }
+
+    #[test]
+    fn reports_unterminated_block_comments() {
+        let ParseResult { errors, .. } = TreeBuilder::parse_module("Id = x => x; #{ Foo = x;");
+
+        assert_eq!(errors.len(), 1);
+    }
+
+    fn inner_kinds(tree: &KindTree, kinds: &mut Vec<String>) {
+        if let Kt::Inner { kind, children } = tree {
+            kinds.push(format!("{:?}", kind));
+            for child in children {
+                inner_kinds(child, kinds);
+            }
+        }
+    }
+
+    fn assert_same_shape(left: &str, right: &str) {
+        let ParseResult { result, errors } = TreeBuilder::parse_repl_input(left);
+        assert!(errors.is_empty());
+        let mut left_kinds = Vec::new();
+        inner_kinds(&KindTree::from(result), &mut left_kinds);
+
+        let ParseResult { result, errors } = TreeBuilder::parse_repl_input(right);
+        assert!(errors.is_empty());
+        let mut right_kinds = Vec::new();
+        inner_kinds(&KindTree::from(result), &mut right_kinds);
+
+        assert_eq!(left_kinds, right_kinds);
+    }
+
+    #[test]
+    fn backslash_abs_parses_like_implicit_abs() {
+        assert_same_shape(r"\x => x", "x => x");
+        assert_same_shape(r"\(x, y) => x", "(x, y) => x");
+        assert_same_shape(r"f (\x => x) y", "f (x => x) y");
+    }
+
+    #[test]
+    fn backslash_abs_requires_an_arrow() {
+        let ParseResult { errors, .. } = TreeBuilder::parse_repl_input(r"\x x");
+
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn reports_missing_abs_bodies_at_the_arrow() {
+        let ParseResult { errors, .. } = TreeBuilder::parse_module("Id = x =>;");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message(),
+            "abstraction body is missing after this '=>'"
+        );
+        assert_eq!(*errors[0].span(), Span::new(7, 9));
+    }
+
+    #[test]
+    fn leading_byte_order_mark_is_ignored() {
+        assert_same_shape("\u{FEFF}Id = x => x", "Id = x => x");
+    }
+
+    #[test]
+    fn shebang_line_is_ignored() {
+        assert_same_shape("#!/usr/bin/env lammy\nId = x => x", "Id = x => x");
+    }
+
+    #[test]
+    fn parses_let_correctly() {
+        let ParseResult { result, errors } =
+            TreeBuilder::parse_repl_input("let n = Suc Zero in Suc n");
+
+        assert!(errors.is_empty());
+        let tree = KindTree::from(result);
+        let expected = r#"ReplInput
+  Tms
+    Let
+      "let"
+      " "
+      Name
+        "n"
+      " "
+      "="
+      " "
+      Tms
+        Alias
+          "Suc"
+        " "
+        Alias
+          "Zero"
+        " "
+      "in"
+      " "
+      Tms
+        Alias
+          "Suc"
+        " "
+        Var
+          "n"
+"#;
+
+        assert_eq!(tree.to_string(), expected);
+    }
+
+    #[test]
+    fn reports_a_missing_in() {
+        let ParseResult { errors, .. } = TreeBuilder::parse_repl_input("let x = y z x");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message(),
+            "expected 'in', followed by a term before this"
+        );
+
+        let ParseResult { errors, .. } = TreeBuilder::parse_module("Id = let x = y;");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message(),
+            "expected 'in', followed by a term before this"
+        );
+    }
+
+    #[test]
+    fn nested_lets_end_at_the_nearest_in() {
+        let ParseResult { result, errors } =
+            TreeBuilder::parse_repl_input("let x = let y = a in y in x");
+        assert!(errors.is_empty());
+
+        let mut kinds = Vec::new();
+        inner_kinds(&KindTree::from(result), &mut kinds);
+        let expected = vec![
+            "ReplInput",
+            "Tms",
+            "Let",
+            "Name",
+            "Tms",
+            "Let",
+            "Name",
+            "Tms",
+            "Var",
+            "Tms",
+            "Var",
+            "Tms",
+            "Var",
+        ];
+        assert_eq!(kinds, expected);
+    }
+
+    fn reserved_word_errors(source: &str) -> Vec<(String, Span)> {
+        let ParseResult { result, errors } = TreeBuilder::parse_repl_input(source);
+        assert_eq!(result.validate(), Ok(()));
+        errors
+            .iter()
+            .map(|error| {
+                assert_eq!(error.code(), Code::ReservedWord, "{}", error.message());
+                (String::from(error.message()), error.span().clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_reserved_words_bound_as_vars() {
+        let in_ = || String::from("`in` is a reserved word");
+        let let_ = || String::from("`let` is a reserved word");
+
+        assert_eq!(
+            reserved_word_errors("in => x"),
+            vec![(in_(), Span::new(0, 2))]
+        );
+        assert_eq!(
+            reserved_word_errors("let => x"),
+            vec![(let_(), Span::new(0, 3))]
+        );
+        assert_eq!(
+            reserved_word_errors("(in, x) => x"),
+            vec![(in_(), Span::new(1, 3))]
+        );
+        assert_eq!(
+            reserved_word_errors("(x, let) => x"),
+            vec![(let_(), Span::new(4, 7))]
+        );
+        assert_eq!(
+            reserved_word_errors("\\in => x"),
+            vec![(in_(), Span::new(1, 3))]
+        );
+        assert_eq!(
+            reserved_word_errors("let in = x in y"),
+            vec![(in_(), Span::new(4, 6))]
+        );
+    }
+
+    #[test]
+    fn reports_reserved_words_used_as_vars() {
+        let in_ = || String::from("`in` is a reserved word");
+        let let_ = || String::from("`let` is a reserved word");
+
+        assert_eq!(reserved_word_errors("f in"), vec![(in_(), Span::new(2, 4))]);
+        assert_eq!(
+            reserved_word_errors("f let"),
+            vec![(let_(), Span::new(2, 5))]
+        );
+        assert_eq!(
+            reserved_word_errors("in => in"),
+            vec![(in_(), Span::new(0, 2)), (in_(), Span::new(6, 8))]
+        );
+        assert_eq!(
+            reserved_word_errors("let => let"),
+            vec![(let_(), Span::new(0, 3)), (let_(), Span::new(7, 10))]
+        );
+
+        // An `in` only ends a `let`'s bound term outside of parentheses, and
+        // not in its body
+        assert_eq!(
+            reserved_word_errors("let x = (f in) in x"),
+            vec![(in_(), Span::new(11, 13))]
+        );
+        assert_eq!(
+            reserved_word_errors("let x = y in f in"),
+            vec![(in_(), Span::new(15, 17))]
+        );
+    }
+
+    #[test]
+    fn accepts_a_trailing_comma_in_abs_vars() {
+        let ParseResult { errors, .. } = TreeBuilder::parse_repl_input("(x, y,) => x");
+        assert!(errors.is_empty());
+
+        let ParseResult { errors, .. } = TreeBuilder::parse_repl_input("(x,\n) => x");
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn rejects_leading_and_doubled_commas_in_abs_vars() {
+        let ParseResult { errors, .. } = TreeBuilder::parse_repl_input("(, x) => x");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "extraneous ','");
+        assert_eq!(*errors[0].span(), Span::new(1, 2));
+
+        let ParseResult { errors, .. } = TreeBuilder::parse_repl_input("(x,, y) => x");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "extraneous ','");
+        assert_eq!(*errors[0].span(), Span::new(3, 4));
+
+        let ParseResult { errors, .. } = TreeBuilder::parse_repl_input("(x, y,,) => x");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(*errors[0].span(), Span::new(6, 7));
+    }
+
+    #[test]
+    fn reports_both_ends_of_an_unmatched_paren() {
+        let ParseResult { errors, .. } = TreeBuilder::parse_module("Id = (x => x x;");
+
+        let found: Vec<(&str, Span, Severity)> = errors
+            .iter()
+            .map(|error| (error.message(), error.span().clone(), error.severity()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("unmatched '('", Span::new(5, 6), Severity::Error),
+                (
+                    "expected a ')' before this",
+                    Span::new(14, 15),
+                    Severity::Hint
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_qualified_names() {
+        let ParseResult { result, errors } = TreeBuilder::parse_repl_input("General.K x");
+        assert!(errors.is_empty());
+
+        let mut kinds = Vec::new();
+        inner_kinds(&result.into(), &mut kinds);
+        assert_eq!(
+            kinds,
+            vec!["ReplInput", "Tms", "Qualified", "Name", "Name", "Var"]
+        );
+    }
+
+    #[test]
+    fn reports_qualified_names_without_an_alias() {
+        let ParseResult { errors, .. } = TreeBuilder::parse_repl_input("General. K");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "expected an alias after this '.'");
+        assert_eq!(*errors[0].span(), Span::new(7, 8));
+
+        let ParseResult { errors, .. } = TreeBuilder::parse_repl_input("General .K");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "unknown token");
+        assert_eq!(*errors[0].span(), Span::new(8, 9));
+    }
+
+    #[test]
+    fn reports_missing_def_bodies() {
+        let ParseResult { errors, .. } = TreeBuilder::parse_module("Foo = ;");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "definition body is missing");
+        assert_eq!(*errors[0].span(), Span::new(5, 6));
+    }
+
+    #[test]
+    fn reports_empty_parentheses() {
+        let ParseResult { errors, .. } = TreeBuilder::parse_module("Foo = ( );");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "empty parentheses");
+        assert_eq!(*errors[0].span(), Span::new(6, 9));
+    }
+
+    #[test]
+    fn try_take_reports_malformed_trees() {
+        let builder = TreeBuilder::from("x");
+        assert_eq!(builder.try_take().unwrap_err(), TakeError::NoTree);
+
+        let mut builder = TreeBuilder::from("x");
+        builder.open(Sk::Tms);
+        assert_eq!(
+            builder.try_take().unwrap_err(),
+            TakeError::UnmatchedOpen(Sk::Tms)
+        );
+
+        let mut builder = TreeBuilder::from("x y");
+        builder.pop_leaf();
+        builder.pop_leaf();
+        assert_eq!(
+            builder.try_take().unwrap_err(),
+            TakeError::MultipleToplevelTrees
+        );
+
+        let mut builder = TreeBuilder::from("x");
+        builder.open(Sk::Tms);
+        builder.pop_leaf();
+        builder.close(Sk::Abs);
+        assert_eq!(
+            builder.try_take().unwrap_err(),
+            TakeError::MismatchedClose {
+                open: Sk::Tms,
+                close: Sk::Abs
+            }
+        );
+
+        let mut builder = TreeBuilder::from("x");
+        builder.pop_leaf();
+        builder.close(Sk::Tms);
+        assert_eq!(
+            builder.try_take().unwrap_err(),
+            TakeError::UnmatchedClose(Sk::Tms)
+        );
+
+        let mut builder = TreeBuilder::from("x");
+        builder.pop_leaf();
+        assert!(builder.try_take().is_ok());
+    }
+
+    #[test]
+    fn try_parse_agrees_with_parse() {
+        let source = "Id = x => x; K = (x, y) => x";
+        let parsed = TreeBuilder::try_parse_module(source).unwrap();
+        assert_eq!(
+            parsed.result.to_source(),
+            TreeBuilder::parse_module(source).result.to_source()
+        );
+        assert_eq!(parsed.errors.len(), 1);
+
+        let parsed = TreeBuilder::try_parse_repl_input("(x => x) y").unwrap();
+        assert!(parsed.errors.is_empty());
+        assert_eq!(parsed.result.to_source(), "(x => x) y");
+    }
+
+    #[test]
+    fn reports_vars_in_imports() {
+        let ParseResult { errors, .. } =
+            TreeBuilder::parse_module(r#"import { Id, foo, K } from "./common";"#);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message(),
+            "imported names must be capitalized aliases, found var `foo`"
+        );
+        assert_eq!(errors[0].code(), Code::MisusedName);
+        assert_eq!(*errors[0].span(), Span::new(13, 16));
+    }
+
+    #[test]
+    fn recovers_from_a_misspelled_import() {
+        let ParseResult { result, errors } =
+            TreeBuilder::parse_module(r#"improt { I } from "./std"; Id = I;"#);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "did you mean `import`?");
+        assert_eq!(*errors[0].span(), Span::new(0, 6));
+
+        let mut kinds = Vec::new();
+        inner_kinds(&result.into(), &mut kinds);
+        assert_eq!(kinds[..3], ["Module", "Import", "ImportAliases"]);
+    }
+
+    #[test]
+    fn only_slight_misspellings_are_taken_for_imports() {
+        assert_eq!(edit_distance("improt", "import"), 2);
+        assert_eq!(edit_distance("imports", "import"), 1);
+
+        let ParseResult { errors, .. } =
+            TreeBuilder::parse_module(r#"include { I } from "./std";"#);
+        assert_eq!(
+            errors[0].message(),
+            "expected definition or import declaration here"
+        );
+    }
+
+    #[test]
+    fn gives_up_gracefully_on_deeply_nested_terms() {
+        let depth = 100_000;
+        let source = format!(
+            "Deep = {}x{}; Id = x => x;",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        );
+
+        let ParseResult { result, errors } = TreeBuilder::parse_module(&source);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "expression nested too deeply");
+        assert_eq!(errors[0].code(), Code::NestedTooDeeply);
+        assert_eq!(result.validate(), Ok(()));
+
+        // Parsing resumes after the offending declaration
+        let module = Module::from(result);
+        assert_eq!(module.defs.len(), 2);
+        assert!(module.defs[1].body.is_some());
+    }
+
+    #[test]
+    fn the_nesting_limit_is_configurable() {
+        let ParseResult { errors, .. } = TreeBuilder::from("A = ((x));")
+            .with_max_depth(3)
+            .into_module();
+        assert!(errors.is_empty());
+
+        let ParseResult { errors, .. } = TreeBuilder::from("A = ((x));")
+            .with_max_depth(2)
+            .into_module();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(*errors[0].span(), Span::new(6, 7));
+    }
+
+    #[test]
+    fn trivia_nodes_group_comments_with_surrounding_whitespace() {
+        let source = "Id = x => x;\n# identity\nK = x => y => x;";
+        let parse = || TreeBuilder::from(source).with_trivia_nodes().into_module();
+
+        let ParseResult { result, errors } = parse();
+        assert!(errors.is_empty());
+        assert_eq!(result.validate(), Ok(()));
+        assert_eq!(result.to_source(), source);
+
+        let UntypedTree::Inner { children, .. } = result else {
+            panic!("expected a module");
+        };
+        let trivia = children.into_iter().nth(2).unwrap();
+        assert_eq!(*trivia.span(), Span::new(12, 24));
+
+        let trivia = Kt::from(trivia);
+        let expected = Kt::inner(
+            Sk::Trivia,
+            vec![Kt::leaf("\n"), Kt::leaf("# identity"), Kt::leaf("\n")],
+        );
+        assert_eq!(trivia, expected, "\n{}", trivia);
+
+        // Grouped trivia doesn't change the syntax tree
+        let module = Module::from(parse().result);
+        assert_eq!(module.defs.len(), 2);
+        assert_eq!(module.defs[1].doc.as_deref(), Some("identity"));
+    }
+
+    #[test]
+    fn stray_tokens_are_not_grouped_into_trivia() {
+        let source = "# note\n% Id = x => x;";
+        let ParseResult { result, errors } =
+            TreeBuilder::from(source).with_trivia_nodes().into_module();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "unknown token");
+        assert_eq!(result.to_source(), source);
+
+        let UntypedTree::Inner { children, .. } = result else {
+            panic!("expected a module");
+        };
+        let leading: Vec<Kt> = children.into_iter().take(3).map(Kt::from).collect();
+        let expected = vec![
+            Kt::inner(Sk::Trivia, vec![Kt::leaf("# note"), Kt::leaf("\n")]),
+            Kt::leaf("%"),
+            Kt::inner(Sk::Trivia, vec![Kt::leaf(" ")]),
+        ];
+        assert_eq!(leading, expected);
+    }
+
+    #[test]
+    fn trivia_is_left_as_bare_leaves_by_default() {
+        let ParseResult { result, .. } = TreeBuilder::parse_module("Id = x => x;  # identity\n");
+        assert!(!format!("{:?}", result).contains("Trivia"));
     }
 }
@@ -1,5 +1,5 @@
 use crate::source::Span;
-use crate::syntax::tokens::Token;
+use crate::syntax::tokens::{Token, TokenKind};
 use std::fmt;
 
 /// A homogeneous (e.g. untyped) tree.
@@ -35,46 +35,263 @@ impl UntypedTree {
         }
     }
 
+    pub fn span(&self) -> &Span {
+        match self {
+            UntypedTree::Inner { span, .. } => span,
+            UntypedTree::Leaf(token) => &token.span,
+        }
+    }
+
+    /// Traverses this tree in pre-order, calling `visitor`'s methods on each
+    /// node. The children of an `Inner` node are skipped if `visit_inner`
+    /// returns `false` for it.
+    pub fn walk(&self, visitor: &mut impl Visitor) {
+        match self {
+            UntypedTree::Inner {
+                kind,
+                span,
+                children,
+            } => {
+                if visitor.visit_inner(kind, span) {
+                    for child in children {
+                        child.walk(visitor);
+                    }
+                }
+            }
+            UntypedTree::Leaf(token) => visitor.visit_leaf(token),
+        }
+    }
+
+    /// Checks that this tree is well-formed, as every tree produced by the
+    /// parser should be:
+    /// - every child's span lies within its parent's span,
+    /// - the children of a node don't overlap, and are in order,
+    /// - each leaf starts exactly where the previous leaf ended, and
+    /// - `Missing` nodes have no children, while `BadName` nodes have exactly
+    ///   one (the offending token).
+    pub fn validate(&self) -> Result<(), StructuralError> {
+        self.validate_from(&mut None)
+    }
+
+    /// Validates this tree, where `leaf_end` holds the end of the last leaf
+    /// visited so far (if any).
+    fn validate_from(&self, leaf_end: &mut Option<usize>) -> Result<(), StructuralError> {
+        match self {
+            UntypedTree::Inner {
+                kind,
+                span,
+                children,
+            } => {
+                let arity_ok = match kind {
+                    SyntaxKind::Missing => children.is_empty(),
+                    SyntaxKind::BadName => children.len() == 1,
+                    _ => true,
+                };
+                if !arity_ok {
+                    return Err(StructuralError::WrongArity {
+                        kind: kind.clone(),
+                        span: span.clone(),
+                        children: children.len(),
+                    });
+                }
+
+                let mut prev_end = span.start;
+                for child in children {
+                    let child_span = child.span();
+                    if child_span.start < span.start || child_span.end > span.end {
+                        return Err(StructuralError::ChildOutsideParent {
+                            kind: kind.clone(),
+                            span: span.clone(),
+                            child: child_span.clone(),
+                        });
+                    }
+                    if child_span.start < prev_end {
+                        return Err(StructuralError::ChildrenOutOfOrder {
+                            kind: kind.clone(),
+                            span: span.clone(),
+                            child: child_span.clone(),
+                        });
+                    }
+                    prev_end = child_span.end;
+                    child.validate_from(leaf_end)?;
+                }
+                Ok(())
+            }
+            UntypedTree::Leaf(token) => match leaf_end {
+                Some(end) if *end != token.span.start => Err(StructuralError::LeafGap {
+                    kind: token.kind,
+                    span: token.span.clone(),
+                    expected_start: *end,
+                }),
+                _ => {
+                    *leaf_end = Some(token.span.end);
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    /// Reconstructs the source text this tree was parsed from, trivia and
+    /// all. The result is byte-identical to the parser's input.
+    pub fn to_source(&self) -> String {
+        let mut out = String::new();
+        self.write_source(&mut out);
+        out
+    }
+
+    fn write_source(&self, out: &mut String) {
+        match self {
+            UntypedTree::Inner { children, .. } => {
+                for child in children {
+                    child.write_source(out);
+                }
+            }
+            UntypedTree::Leaf(Token { kind, text, .. }) => match kind {
+                // A string's text is written as-is, but without its quotes
+                TokenKind::String | TokenKind::UnterminatedString => {
+                    out.push('"');
+                    out.push_str(text);
+                    if *kind == TokenKind::String {
+                        out.push('"');
+                    }
+                }
+                _ => out.push_str(text),
+            },
+        }
+    }
+
+    /// Renders this tree like its `Debug` form, but without any whitespace,
+    /// line continuation, or comment leaves, so that only its structure (and
+    /// meaningful tokens) remain.
+    pub fn debug_pruned(&self) -> String {
+        let mut out = String::new();
+        self.write_debug(&mut out, 0, true)
+            .expect("writing to a String can't fail");
+        out
+    }
+
     fn fmt_debug(&self, f: &mut fmt::Formatter, level: usize) -> fmt::Result {
-        Self::indent(f, level)?;
+        self.write_debug(f, level, false)
+    }
 
+    fn write_debug(&self, out: &mut impl fmt::Write, level: usize, pruned: bool) -> fmt::Result {
         match self {
             UntypedTree::Inner {
                 kind,
                 span,
                 children,
             } => {
-                writeln!(f, "{:?}@{:?}", kind, span)?;
+                Self::indent(out, level)?;
+                writeln!(out, "{:?}@{:?}", kind, span)?;
                 for child in children {
-                    child.fmt_debug(f, level + 1)?;
+                    child.write_debug(out, level + 1, pruned)?;
                 }
                 Ok(())
             }
+            UntypedTree::Leaf(Token {
+                kind: TokenKind::Whitespace | TokenKind::LineContinuation | TokenKind::Comment,
+                ..
+            }) if pruned => Ok(()),
             UntypedTree::Leaf(Token {
                 kind,
                 text,
                 span: info,
             }) => {
-                writeln!(f, r#"{:?}("{}")@{:?}"#, kind, text, info)
+                Self::indent(out, level)?;
+                writeln!(out, r#"{:?}("{}")@{:?}"#, kind, text, info)
             }
         }
     }
 
     #[inline]
-    fn indent(f: &mut fmt::Formatter, level: usize) -> fmt::Result {
+    fn indent(out: &mut impl fmt::Write, level: usize) -> fmt::Result {
         for _ in 0..level {
-            write!(f, "  ")?;
+            write!(out, "  ")?;
         }
         Ok(())
     }
 }
 
+/// A traversal over an `UntypedTree` (see `UntypedTree::walk`). Every method
+/// does nothing by default, so implementors only override the ones they need.
+pub trait Visitor {
+    /// Visits an `Inner` node, producing whether or not its children should be
+    /// visited as well.
+    fn visit_inner(&mut self, _kind: &SyntaxKind, _span: &Span) -> bool {
+        true
+    }
+
+    fn visit_leaf(&mut self, _token: &Token) {}
+}
+
+/// A violation of one of the properties checked by `UntypedTree::validate`.
+/// Each names the kind and span of the offending node.
+#[derive(Debug, PartialEq)]
+pub enum StructuralError {
+    /// The span of one of this node's children isn't contained in its own.
+    ChildOutsideParent {
+        kind: SyntaxKind,
+        span: Span,
+        child: Span,
+    },
+    /// One of this node's children starts before its previous sibling ends.
+    ChildrenOutOfOrder {
+        kind: SyntaxKind,
+        span: Span,
+        child: Span,
+    },
+    /// This leaf doesn't start where the previous leaf ended.
+    LeafGap {
+        kind: TokenKind,
+        span: Span,
+        expected_start: usize,
+    },
+    /// This node has the wrong number of children for its kind.
+    WrongArity {
+        kind: SyntaxKind,
+        span: Span,
+        children: usize,
+    },
+}
+
+impl fmt::Display for StructuralError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StructuralError::ChildOutsideParent { kind, span, child } => write!(
+                f,
+                "{:?}@{:?} has a child outside of its span, at {:?}",
+                kind, span, child
+            ),
+            StructuralError::ChildrenOutOfOrder { kind, span, child } => write!(
+                f,
+                "{:?}@{:?} has a child that overlaps (or precedes) its previous sibling, at {:?}",
+                kind, span, child
+            ),
+            StructuralError::LeafGap {
+                kind,
+                span,
+                expected_start,
+            } => write!(
+                f,
+                "{:?}@{:?} doesn't start where the previous leaf ended ({})",
+                kind, span, expected_start
+            ),
+            StructuralError::WrongArity {
+                kind,
+                span,
+                children,
+            } => write!(f, "{:?}@{:?} has {} children", kind, span, children),
+        }
+    }
+}
+
 /// The possible types that a tree (specifically, an `Inner` node) might have.
 /// These are intended to demarcate the important parts of syntax that will
 /// later be extracted into a struct.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SyntaxKind {
     ReplInput,
+    Command,
     Module,
     Def,
     Import,
@@ -83,9 +300,165 @@ pub enum SyntaxKind {
     Tms,
     Var,
     Alias,
+    Qualified,
     Abs,
     AbsVars,
+    Let,
     Name,
     BadName,
     Missing,
+    /// A run of trivia (whitespace, comments, etc.), which is only grouped
+    /// into a node by `TreeBuilder::with_trivia_nodes`.
+    Trivia,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tree_builder::TreeBuilder;
+    use super::super::ParseResult;
+    use super::*;
+
+    /// Counts the abstractions in a tree, optionally without looking inside
+    /// any of them.
+    struct AbsCounter {
+        count: usize,
+        outermost_only: bool,
+    }
+
+    impl Visitor for AbsCounter {
+        fn visit_inner(&mut self, kind: &SyntaxKind, _: &Span) -> bool {
+            if *kind == SyntaxKind::Abs {
+                self.count += 1;
+                return !self.outermost_only;
+            }
+            true
+        }
+    }
+
+    fn count_abs(source: &str, outermost_only: bool) -> usize {
+        let ParseResult { result, .. } = TreeBuilder::parse_module(source);
+        let mut counter = AbsCounter {
+            count: 0,
+            outermost_only,
+        };
+        result.walk(&mut counter);
+        counter.count
+    }
+
+    #[test]
+    fn walk_visits_every_node() {
+        assert_eq!(count_abs("K = x => y => x; Id = x => x;", false), 3);
+    }
+
+    #[test]
+    fn walk_skips_subtrees_on_request() {
+        assert_eq!(count_abs("K = x => y => x; Id = x => x;", true), 2);
+    }
+
+    fn round_trip(source: &str) -> String {
+        let ParseResult { result, .. } = TreeBuilder::parse_module(source);
+        result.to_source()
+    }
+
+    #[test]
+    fn reproduces_the_parsed_source_exactly() {
+        let source = r#"# Some combinators
+import { Pair } from "./pairs";
+
+Id = x => x;
+K  = (x, y) => x;   #{ constant }#
+S  = (x, y, z) =>
+    x z (y z);
+"#;
+
+        assert_eq!(round_trip(source), source);
+    }
+
+    #[test]
+    fn reproduces_escapes_and_incomplete_input() {
+        let source = r#"import { A } from "./a\"b\\c";
+Id = x =>;
+import { B } from "./unterminated"#;
+
+        assert_eq!(round_trip(source), source);
+    }
+
+    #[test]
+    fn reproduces_strings_exactly_as_written() {
+        let sources = [
+            r#"import { A } from "a\tb";"#,
+            "import { A } from \"a\tb\";",
+            r#"import { A } from "\q\n";"#,
+            "import { A } from \"a\\\nb\";",
+            "import { A } from \"a\nb\";",
+        ];
+        for source in sources {
+            assert_eq!(round_trip(source), source);
+        }
+    }
+
+    #[test]
+    fn parsed_trees_are_well_formed() {
+        let sources = [
+            "K = x => y => x; Id = x => x;",
+            "import { Pair, bad } from \"./pairs\"; P = Pair General.K;",
+            "Foo = ; Bar = ( ) x; Baz = (x => x",
+            "= x; Id = \\x => let y = x in y; ?? ; #{ unterminated",
+            "",
+        ];
+
+        for source in sources {
+            let ParseResult { result, .. } = TreeBuilder::parse_module(source);
+            assert_eq!(result.validate(), Ok(()), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn validation_names_the_offending_node() {
+        let leaf = |kind, start, end| {
+            UntypedTree::Leaf(Token::new(
+                kind,
+                std::rc::Rc::new(String::new()),
+                Span::new(start, end),
+            ))
+        };
+
+        let gap = UntypedTree::Inner {
+            kind: SyntaxKind::Tms,
+            span: Span::new(0, 3),
+            children: vec![leaf(TokenKind::Var, 0, 1), leaf(TokenKind::Var, 2, 3)],
+        };
+        assert_eq!(
+            gap.validate(),
+            Err(StructuralError::LeafGap {
+                kind: TokenKind::Var,
+                span: Span::new(2, 3),
+                expected_start: 1,
+            })
+        );
+
+        let outside = UntypedTree::Inner {
+            kind: SyntaxKind::Var,
+            span: Span::new(0, 1),
+            children: vec![leaf(TokenKind::Var, 0, 2)],
+        };
+        assert_eq!(
+            outside.validate().unwrap_err().to_string(),
+            "Var@0..1 has a child outside of its span, at 0..2"
+        );
+
+        let missing = UntypedTree::Inner {
+            kind: SyntaxKind::Missing,
+            span: Span::new(0, 1),
+            children: vec![leaf(TokenKind::Var, 0, 1)],
+        };
+        assert_eq!(
+            missing.validate(),
+            Err(StructuralError::WrongArity {
+                kind: SyntaxKind::Missing,
+                span: Span::new(0, 1),
+                children: 1,
+            })
+        );
+    }
 }
@@ -1,7 +1,8 @@
 use crate::source::Span;
+use std::ops::Index;
 use std::rc::Rc;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
     pub text: Rc<String>,
@@ -20,32 +21,97 @@ impl Token {
     pub fn is_nontrivial(&self) -> bool {
         self.kind.is_nontrivial()
     }
+
+    pub fn is_skippable(&self) -> bool {
+        self.kind.is_skippable()
+    }
+
+    /// Tests if this token is the keyword whose interned text is `keyword`
+    /// (keywords lex as vars). Tokens read by the lexer that interned
+    /// `keyword` share its text, so this is usually a pointer comparison; it
+    /// falls back to comparing text for tokens built some other way.
+    pub fn is_keyword(&self, keyword: &Rc<String>) -> bool {
+        self.kind == TokenKind::Var && (Rc::ptr_eq(&self.text, keyword) || *self.text == **keyword)
+    }
+}
+
+/// The vars that have a special meaning in some contexts.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Keyword {
+    Import,
+    From,
+    Let,
+    In,
+}
+
+impl Keyword {
+    pub const ALL: [Keyword; 4] = [Keyword::Import, Keyword::From, Keyword::Let, Keyword::In];
+
+    pub fn text(self) -> &'static str {
+        match self {
+            Keyword::Import => "import",
+            Keyword::From => "from",
+            Keyword::Let => "let",
+            Keyword::In => "in",
+        }
+    }
+}
+
+/// The interned text of each `Keyword`, indexed by keyword.
+#[derive(Debug, Clone)]
+pub struct Keywords([Rc<String>; 4]);
+
+impl Keywords {
+    /// Interns the text of every keyword using `intern`.
+    pub fn new(mut intern: impl FnMut(&'static str) -> Rc<String>) -> Self {
+        Keywords(Keyword::ALL.map(|keyword| intern(keyword.text())))
+    }
+}
+
+impl Index<Keyword> for Keywords {
+    type Output = Rc<String>;
+
+    fn index(&self, keyword: Keyword) -> &Rc<String> {
+        &self.0[keyword as usize]
+    }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum TokenKind {
-    LParen,             // (
-    RParen,             // )
-    LBrace,             // {
-    RBrace,             // }
-    Comma,              // ,
-    Semi,               // ;
-    Equals,             // =
-    Arrow,              // =>
-    Var,                // [a-z][a-zA-Z0-9*+']*
-    Alias,              // [A-Z][a-zA-Z0-9*+']*
-    String,             // ".."
-    UnterminatedString, // "..
-    Comment,            // # ..
-    Whitespace,         // ' ' | \t | \n | \r | \r\n
-    Eof,                //
-    Unknown,            //
+    LParen,              // (
+    RParen,              // )
+    LBrace,              // {
+    RBrace,              // }
+    Comma,               // ,
+    Semi,                // ;
+    Colon,               // :
+    Dot,                 // .
+    Equals,              // =
+    Arrow,               // =>
+    Lambda,              // \
+    LineContinuation,    // \ (immediately followed by a line break)
+    Var,                 // [a-z][a-zA-Z0-9*+'?]* | _
+    Alias,               // [A-Z][a-zA-Z0-9*+'?]*
+    Number,              // [0-9]+
+    String,              // ".."
+    UnterminatedString,  // "..
+    Comment,             // # .. | #{ .. }#
+    UnterminatedComment, // #{ ..
+    Whitespace,          // ' ' | \t | \n | \r | \r\n
+    Eof,                 //
+    Unknown,             //
 }
 
 impl TokenKind {
+    /// Tests if this kind of token is trivia, i.e. whitespace or a comment. A
+    /// line continuation only records where the author chose to break a long
+    /// line, so it counts as whitespace.
     pub fn is_trivial(&self) -> bool {
         match self {
-            Self::Whitespace | Self::Comment | Self::Unknown => true,
+            Self::Whitespace
+            | Self::LineContinuation
+            | Self::Comment
+            | Self::UnterminatedComment => true,
             _ => false,
         }
     }
@@ -53,4 +119,16 @@ impl TokenKind {
     pub fn is_nontrivial(&self) -> bool {
         !self.is_trivial()
     }
+
+    /// Tests if the parser passes over tokens of this kind wherever they
+    /// appear: trivia, along with stray tokens (which it reports). A ':' only
+    /// means something at the start of REPL input (where it introduces a
+    /// command), and a '.' only between the parts of a qualified name, so
+    /// elsewhere they're treated just like unknown tokens.
+    pub fn is_skippable(&self) -> bool {
+        match self {
+            Self::Colon | Self::Dot | Self::Unknown => true,
+            _ => self.is_trivial(),
+        }
+    }
 }